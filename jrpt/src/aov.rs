@@ -0,0 +1,55 @@
+use crate::{
+    objects::Intersection,
+    scene::Scene,
+    ray::Ray,
+    colour::Colour
+};
+
+/// Arbitrary output variables, buffers describing the first surface seen through each pixel
+/// that are written alongside the rendered image. Pixels where camera rays hit nothing are zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    Depth,          // distance from the camera, in every channel
+    Normal,         // unit world space normal, facing the camera
+    Albedo,         // base colour of the material
+    Position,       // world space position
+    Uv,             // texture coordinates in red and green
+    ObjectId,       // id of the object hit, see Intersection::object_id
+    MaterialId      // id of the material hit, see Scene::material_id
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::Position, Aov::Uv, Aov::ObjectId, Aov::MaterialId];
+
+    /// Short lower case name, for file names
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id"
+        }
+    }
+
+    /// Whether samples are averaged over the pixel. Ids are not, as the average of two ids means nothing,
+    /// so pixels keep the id of their first sample
+    pub(crate) fn is_filtered(&self) -> bool {
+        !matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+
+    /// Returns the value of this AOV for camera ray r hitting rec in scene
+    pub(crate) fn evaluate(&self, scene: &Scene, r: &Ray, rec: &Intersection) -> Colour {
+        match self {
+            Aov::Depth => Colour::from_value(rec.t * r.dir.length()),
+            Aov::Normal => rec.n.normalized(),
+            Aov::Albedo => rec.material.albedo(rec),
+            Aov::Position => rec.p,
+            Aov::Uv => Colour::new(rec.u, rec.v, 0.0),
+            Aov::ObjectId => Colour::from_value(rec.object_id as f64),
+            Aov::MaterialId => Colour::from_value(scene.material_id(&rec.material) as f64)
+        }
+    }
+}
@@ -0,0 +1,386 @@
+/// Bidirectional path tracing
+/*
+ Paths are built from a camera subpath and a light subpath, and every way of
+ connecting a prefix of one to a prefix of the other is weighted against the
+ others with the balance heuristic. Strategies that connect light subpaths
+ directly to the camera (splatting onto other pixels) are not used, so light
+ seen directly by the camera is only found by the camera subpath.
+*/
+use std::f64::consts::PI;
+use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
+    scene::Scene,
+    ray::Ray,
+    constants::{EPSILON, INFINITY},
+    objects::{Intersection, object_list},
+    renderer::russian_roulette,
+    random::random_unit_vector,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+/// Vertex of a camera or light subpath
+struct Vertex {
+    rec: Intersection,
+    ray_in: Ray,            // ray the subpath arrived along, unused for the light origin
+    beta: Colour,           // throughput of the subpath up to this vertex
+    pdf_fwd: f64,           // area density of sampling this vertex from the previous vertex of its subpath
+    pdf_rev: f64,           // area density of sampling this vertex from the next vertex, walking the other way
+    delta: bool             // scatters in discrete directions only, so can not be connected to
+}
+
+/// Pdfs of a vertex as used by the MIS weights
+#[derive(Clone, Copy)]
+struct VertexPdfs {
+    fwd: f64,
+    rev: f64,
+    delta: bool
+}
+
+impl Vertex {
+    fn pdfs(&self) -> VertexPdfs {
+        VertexPdfs { fwd: self.pdf_fwd, rev: self.pdf_rev, delta: self.delta }
+    }
+}
+
+/// Traces camera ray r through the scene, returning the light carried back along it.
+/// Subpaths have at most depth vertices besides the camera, and paths at most depth segments
+pub(crate) fn trace(rng: &mut dyn Sampler, scene: &Scene, r: Ray, depth: u32, roulette_depth: u32) -> Colour {
+    let time = r.time;
+    let depth = depth as usize;
+
+    let mut camera_path = vec![];
+    let escaped = random_walk(rng, scene, r, (Colour::from_value(1.0), 0.0), (depth, roulette_depth), &mut camera_path);
+
+    stats::count(Counter::Paths);
+    stats::add(Counter::PathVertices, camera_path.len() as u64);
+
+    // the background can only be found by the camera subpath
+    let mut radiance = match escaped {
+        Some(beta) => beta * scene.background_colour,
+        None => Colour::zero()
+    };
+
+    let mut light_path = vec![];
+    if !object_list::is_empty(&scene.lights) {
+        light_subpath(rng, scene, time, depth, roulette_depth, &mut light_path);
+    }
+
+    for t in 1..=camera_path.len() {
+        for s in 0..=light_path.len().max(1) {
+            if s + t > depth {
+                break;
+            }
+
+            radiance += connect(rng, scene, &camera_path, &light_path, s, t, time);
+        }
+    }
+
+    radiance
+}
+
+/// Samples a light subpath starting on a point of one of the scene's lights
+fn light_subpath(rng: &mut dyn Sampler, scene: &Scene, time: f64, depth: usize, roulette_depth: u32, path: &mut Vec<Vertex>) {
+    let lights = &scene.lights;
+
+    let (rec, pdf_area) = match (lights.sample_point)(lights, rng, time) {
+        Some(sample) => sample,
+        None => return
+    };
+
+    let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+
+    if pdf_area <= 0.0 || is_black(&emitted) {
+        return;
+    }
+
+    let dir = emission_direction(rng, &rec.n);
+    let pdf_dir = emission_pdf(&rec, &(rec.p + dir));
+    let ray = Ray::new(rec.p, dir, time);
+
+    path.push(Vertex {
+        ray_in: ray.clone(),
+        beta: emitted / pdf_area,
+        pdf_fwd: pdf_area,
+        pdf_rev: 0.0,
+        delta: false,
+        rec
+    });
+
+    // cosine over pdf_dir is 2pi
+    random_walk(rng, scene, ray, (emitted * (2.0 * PI / pdf_area), pdf_dir), (depth, roulette_depth), path);
+
+    // MIS measures the origin the same way whichever subpath found it
+    if path.len() >= 2 {
+        path[0].pdf_fwd = light_origin_pdf(rng, scene, &path[0].rec, &path[1].rec.p, time);
+    }
+}
+
+/// Extends path along ray, which carries throughput beta and whose direction was sampled with pdf_dir
+/// (solid angle) at the last vertex, until it has at most max_vertices vertices. Returns the throughput of the ray if it escaped the scene
+fn random_walk(rng: &mut dyn Sampler, scene: &Scene, r: Ray, (beta, pdf_dir): (Colour, f64), (max_vertices, roulette_depth): (usize, u32), path: &mut Vec<Vertex>) -> Option<Colour> {
+    let start = path.len();
+    let mut ray = r;
+    let mut beta = beta;
+    let mut pdf_fwd = pdf_dir;
+
+    while path.len() < max_vertices {
+        // camera subpaths start empty, light subpaths with their point on the light
+        stats::count(if path.is_empty() { Counter::PrimaryRays } else { Counter::SecondaryRays });
+        let rec = (scene.objects.intersect)(&scene.objects, rng, &ray, EPSILON, INFINITY);
+
+        let rec = match rec {
+            Some(rec) => rec,
+            None => return Some(beta)
+        };
+
+        let scatter = rec.material.scatter(rng, ray.clone(), &rec);
+        let vertex_pdf_fwd = to_area(pdf_fwd, &ray.origin, &rec);
+
+        let (attenuation, scattered) = match scatter {
+            Some(scatter) => scatter,
+            None => {
+                path.push(Vertex { rec, ray_in: ray, beta, pdf_fwd: vertex_pdf_fwd, pdf_rev: 0.0, delta: false });
+                return None;
+            }
+        };
+        stats::count(Counter::ScatterEvents);
+
+        // pdfs of scattering forwards along the subpath and backwards against it
+        let delta = rec.material.eval(&ray, &rec, &scattered.dir).is_none();
+        let pdf_rev = if delta {
+            pdf_fwd = 0.0;
+            0.0
+        } else {
+            pdf_fwd = rec.material.pdf(&ray, &rec, &scattered.dir);
+            rec.material.pdf(&Ray::new(rec.p, -scattered.dir, ray.time), &rec, &(-ray.dir))
+        };
+
+        if let Some(prev) = path.last_mut() {
+            prev.pdf_rev = to_area(pdf_rev, &rec.p, &prev.rec);
+        }
+
+        path.push(Vertex { rec, ray_in: ray, beta, pdf_fwd: vertex_pdf_fwd, pdf_rev: 0.0, delta });
+
+        beta = beta * attenuation;
+
+        // same point in the path as the unidirectional tracer starts russian roulette
+        if path.len() - start > roulette_depth as usize {
+            match russian_roulette(rng, &beta) {
+                Some(scale) => beta *= scale,
+                None => return None
+            }
+        }
+
+        ray = scattered;
+    }
+
+    None
+}
+
+/// Returns the contribution of the path made of the first t camera vertices and the first s light vertices,
+/// weighted against the other ways of sampling the same path
+fn connect(rng: &mut dyn Sampler, scene: &Scene, camera_path: &[Vertex], light_path: &[Vertex], s: usize, t: usize, time: f64) -> Colour {
+    let z = &camera_path[t - 1];
+    let mut camera_pdfs: Vec<VertexPdfs> = camera_path[..t].iter().map(Vertex::pdfs).collect();
+
+    match s {
+        // camera subpath found a light
+        0 => {
+            let emitted = z.rec.material.emitted(z.rec.u, z.rec.v, &z.rec.p);
+            if is_black(&emitted) {
+                return Colour::zero();
+            }
+
+            let prev = z.ray_in.origin;
+            let origin_pdf = light_origin_pdf(rng, scene, &z.rec, &prev, time);
+
+            // lights that can not be sampled are only found this way
+            if origin_pdf <= 0.0 {
+                return z.beta * emitted;
+            }
+
+            camera_pdfs[t - 1].rev = origin_pdf;
+            if t >= 2 {
+                camera_pdfs[t - 2].rev = to_area(emission_pdf(&z.rec, &prev), &z.rec.p, &camera_path[t - 2].rec);
+            }
+
+            z.beta * emitted * mis_weight(&camera_pdfs, &[])
+        },
+        // sample a point on a light from the last camera vertex
+        1 => {
+            if z.delta {
+                return Colour::zero();
+            }
+
+            let lights = &scene.lights;
+            let dir = match (lights.sample)(lights, rng, &z.rec.p, time) {
+                Some(dir) => dir,
+                None => return Colour::zero()
+            };
+
+            let light_pdf = (lights.pdf_value)(lights, rng, &z.rec.p, &dir, time);
+            let f = match z.rec.material.eval(&z.ray_in, &z.rec, &dir) {
+                Some(f) => f,
+                None => return Colour::zero()
+            };
+
+            if light_pdf <= 0.0 || is_black(&f) {
+                return Colour::zero();
+            }
+
+            stats::count(Counter::ShadowRays);
+            let light = match (scene.objects.intersect)(&scene.objects, rng, &Ray::new(z.rec.p, dir, time), EPSILON, INFINITY) {
+                Some(rec) => rec,
+                None => return Colour::zero()
+            };
+
+            let emitted = light.material.emitted(light.u, light.v, &light.p);
+            if is_black(&emitted) {
+                return Colour::zero();
+            }
+
+            let light_pdfs = [VertexPdfs {
+                fwd: to_area(light_pdf, &z.rec.p, &light),
+                rev: to_area(z.rec.material.pdf(&z.ray_in, &z.rec, &dir), &z.rec.p, &light),
+                delta: false
+            }];
+
+            camera_pdfs[t - 1].rev = to_area(emission_pdf(&light, &z.rec.p), &light.p, &z.rec);
+            if t >= 2 {
+                let prev = &camera_path[t - 2].rec;
+                let pdf = z.rec.material.pdf(&Ray::new(light.p, -dir, time), &z.rec, &(prev.p - z.rec.p));
+                camera_pdfs[t - 2].rev = to_area(pdf, &z.rec.p, prev);
+            }
+
+            z.beta * f * emitted / light_pdf * mis_weight(&camera_pdfs, &light_pdfs)
+        },
+        // join the last vertices of both subpaths
+        _ => {
+            let y = &light_path[s - 1];
+            if y.delta || z.delta {
+                return Colour::zero();
+            }
+
+            let d = y.rec.p - z.rec.p;
+
+            let f_z = z.rec.material.eval(&z.ray_in, &z.rec, &d);
+            let f_y = y.rec.material.eval(&y.ray_in, &y.rec, &(-d));
+
+            let contribution = match (f_z, f_y) {
+                (Some(f_z), Some(f_y)) => z.beta * f_z * f_y * y.beta / d.length_squared(),
+                _ => return Colour::zero()
+            };
+
+            if is_black(&contribution) {
+                return Colour::zero();
+            }
+
+            // direction d is not normalized, so the light vertex is at t = 1
+            stats::count(Counter::ShadowRays);
+            if (scene.objects.intersect)(&scene.objects, rng, &Ray::new(z.rec.p, d, time), EPSILON, 1.0 - EPSILON).is_some() {
+                return Colour::zero();
+            }
+
+            let mut light_pdfs: Vec<VertexPdfs> = light_path[..s].iter().map(Vertex::pdfs).collect();
+
+            light_pdfs[s - 1].rev = to_area(z.rec.material.pdf(&z.ray_in, &z.rec, &d), &z.rec.p, &y.rec);
+            camera_pdfs[t - 1].rev = to_area(y.rec.material.pdf(&y.ray_in, &y.rec, &(-d)), &y.rec.p, &z.rec);
+
+            let prev = &light_path[s - 2].rec;
+            let pdf = y.rec.material.pdf(&Ray::new(z.rec.p, d, time), &y.rec, &(prev.p - y.rec.p));
+            light_pdfs[s - 2].rev = to_area(pdf, &y.rec.p, prev);
+
+            if t >= 2 {
+                let prev = &camera_path[t - 2].rec;
+                let pdf = z.rec.material.pdf(&Ray::new(y.rec.p, -d, time), &z.rec, &(prev.p - z.rec.p));
+                camera_pdfs[t - 2].rev = to_area(pdf, &z.rec.p, prev);
+            }
+
+            contribution * mis_weight(&camera_pdfs, &light_pdfs)
+        }
+    }
+}
+
+/// Balance heuristic weight of the strategy joining the given camera and light subpaths, found
+/// from the ratios of the pdfs of the other strategies that could have sampled the same path
+fn mis_weight(camera: &[VertexPdfs], light: &[VertexPdfs]) -> f64 {
+    // delta vertices have no density, their ratios cancel and their strategies are skipped
+    let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+
+    let mut sum = 0.0;
+
+    // give camera vertices to the light subpath, always keeping the first one
+    let mut ratio = 1.0;
+    for i in (1..camera.len()).rev() {
+        ratio *= remap(camera[i].rev) / remap(camera[i].fwd);
+
+        if !camera[i].delta && !camera[i - 1].delta {
+            sum += ratio;
+        }
+    }
+
+    // give light vertices to the camera subpath
+    let mut ratio = 1.0;
+    for i in (0..light.len()).rev() {
+        ratio *= remap(light[i].rev) / remap(light[i].fwd);
+
+        if !light[i].delta && (i == 0 || !light[i - 1].delta) {
+            sum += ratio;
+        }
+    }
+
+    1.0 / (1.0 + sum)
+}
+
+/// Samples a direction for light leaving a light with normal n. Lights emit from both sides,
+/// so this is cosine weighted on a random side, with pdf emission_pdf
+pub(crate) fn emission_direction(rng: &mut dyn Sampler, n: &Vec3) -> Vec3 {
+    let mut dir = *n + random_unit_vector(rng);
+    if dir.near_zero() {
+        dir = *n;
+    }
+
+    if rng.get_1d() < 0.5 { -dir } else { dir }
+}
+
+/// Area density at the light point rec of the light subpath starting there, measured from point from.
+/// Uses the pdf of sampling lights towards rec, so it is the same for every strategy
+fn light_origin_pdf(rng: &mut dyn Sampler, scene: &Scene, rec: &Intersection, from: &Point3, time: f64) -> f64 {
+    let lights = &scene.lights;
+    let pdf = (lights.pdf_value)(lights, rng, from, &(rec.p - *from), time);
+
+    to_area(pdf, from, rec)
+}
+
+/// Pdf (with respect to solid angle) of a light at rec emitting towards point to
+pub(crate) fn emission_pdf(rec: &Intersection, to: &Point3) -> f64 {
+    let dir = *to - rec.p;
+
+    (rec.n.dot(&dir) / (rec.n.length() * dir.length())).abs() / (2.0 * PI)
+}
+
+/// Converts pdf with respect to solid angle at point from into a pdf with respect to area at rec
+fn to_area(pdf: f64, from: &Point3, rec: &Intersection) -> f64 {
+    let dir: Vec3 = rec.p - *from;
+    let distance_squared = dir.length_squared();
+
+    if distance_squared <= 0.0 {
+        return 0.0;
+    }
+
+    // no surface inside media, so no foreshortening
+    let cosine = if rec.material.is_volumetric() {
+        1.0
+    } else {
+        (rec.n.dot(&dir) / (rec.n.length() * distance_squared.sqrt())).abs()
+    };
+
+    pdf * cosine / distance_squared
+}
+
+fn is_black(col: &Colour) -> bool {
+    col.x <= 0.0 && col.y <= 0.0 && col.z <= 0.0
+}
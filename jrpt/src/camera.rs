@@ -1,6 +1,6 @@
 use std::ops::Range;
-use rand::{rngs::SmallRng, Rng};
 use crate::{
+    sampler::Sampler,
     point3::Point3, 
     vec3::Vec3, 
     ray::Ray, 
@@ -58,8 +58,23 @@ impl Camera {
         }
     }
 
+    /// Widens or narrows the view to fit an image of the given aspect ratio, keeping the vertical field of view
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f64) {
+        let horizontal = aspect_ratio / self.aspect_ratio * self.horizontal;
+
+        self.lower_left_corner += (self.horizontal - horizontal) / 2.0;
+        self.horizontal = horizontal;
+        self.aspect_ratio = aspect_ratio;
+    }
+
+    /// Mirrors the image left to right, for scenes made for left-handed cameras
+    pub fn mirror(&mut self) {
+        self.lower_left_corner += self.horizontal;
+        self.horizontal = -self.horizontal;
+    }
+
     // get new ray to trace from this camera
-    pub fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Ray {
+    pub fn get_ray(&self, rng: &mut dyn Sampler, s: f64, t: f64) -> Ray {
         let origin: Point3;
         let dir: Vec3;
         
@@ -74,8 +89,11 @@ impl Camera {
             dir = &self.lower_left_corner + s * &self.horizontal + t * &self.vertical - &self.origin - &offset;
         }
         
-        let ray_time = if self.time.is_empty() { 0.0 } else { rng.gen_range(self.time.clone()) };
+        Ray::new(origin, dir, self.random_time(rng))
+    }
 
-        Ray::new(origin, dir, ray_time)
+    /// Returns a random time while the shutter is open
+    pub fn random_time(&self, rng: &mut dyn Sampler) -> f64 {
+        if self.time.is_empty() { 0.0 } else { self.time.start + rng.get_1d() * (self.time.end - self.time.start) }
     }
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+/// Checkpoints saving the progress of a render so that it can be resumed later
+/*
+ A checkpoint holds the film's sums bit for bit, along with the seed, the number
+ of samples per pass and the share of the render it was taking. Samplers derive
+ every sample's random numbers from the seed, the pixel and the sample's index, so
+ there is no other random state to keep: the samples a pass takes only depend on
+ how many were taken before it, and a resumed render takes exactly the samples
+ the uninterrupted render would have.
+
+ The format is little endian: a magic string and version, the header, then each of
+ the film's buffers in turn.
+*/
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path
+};
+use crate::{
+    aov::Aov,
+    colour::Colour,
+    film::Film,
+    renderer::Partition
+};
+
+const MAGIC: &[u8; 8] = b"JRPTCKPT";
+const VERSION: u32 = 2;
+const COLOUR_SIZE: usize = 3 * 8;
+
+/// Render in progress read back from a checkpoint
+pub struct Checkpoint {
+    pub film: Film,
+    pub seed: u64,              // seed of the renderer that took the samples
+    pub samples_per_pass: u32,  // photon mapping narrows its radius every pass, so passes must line up
+    pub partition: Option<Partition>
+}
+
+/// Saves film to path, replacing the file only once it has been written in full so that
+/// a crash while saving keeps the previous checkpoint.
+/// Films are only complete between passes, so save from render_progressive's callback
+pub fn save<P: AsRef<Path>>(path: P, film: &Film, seed: u64, samples_per_pass: u32, partition: Option<Partition>) -> io::Result<()> {
+    let path = path.as_ref();
+    let partial = path.with_extension("partial");
+
+    let mut file = BufWriter::new(File::create(&partial)?);
+    write(&mut file, film, seed, samples_per_pass, partition)?;
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+    fs::rename(partial, path)
+}
+
+/// Loads a checkpoint saved by save
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+/// Writes film as a checkpoint
+pub fn write<W: Write>(w: &mut W, film: &Film, seed: u64, samples_per_pass: u32, partition: Option<Partition>) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    write_u32(w, VERSION)?;
+
+    write_u64(w, seed)?;
+    write_u32(w, samples_per_pass)?;
+
+    let (kind, (worker, workers)) = match partition {
+        None => (0, (0, 1)),
+        Some(partition @ Partition::Samples { .. }) => (1, partition.worker()),
+        Some(partition @ Partition::Tiles { .. }) => (2, partition.worker())
+    };
+    write_u32(w, kind)?;
+    write_u32(w, worker)?;
+    write_u32(w, workers)?;
+
+    write_u32(w, film.width)?;
+    write_u32(w, film.height)?;
+    write_u32(w, film.samples)?;
+
+    write_u32(w, film.aovs.len() as u32)?;
+    for (aov, _) in &film.aovs {
+        write_u32(w, *aov as u32)?;
+    }
+
+    write_colours(w, &film.weighted_sum)?;
+    write_f64s(w, &film.weights)?;
+    write_colours(w, &film.sum)?;
+    write_f64s(w, &film.sum_sq)?;
+    for &count in &film.counts {
+        write_u32(w, count)?;
+    }
+
+    for (_, sums) in &film.aovs {
+        write_colours(w, sums)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a checkpoint written by write
+pub fn read<R: Read>(r: &mut R) -> io::Result<Checkpoint> {
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a checkpoint"));
+    }
+
+    let version = read_u32(r)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported checkpoint version {}", version)));
+    }
+
+    let seed = read_u64(r)?;
+    let samples_per_pass = read_u32(r)?;
+
+    let (kind, worker, workers) = (read_u32(r)?, read_u32(r)?, read_u32(r)?);
+    let partition = match kind {
+        0 => None,
+        1 => Some(Partition::Samples { worker, workers }),
+        2 => Some(Partition::Tiles { worker, workers }),
+        _ => return Err(invalid_data("unknown partition"))
+    };
+
+    let width = read_u32(r)?;
+    let height = read_u32(r)?;
+    let samples = read_u32(r)?;
+
+    let mut aovs = vec![];
+    for _ in 0..read_u32(r)? {
+        let aov = *Aov::ALL.get(read_u32(r)? as usize).ok_or_else(|| invalid_data("unknown AOV"))?;
+        aovs.push(aov);
+    }
+
+    // the buffers are read in before the film is made, so a corrupt size runs out of data rather than memory
+    let pixels = width.checked_mul(height).ok_or_else(|| invalid_data("film is too large"))?;
+    let pixel_size = ((2 + aovs.len()) * COLOUR_SIZE + 2 * 8 + 4) as u64;     // sums, weights and squares, count, AOVs
+    let size = (pixels as u64).checked_mul(pixel_size).ok_or_else(|| invalid_data("film is too large"))?;
+
+    let mut data = vec![];
+    r.take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(invalid_data("checkpoint ends before its film"));
+    }
+    let r = &mut data.as_slice();
+
+    let mut film = Film::with_aovs(width, height, &aovs);
+    film.samples = samples;
+
+    read_colours(r, &mut film.weighted_sum)?;
+    read_f64s(r, &mut film.weights)?;
+    read_colours(r, &mut film.sum)?;
+    read_f64s(r, &mut film.sum_sq)?;
+    for count in film.counts.iter_mut() {
+        *count = read_u32(r)?;
+    }
+
+    for (_, sums) in film.aovs.iter_mut() {
+        read_colours(r, sums)?;
+    }
+
+    Ok(Checkpoint { film, seed, samples_per_pass, partition })
+}
+
+/// Combines the checkpoints saved by each worker of a partitioned render into one of the whole render,
+/// which can be resumed to add more samples like any other
+pub fn merge(partials: Vec<Checkpoint>) -> io::Result<Checkpoint> {
+    let first = partials.first().ok_or_else(|| invalid_input("no checkpoints to merge"))?;
+    let partition = first.partition.ok_or_else(|| invalid_input("checkpoint is not of a partitioned render"))?;
+    let (seed, samples_per_pass, (_, workers)) = (first.seed, first.samples_per_pass, partition.worker());
+
+    let mut film = Film::with_aovs(first.film.width, first.film.height, &first.film.aovs());
+    let mut merged = vec![false; workers as usize];
+
+    for checkpoint in &partials {
+        let worker = match (checkpoint.partition, partition) {
+            (Some(Partition::Samples { worker, workers: n }), Partition::Samples { .. }) |
+            (Some(Partition::Tiles { worker, workers: n }), Partition::Tiles { .. }) if n == workers => worker,
+            _ => return Err(invalid_input("checkpoints are partitioned differently"))
+        };
+
+        if checkpoint.seed != seed || checkpoint.samples_per_pass != samples_per_pass {
+            return Err(invalid_input("checkpoints were rendered with different settings"));
+        }
+
+        if (checkpoint.film.width, checkpoint.film.height) != (film.width, film.height) || checkpoint.film.aovs() != film.aovs() {
+            return Err(invalid_input("checkpoints are of different films"));
+        }
+
+        match merged.get_mut(worker as usize) {
+            Some(seen @ false) => *seen = true,
+            _ => return Err(invalid_input(&format!("worker {} is missing or merged twice", worker)))
+        }
+
+        film.add_film(&checkpoint.film);
+    }
+
+    if merged.contains(&false) {
+        return Err(invalid_input("checkpoints of some workers are missing"));
+    }
+
+    // workers sharing out samples took a part of every pixel's samples, workers sharing out tiles took all of them
+    film.samples = match partition {
+        Partition::Samples { .. } => partials.iter().map(|checkpoint| checkpoint.film.samples).sum(),
+        _ => partials.iter().map(|checkpoint| checkpoint.film.samples).min().unwrap_or(0)
+    };
+
+    Ok(Checkpoint { film, seed, samples_per_pass, partition: None })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn write_u32<W: Write>(w: &mut W, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_u64<W: Write>(w: &mut W, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_f64s<W: Write>(w: &mut W, values: &[f64]) -> io::Result<()> {
+    for v in values {
+        w.write_all(&v.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn write_colours<W: Write>(w: &mut W, colours: &[Colour]) -> io::Result<()> {
+    for col in colours {
+        write_f64s(w, &[col.x, col.y, col.z])?;
+    }
+
+    Ok(())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(r)?))
+}
+
+fn read_f64s<R: Read>(r: &mut R, values: &mut [f64]) -> io::Result<()> {
+    for v in values.iter_mut() {
+        *v = read_f64(r)?;
+    }
+
+    Ok(())
+}
+
+fn read_colours<R: Read>(r: &mut R, colours: &mut [Colour]) -> io::Result<()> {
+    for col in colours.iter_mut() {
+        *col = Colour::new(read_f64(r)?, read_f64(r)?, read_f64(r)?);
+    }
+
+    Ok(())
+}
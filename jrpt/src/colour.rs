@@ -1,7 +1,8 @@
 use image::Rgb;
 use crate::{
     vec3::Vec3, 
-    utils::clamp
+    utils::clamp,
+    tone_mapping::srgb_oetf
 };
 
 pub type Colour = Vec3;
@@ -11,6 +12,12 @@ impl Colour {
         Self::new(r, g, b)
     }
 
+    /// Returns the relative luminance of the colour, using the Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    /// Encodes the colour as an 8 bit sRGB pixel, clipping values outside [0,1]
     pub fn to_rgb(&self) -> Rgb<u8> {
         let mut r = self.x;
         let mut g = self.y;
@@ -20,9 +27,9 @@ impl Colour {
         if g.is_nan() { g = 0.0; };
         if b.is_nan() { b = 0.0; };
 
-        r = r.sqrt();
-        g = g.sqrt();
-        b = b.sqrt();
+        r = srgb_oetf(r);
+        g = srgb_oetf(g);
+        b = srgb_oetf(b);
 
         Rgb([
             (256.0 * clamp(r, 0.0, 0.999)) as u8, 
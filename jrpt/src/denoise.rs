@@ -0,0 +1,182 @@
+/// Edge-avoiding À-trous wavelet denoising
+/*
+ Follows Dammertz et al., "Edge-Avoiding À-Trous Wavelet Transform for fast
+ Global Illumination Filtering". Each iteration blurs with a 5x5 B3 spline
+ kernel whose taps are spread 2^i pixels apart, so a few iterations cover a
+ wide area. Taps are weighted down when they differ from the centre pixel in
+ normal, depth or albedo, which keeps edges and texture detail sharp, and when
+ they differ in brightness by more than the noise around the centre pixel, as
+ measured by the local variance like in SVGF. The image is divided by the
+ albedo before filtering and multiplied back after, so only the lighting is
+ blurred.
+*/
+use image::{ImageBuffer, Rgb, Rgb32FImage};
+use rayon::prelude::*;
+use crate::{
+    aov::Aov,
+    film::Film,
+    colour::Colour,
+    utils::fmax
+};
+
+// B3 spline weights of the taps at offsets -2..=2
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// albedo below this is not divided out, as for pixels that hit nothing
+const MIN_ALBEDO: f64 = 0.01;
+
+// keeps the luminance weight defined where the image has no noise
+const MIN_SIGMA: f64 = 1e-6;
+
+/// Settings for the denoiser
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    pub iterations: u32,        // each iteration reaches twice as far as the last, starting 2 pixels out
+    pub colour_sigma: f64,      // allowed difference in luminance, in standard deviations of the noise around a pixel
+    pub normal_power: f64,      // sharpness of the falloff with the angle between normals
+    pub depth_sigma: f64,       // allowed difference in depth, relative to the depth of the centre pixel
+    pub albedo_sigma: f64       // allowed difference in albedo
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 3,
+            colour_sigma: 3.0,
+            normal_power: 64.0,
+            depth_sigma: 0.05,
+            albedo_sigma: 0.1
+        }
+    }
+}
+
+/// Per pixel buffers describing the first surface seen, used to find edges.
+/// Pixels that hit nothing are zero in every buffer
+pub struct Guides<'a> {
+    pub albedo: &'a Rgb32FImage,
+    pub normal: &'a Rgb32FImage,
+    pub depth: &'a Rgb32FImage
+}
+
+impl Denoiser {
+    /// Denoises the linear HDR image, guided by the given buffers which must be the same size
+    pub fn denoise(&self, image: &Rgb32FImage, guides: &Guides) -> Rgb32FImage {
+        let (width, height) = image.dimensions();
+        assert!([guides.albedo, guides.normal, guides.depth].iter().all(|guide| guide.dimensions() == (width, height)),
+            "guide buffers must be the same size as the image");
+
+        let albedo = to_colours(guides.albedo);
+        let normal = to_colours(guides.normal);
+        let depth: Vec<f64> = to_colours(guides.depth).iter().map(|d| d.x).collect();
+
+        // filter lighting alone, texture detail is put back at the end
+        let texture: Vec<Colour> = albedo.iter().map(|a| Colour::new(texture_factor(a.x), texture_factor(a.y), texture_factor(a.z))).collect();
+        let mut colour: Vec<Colour> = to_colours(image).into_iter().zip(&texture)
+            .map(|(col, t)| Colour::new(col.x / t.x, col.y / t.y, col.z / t.z))
+            .collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let std_dev = local_std_dev(&colour, width, height);
+
+            colour = (0..colour.len()).into_par_iter().map(|idx| {
+                let (x, y) = ((idx as u32 % width) as i64, (idx as u32 / width) as i64);
+                let luminance_sigma = fmax(self.colour_sigma * std_dev[idx], MIN_SIGMA);
+                let mut sum = Colour::zero();
+                let mut total_weight = 0.0;
+
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (dx as i64 - 2) * step;
+                        let qy = y + (dy as i64 - 2) * step;
+
+                        if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                            continue;
+                        }
+
+                        let q = (qy * width as i64 + qx) as usize;
+                        let weight = kx * ky
+                            * (-(colour[idx].luminance() - colour[q].luminance()).abs() / luminance_sigma).exp()
+                            * normal_weight(normal[idx], normal[q], self.normal_power)
+                            * depth_weight(depth[idx], depth[q], self.depth_sigma * step as f64)
+                            * albedo_weight(albedo[idx], albedo[q], self.albedo_sigma);
+
+                        sum += weight * colour[q];
+                        total_weight += weight;
+                    }
+                }
+
+                // the centre tap always has weight, so total_weight is positive
+                sum / total_weight
+            }).collect();
+        }
+
+        ImageBuffer::from_fn(width, height, |i, j| {
+            let idx = (j * width + i) as usize;
+            let col = colour[idx] * texture[idx];
+
+            Rgb([col.x as f32, col.y as f32, col.z as f32])
+        })
+    }
+
+    /// Denoises the image in film, guided by its albedo, normal and depth AOVs.
+    /// Returns None if the film does not keep all three
+    pub fn denoise_film(&self, film: &Film) -> Option<Rgb32FImage> {
+        let albedo = film.aov_image(Aov::Albedo)?;
+        let normal = film.aov_image(Aov::Normal)?;
+        let depth = film.aov_image(Aov::Depth)?;
+
+        Some(self.denoise(&film.to_hdr_image(), &Guides { albedo: &albedo, normal: &normal, depth: &depth }))
+    }
+}
+
+/// Amount a channel with the given albedo is divided by before filtering
+fn texture_factor(albedo: f64) -> f64 {
+    if albedo > MIN_ALBEDO { albedo } else { 1.0 }
+}
+
+fn to_colours(image: &Rgb32FImage) -> Vec<Colour> {
+    image.pixels().map(|Rgb([r, g, b])| Colour::new(*r as f64, *g as f64, *b as f64)).collect()
+}
+
+/// Standard deviation of luminance over the 3x3 pixels around each pixel
+fn local_std_dev(colour: &[Colour], width: u32, height: u32) -> Vec<f64> {
+    (0..colour.len()).map(|idx| {
+        let (x, y) = ((idx as u32 % width) as i64, (idx as u32 / width) as i64);
+        let (mut sum, mut sum_sq, mut n) = (0.0, 0.0, 0.0);
+
+        for qy in (y - 1).max(0)..=(y + 1).min(height as i64 - 1) {
+            for qx in (x - 1).max(0)..=(x + 1).min(width as i64 - 1) {
+                let l = colour[(qy * width as i64 + qx) as usize].luminance();
+                sum += l;
+                sum_sq += l * l;
+                n += 1.0;
+            }
+        }
+
+        let mean = sum / n;
+        fmax(sum_sq / n - mean * mean, 0.0).sqrt()
+    }).collect()
+}
+
+fn albedo_weight(p: Colour, q: Colour, sigma: f64) -> f64 {
+    (-(p - q).length_squared() / (sigma * sigma)).exp()
+}
+
+fn normal_weight(p: Colour, q: Colour, power: f64) -> f64 {
+    // pixels that hit nothing only blend with each other
+    match (p.length_squared() > 0.0, q.length_squared() > 0.0) {
+        (false, false) => 1.0,
+        // normals averaged over a pixel that is partly background are shorter
+        (true, true) => fmax(p.normalized().dot(&q.normalized()), 0.0).powf(power),
+        _ => 0.0
+    }
+}
+
+fn depth_weight(p: f64, q: f64, sigma: f64) -> f64 {
+    if p <= 0.0 {
+        return if q <= 0.0 { 1.0 } else { 0.0 };
+    }
+
+    (-(p - q).abs() / (sigma * p)).exp()
+}
@@ -0,0 +1,276 @@
+use std::ops::Range;
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use crate::{
+    aov::Aov,
+    colour::Colour,
+    stats::RenderStats,
+    tone_mapping::ToneMapping,
+    utils::fmax
+};
+
+// pixels darker than this use it as their mean when measuring relative error, so that noise in
+// nearly black pixels does not keep them sampling forever
+const MIN_MEAN: f64 = 0.01;
+
+/// Samples taken for a single pixel
+#[derive(Clone, Copy, Default)]
+pub(crate) struct PixelSamples {
+    pub sum: Colour,        // sum of the samples
+    pub sum_sq: f64,        // sum of the squared luminance of the samples
+    pub count: u32,
+    pub aovs: [Colour; Aov::ALL.len()]  // sum of the samples of each AOV, indexed by Aov
+}
+
+impl PixelSamples {
+    pub fn add(&mut self, sample: Colour) {
+        let luminance = sample.luminance();
+
+        self.sum += sample;
+        self.sum_sq += luminance * luminance;
+        self.count += 1;
+    }
+
+    pub fn add_aov(&mut self, aov: Aov, value: Colour) {
+        accumulate_aov(aov, &mut self.aovs[aov as usize], value);
+    }
+}
+
+/// Adds value to the accumulated value of aov, or keeps the first value found for AOVs that are not averaged
+fn accumulate_aov(aov: Aov, acc: &mut Colour, value: Colour) {
+    if aov.is_filtered() {
+        *acc += value;
+    } else if *acc == Colour::zero() {
+        *acc = value;
+    }
+}
+
+/// Samples weighted by a reconstruction filter, summed over a rectangle of pixels
+pub(crate) struct Splats {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    sum: Vec<Colour>,       // sum of weighted samples for each pixel, row by row starting at the top
+    weights: Vec<f64>       // sum of the weights for each pixel
+}
+
+impl Splats {
+    /// Creates empty splats covering pixels x0..x1, y0..y1
+    pub fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        let (width, height) = (x1 - x0, y1 - y0);
+
+        Self {
+            x0, y0, width, height,
+            sum: vec![Colour::zero(); (width * height) as usize],
+            weights: vec![0.0; (width * height) as usize]
+        }
+    }
+
+    /// Adds sample to pixel (x,y) with the given weight, ignoring pixels outside the rectangle
+    pub fn add(&mut self, x: i64, y: i64, sample: Colour, weight: f64) {
+        let (x, y) = (x - self.x0 as i64, y - self.y0 as i64);
+
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+
+        let idx = (y * self.width as i64 + x) as usize;
+        self.sum[idx] += weight * sample;
+        self.weights[idx] += weight;
+    }
+
+    /// Returns the columns and rows of the pixels covered
+    pub fn bounds(&self) -> (Range<i64>, Range<i64>) {
+        let (x0, y0) = (self.x0 as i64, self.y0 as i64);
+        (x0..x0 + self.width as i64, y0..y0 + self.height as i64)
+    }
+
+    /// Returns each pixel with its weighted sum and total weight
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32, Colour, f64)> + '_ {
+        self.sum.iter().zip(&self.weights).enumerate().map(|(k, (sum, weight))| {
+            let k = k as u32;
+            (self.x0 + k % self.width, self.y0 + k / self.width, *sum, *weight)
+        })
+    }
+}
+
+/// Floating point image that samples are accumulated into, possibly over many passes.
+/// The image is the filter weighted average of the samples splatted onto each pixel, while the
+/// statistics used for adaptive sampling and the AOVs only count the samples taken in the pixel itself
+pub struct Film {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) weighted_sum: Vec<Colour>,       // sum of filter weighted samples for each pixel, row by row starting at the top
+    pub(crate) weights: Vec<f64>,               // sum of filter weights for each pixel
+    pub(crate) sum: Vec<Colour>,                // sum of samples taken in each pixel
+    pub(crate) sum_sq: Vec<f64>,                // sum of squared sample luminance for each pixel
+    pub(crate) counts: Vec<u32>,                // number of samples summed for each pixel
+    pub(crate) aovs: Vec<(Aov, Vec<Colour>)>,   // sum of the samples of each AOV kept, for each pixel
+    pub(crate) samples: u32,                    // samples per pixel taken by completed passes
+    pub(crate) stats: RenderStats               // work done rendering into the film
+}
+
+impl Film {
+    /// Creates an empty film with no samples
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_aovs(width, height, &[])
+    }
+
+    /// Creates an empty film that also keeps the given AOVs
+    pub fn with_aovs(width: u32, height: u32, aovs: &[Aov]) -> Self {
+        Self {
+            width,
+            height,
+            weighted_sum: vec![Colour::zero(); (width * height) as usize],
+            weights: vec![0.0; (width * height) as usize],
+            sum: vec![Colour::zero(); (width * height) as usize],
+            sum_sq: vec![0.0; (width * height) as usize],
+            counts: vec![0; (width * height) as usize],
+            aovs: aovs.iter().map(|&aov| (aov, vec![Colour::zero(); (width * height) as usize])).collect(),
+            samples: 0,
+            stats: RenderStats::default()
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the number of samples per pixel taken by completed passes.
+    /// Pixels may have more if a render was cancelled partway through a pass
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Returns the work done and time taken rendering into the film so far
+    pub fn stats(&self) -> &RenderStats {
+        &self.stats
+    }
+
+    /// Returns the number of samples taken for pixel (i,j)
+    pub fn pixel_samples(&self, i: u32, j: u32) -> u32 {
+        self.counts[(j * self.width + i) as usize]
+    }
+
+    /// Returns the current estimate of pixel (i,j), where row j counts from the top
+    pub fn get_pixel(&self, i: u32, j: u32) -> Colour {
+        let idx = (j * self.width + i) as usize;
+
+        if self.weights[idx] > 0.0 {
+            return self.weighted_sum[idx] / self.weights[idx];
+        }
+
+        // negative filter lobes can cancel out the weights, leaving only the pixel's own samples to go on
+        if self.counts[idx] == 0 {
+            return Colour::zero();
+        }
+
+        self.sum[idx] / self.counts[idx] as f64
+    }
+
+    /// Returns the estimated relative standard error of the mean luminance of pixel (i,j),
+    /// or infinity if it has fewer than two samples
+    pub fn relative_error(&self, i: u32, j: u32) -> f64 {
+        let idx = (j * self.width + i) as usize;
+        let n = self.counts[idx] as f64;
+
+        if n < 2.0 {
+            return f64::INFINITY;
+        }
+
+        let mean = self.sum[idx].luminance() / n;
+        let variance = fmax(0.0, (self.sum_sq[idx] / n - mean * mean) * n / (n - 1.0));
+
+        (variance / n).sqrt() / fmax(mean.abs(), MIN_MEAN)
+    }
+
+    /// Adds the samples taken for pixel (i,j)
+    pub(crate) fn add_samples(&mut self, i: u32, j: u32, samples: &PixelSamples) {
+        let idx = (j * self.width + i) as usize;
+
+        self.sum[idx] += samples.sum;
+        self.sum_sq[idx] += samples.sum_sq;
+        self.counts[idx] += samples.count;
+
+        for (aov, sums) in self.aovs.iter_mut() {
+            accumulate_aov(*aov, &mut sums[idx], samples.aovs[*aov as usize]);
+        }
+    }
+
+    /// Adds a sum of filter weighted samples and their total weight to pixel (i,j)
+    pub(crate) fn add_splat(&mut self, i: u32, j: u32, weighted_sum: Colour, weight: f64) {
+        let idx = (j * self.width + i) as usize;
+
+        self.weighted_sum[idx] += weighted_sum;
+        self.weights[idx] += weight;
+    }
+
+    /// Adds the samples and splats of other, a film of the same size keeping the same AOVs
+    pub(crate) fn add_film(&mut self, other: &Film) {
+        for idx in 0..self.sum.len() {
+            self.weighted_sum[idx] += other.weighted_sum[idx];
+            self.weights[idx] += other.weights[idx];
+            self.sum[idx] += other.sum[idx];
+            self.sum_sq[idx] += other.sum_sq[idx];
+            self.counts[idx] += other.counts[idx];
+        }
+
+        for ((aov, sums), (_, other_sums)) in self.aovs.iter_mut().zip(&other.aovs) {
+            for (acc, &value) in sums.iter_mut().zip(other_sums) {
+                accumulate_aov(*aov, acc, value);
+            }
+        }
+
+        self.stats += &other.stats;
+    }
+
+    /// Returns the AOVs this film keeps
+    pub fn aovs(&self) -> Vec<Aov> {
+        self.aovs.iter().map(|(aov, _)| *aov).collect()
+    }
+
+    /// Returns the current estimate of aov as a floating point image, or None if the film does not keep it
+    pub fn aov_image(&self, aov: Aov) -> Option<Rgb32FImage> {
+        let (_, sums) = self.aovs.iter().find(|(kept, _)| *kept == aov)?;
+
+        Some(ImageBuffer::from_fn(self.width, self.height, |i, j| {
+            let idx = (j * self.width + i) as usize;
+
+            let col = if aov.is_filtered() && self.counts[idx] > 0 {
+                sums[idx] / self.counts[idx] as f64
+            } else {
+                sums[idx]
+            };
+
+            Rgb([col.x as f32, col.y as f32, col.z as f32])
+        }))
+    }
+
+    /// Returns the current estimate as a linear floating point image
+    pub fn to_hdr_image(&self) -> Rgb32FImage {
+        ImageBuffer::from_fn(self.width, self.height, |i, j| {
+            let col = self.get_pixel(i, j);
+            Rgb([col.x as f32, col.y as f32, col.z as f32])
+        })
+    }
+
+    /// Converts the current estimate into an 8 bit image
+    pub fn to_rgb_image(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        tone_mapping.apply(&self.to_hdr_image())
+    }
+
+    /// Returns an image of the number of samples taken per pixel, from black for none
+    /// to white for the most sampled pixels
+    pub fn to_heatmap_image(&self) -> RgbImage {
+        let most = self.counts.iter().copied().max().unwrap_or(0).max(1);
+
+        ImageBuffer::from_fn(self.width, self.height, |i, j| {
+            let v = (255.0 * self.pixel_samples(i, j) as f64 / most as f64) as u8;
+            Rgb([v, v, v])
+        })
+    }
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, f64::consts::PI, fmt};
+
+/// Widest filter radius allowed, in pixels. Each sample is splatted onto a square of pixels whose
+/// side grows with the radius, and wider filters only blur the image further
+pub const MAX_RADIUS: f64 = 8.0;
+
+/// Shapes of filter used to weight samples by their distance from a pixel's centre
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,            // equal weight over the whole radius
+    Tent,           // weight falling linearly to zero at the radius
+    Gaussian,       // gaussian with a standard deviation of a third of the radius, shifted to reach zero at the radius
+    Mitchell,       // Mitchell-Netravali cubic with B = C = 1/3, sharper than a gaussian but with small negative lobes
+    Lanczos         // sinc windowed by a wider sinc, the sharpest, with the largest negative lobes
+}
+
+impl FilterKind {
+    /// Returns the radius this kind of filter is usually given, the same as pbrt's
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 2.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 4.0
+        }
+    }
+}
+
+/// Reconstruction filter deciding how samples are shared between nearby pixels.
+/// Each sample adds to every pixel whose centre lies within radius pixels of it, weighted by the filter
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64     // in pixels, in (0, MAX_RADIUS]
+}
+
+/// Error for a filter radius that is not a number, not positive or wider than MAX_RADIUS
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvalidRadius(pub f64);
+
+impl fmt::Display for InvalidRadius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "filter radius {} must be above 0 and at most {}", self.0, MAX_RADIUS)
+    }
+}
+
+impl Error for InvalidRadius {}
+
+impl Default for Filter {
+    /// Box filter covering a single pixel, so each sample only counts towards the pixel it was taken in
+    fn default() -> Self {
+        Self::with_default_radius(FilterKind::Box)
+    }
+}
+
+impl Filter {
+    /// Creates a filter reaching radius pixels from a sample
+    pub fn new(kind: FilterKind, radius: f64) -> Result<Self, InvalidRadius> {
+        match radius > 0.0 && radius <= MAX_RADIUS {
+            true => Ok(Self { kind, radius }),
+            false => Err(InvalidRadius(radius))
+        }
+    }
+
+    /// Creates a filter of the given kind with its usual radius
+    pub fn with_default_radius(kind: FilterKind) -> Self {
+        Self { kind, radius: kind.default_radius() }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Returns the weight of a sample offset (dx,dy) pixels from a pixel's centre
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    /// Number of pixels either side of the one a sample is taken in that it may add to
+    pub(crate) fn margin(&self) -> u32 {
+        (self.radius - 0.5).ceil().max(0.0) as u32
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+
+        if x > self.radius {
+            return 0.0;
+        }
+
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => self.radius - x,
+            FilterKind::Gaussian => {
+                let sigma = self.radius / 3.0;
+                gaussian(x, sigma) - gaussian(self.radius, sigma)
+            },
+            FilterKind::Mitchell => mitchell(2.0 * x / self.radius),
+            FilterKind::Lanczos => sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+fn gaussian(x: f64, sigma: f64) -> f64 {
+    (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+/// Mitchell-Netravali cubic over [0,2]
+fn mitchell(x: f64) -> f64 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+
+    let v = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3) + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x.powi(3) + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+    };
+
+    v / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+
+    (PI * x).sin() / (PI * x)
+}
@@ -4,13 +4,28 @@ pub mod camera;
 pub mod objects;
 pub mod scene;
 pub mod renderer;
+pub mod film;
+pub mod checkpoint;
+pub mod scene_file;
+pub mod pbrt;
+pub mod ply;
+pub mod filter;
+pub mod aov;
+pub mod denoise;
+pub mod output;
+pub mod tone_mapping;
 pub mod materials;
 pub mod point3;
 pub mod textures;
 pub mod random;
+pub mod sampler;
+pub mod stats;
 mod aabb;
 mod constants;
 mod utils;
+mod onb;
+mod bidirectional;
+mod photon_map;
 mod ray;
 
 #[cfg(test)]
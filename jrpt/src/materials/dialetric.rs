@@ -1,5 +1,5 @@
-use rand::{Rng, rngs::SmallRng};
 use crate::{
+    sampler::Sampler,
     objects::Intersection,
     materials::Material,
     ray::Ray,
@@ -29,7 +29,7 @@ impl Dialetric {
 
 impl Material for Dialetric {
     // Returns (attenuation, scattered_ray) as an option
-    fn scatter(&self, rng: &mut SmallRng, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
+    fn scatter(&self, rng: &mut dyn Sampler, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
         let attenuation = Colour::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face { 1.0 / self.ir } else { self.ir };
 
@@ -39,7 +39,7 @@ impl Material for Dialetric {
 
         let is_total_internal_reflection = refraction_ratio * sin_theta > 1.0;
 
-        let direction = if is_total_internal_reflection || Self::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>() {
+        let direction = if is_total_internal_reflection || Self::reflectance(cos_theta, refraction_ratio) > rng.get_1d() {
             unit_direction.reflect(&rec.n)
         } else {
             unit_direction.refract(&rec.n, refraction_ratio)
@@ -1,6 +1,6 @@
 use std::sync::Arc;
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
     textures::{Texture, solid_colour::SolidColour}, 
     colour::Colour, 
     materials::Material,
@@ -30,7 +30,7 @@ impl DiffuseLight {
 
 impl Material for DiffuseLight {
     // This light doesn't scatter light
-    fn scatter(&self, _: &mut SmallRng, _: Ray, _: &Intersection) -> Option<(Colour, Ray)> {
+    fn scatter(&self, _: &mut dyn Sampler, _: Ray, _: &Intersection) -> Option<(Colour, Ray)> {
         None
     }
 
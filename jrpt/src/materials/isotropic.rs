@@ -1,13 +1,14 @@
-use std::sync::Arc;
-use rand::rngs::SmallRng;
+use std::{sync::Arc, f64::consts::PI};
 use crate::{
+    sampler::Sampler,
     textures::{Texture, 
        solid_colour::SolidColour}, 
        colour::Colour, 
        ray::Ray, 
        objects::Intersection, 
        random::random_in_unit_sphere,
-       materials::Material
+       materials::Material,
+       vec3::Vec3
 };
 
 pub struct Isotropic {
@@ -30,11 +31,28 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, rng: &mut SmallRng, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
+    fn scatter(&self, rng: &mut dyn Sampler, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
 
         let scattered = Ray::new(rec.p, random_in_unit_sphere(rng), ray_in.time);
         let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
 
         Some((attenuation, scattered))
     }
+
+    // Scatters uniformly over the sphere of directions
+    fn eval(&self, _: &Ray, rec: &Intersection, _: &Vec3) -> Option<Colour> {
+        Some(self.albedo.value(rec.u, rec.v, &rec.p) / (4.0 * PI))
+    }
+
+    fn pdf(&self, _: &Ray, _: &Intersection, _: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn albedo(&self, rec: &Intersection) -> Colour {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
 }
\ No newline at end of file
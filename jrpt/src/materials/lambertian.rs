@@ -1,11 +1,12 @@
-use std::sync::Arc;
-use rand::rngs::SmallRng;
+use std::{sync::Arc, f64::consts::PI};
 use crate::{
+    sampler::Sampler,
     objects::Intersection,
     materials::Material,
     random::random_unit_vector,
     ray::Ray,
     colour::Colour,
+    vec3::Vec3,
     textures::{Texture, solid_colour::SolidColour}
 };
 
@@ -30,7 +31,7 @@ impl Lambertian {
 
 impl Material for Lambertian {
     // Returns (attenuation, scattered_ray) as an option
-    fn scatter(&self, rng: &mut SmallRng, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
+    fn scatter(&self, rng: &mut dyn Sampler, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
         let mut scatter_direction = &rec.n + random_unit_vector(rng);
 
         // catch near 0 direction
@@ -43,6 +44,25 @@ impl Material for Lambertian {
 
         Some((attenuation, scattered))
     }
-}
 
+    // Cosine weighted, matching the distribution of scatter
+    fn eval(&self, _: &Ray, rec: &Intersection, dir: &Vec3) -> Option<Colour> {
+        let cosine = rec.n.dot(&dir.normalized());
+
+        if cosine <= 0.0 {
+            return Some(Colour::zero());
+        }
+
+        Some(self.albedo.value(rec.u, rec.v, &rec.p) * (cosine / PI))
+    }
+
+    fn pdf(&self, _: &Ray, rec: &Intersection, dir: &Vec3) -> f64 {
+        let cosine = rec.n.dot(&dir.normalized());
+
+        if cosine <= 0.0 { 0.0 } else { cosine / PI }
+    }
 
+    fn albedo(&self, rec: &Intersection) -> Colour {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+}
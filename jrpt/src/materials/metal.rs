@@ -1,11 +1,13 @@
-use rand::rngs::SmallRng;
+use std::f64::consts::PI;
 use crate::{
+    sampler::Sampler,
     objects::Intersection,
     materials::Material,
     random::random_in_unit_sphere,
     colour::Colour,
-    utils::fmin,
-    ray::Ray
+    utils::{fmin, fmax},
+    ray::Ray,
+    vec3::Vec3
 };
 
 pub struct Metal {
@@ -21,11 +23,28 @@ impl Metal {
             fuzzy: fmin(fuzzy, 1.0)
         }
     }
+
+    /// Pdf of scatter choosing direction dir, where scatter perturbs the unit reflected
+    /// direction by a point uniform in a ball of radius fuzzy.
+    /// Integrates the ball's density along the line of points projecting onto dir
+    fn fuzz_pdf(&self, reflected: &Vec3, dir: &Vec3) -> f64 {
+        let c = reflected.dot(&dir.normalized());
+        let disc = c * c - 1.0 + self.fuzzy * self.fuzzy;
+
+        if disc < 0.0 {
+            return 0.0;
+        }
+
+        let s0 = fmax(c - disc.sqrt(), 0.0);
+        let s1 = fmax(c + disc.sqrt(), 0.0);
+
+        (s1.powi(3) - s0.powi(3)) / (4.0 * PI * self.fuzzy.powi(3))
+    }
 }
 
 impl Material for Metal {
     // Returns (attenuation, scattered_ray) as an option
-    fn scatter(&self, rng: &mut SmallRng, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
+    fn scatter(&self, rng: &mut dyn Sampler, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)> {
         let reflected_dir = ray_in.dir.normalized().reflect(&rec.n);
 
         let scattered = Ray::new(rec.p, &reflected_dir + self.fuzzy*random_in_unit_sphere(rng), ray_in.time);
@@ -37,4 +56,28 @@ impl Material for Metal {
             None
         }
     }
+
+    // A perfect mirror (no fuzz) only reflects in one direction
+    fn eval(&self, ray_in: &Ray, rec: &Intersection, dir: &Vec3) -> Option<Colour> {
+        if self.fuzzy <= 0.0 {
+            return None;
+        }
+
+        // scatter weighs each direction by albedo alone, so BSDF * cosine is albedo * pdf
+        Some(self.albedo * self.pdf(ray_in, rec, dir))
+    }
+
+    fn pdf(&self, ray_in: &Ray, rec: &Intersection, dir: &Vec3) -> f64 {
+        // directions below the surface are absorbed
+        if self.fuzzy <= 0.0 || dir.dot(&rec.n) <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected_dir = ray_in.dir.normalized().reflect(&rec.n);
+        self.fuzz_pdf(&reflected_dir, dir)
+    }
+
+    fn albedo(&self, _: &Intersection) -> Colour {
+        self.albedo
+    }
 }
@@ -1,22 +1,50 @@
-use rand::rngs::SmallRng;
 
 use crate::{
+    sampler::Sampler,
     ray::Ray, 
     objects::Intersection, 
     colour::Colour, 
-    point3::Point3
+    point3::Point3,
+    vec3::Vec3
 };
 
 #[allow(unused)]
 pub trait Material: Send + Sync {
     /// Returns (attenuation, scattered_ray) as an option
-    fn scatter(&self, rng: &mut SmallRng, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)>;
+    fn scatter(&self, rng: &mut dyn Sampler, ray_in: Ray, rec: &Intersection) -> Option<(Colour, Ray)>;
 
     /// Returns emitted colour from this material.
     /// defaults to black for all materials
     fn emitted(&self, u: f64, v: f64, p: &Point3) -> Colour {
         Colour::zero()
     }
+
+    /// Returns the BSDF value (including the cosine term) for light arriving from
+    /// direction dir and leaving along the reverse of ray_in.
+    /// Returns None for materials that only scatter in discrete directions (mirrors, glass),
+    /// the renderer never samples lights directly from these
+    fn eval(&self, ray_in: &Ray, rec: &Intersection, dir: &Vec3) -> Option<Colour> {
+        None
+    }
+
+    /// Returns the pdf (with respect to solid angle) of scatter choosing direction dir.
+    /// Used to weight light samples against scattered rays, defaults to 0 which
+    /// leaves all direct lighting to light sampling
+    fn pdf(&self, ray_in: &Ray, rec: &Intersection, dir: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns the base colour of the material at rec, written out as the albedo render pass.
+    /// Defaults to white, which suits materials that tint nothing such as glass
+    fn albedo(&self, rec: &Intersection) -> Colour {
+        Colour::from_value(1.0)
+    }
+
+    /// Returns true for materials that scatter inside participating media rather than on a surface,
+    /// where converting between solid angle and area has no cosine term
+    fn is_volumetric(&self) -> bool {
+        false
+    }
 }
 
 
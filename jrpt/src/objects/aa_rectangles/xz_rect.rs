@@ -1,18 +1,20 @@
 // Rectangle aligned with X-Z Plane
 
 use std::{sync::Arc, ops::Range};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
     materials::Material, 
     aabb::AABB, 
     point3::Point3, 
     ray::Ray, 
     vec3::Vec3,
+    constants::{EPSILON, INFINITY},
     objects::{Intersection, Object, AuxObjectData}
 };
 
 pub struct XzRectangle {
-    material: Arc<dyn Material>,
+    pub(crate) material: Arc<dyn Material>,
     x0: f64,
     x1: f64,
     z0: f64,
@@ -28,7 +30,8 @@ pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, y: f64, material: Arc<dyn Materia
     };
 
     Object {
-        intersect, bounding_box,
+        intersect, bounding_box, sample, pdf_value, sample_point,
+        id: 0,
         aux: AuxObjectData::XzRectangle(data)
     }
 }
@@ -50,7 +53,9 @@ fn bounding_box(obj: &Object, _: Range<f64>) -> Option<AABB> {
     ))
 }
 
-fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+    stats::count(Counter::PrimitiveTests);
+
     let aux = if let AuxObjectData::XzRectangle(aux) = &obj.aux { aux } else { panic!("Could not extract XzRectangle from aux data") };
 
     let t = (aux.y - r.origin.y) / r.dir.y;
@@ -76,3 +81,47 @@ fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) ->
     
     Some(rec)
 }
+
+/// Samples a direction from origin towards a point chosen uniformly on the rectangle
+fn sample(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, _: f64) -> Option<Vec3> {
+    let aux = if let AuxObjectData::XzRectangle(aux) = &obj.aux { aux } else { panic!("Could not extract XzRectangle from aux data") };
+
+    let (u, v) = rng.get_2d();
+    let p = Point3::new(
+        aux.x0 + u * (aux.x1 - aux.x0),
+        aux.y,
+        aux.z0 + v * (aux.z1 - aux.z0)
+    );
+
+    Some(p - origin)
+}
+
+/// Pdf (with respect to solid angle) of sampling given direction from origin
+fn pdf_value(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, dir: &Vec3, time: f64) -> f64 {
+    let aux = if let AuxObjectData::XzRectangle(aux) = &obj.aux { aux } else { panic!("Could not extract XzRectangle from aux data") };
+
+    let rec = match intersect(obj, rng, &Ray::new(*origin, *dir, time), EPSILON, INFINITY) {
+        Some(rec) => rec,
+        None => return 0.0
+    };
+
+    let area = (aux.x1 - aux.x0) * (aux.z1 - aux.z0);
+    let distance_squared = rec.t * rec.t * dir.length_squared();
+    let cosine = (dir.dot(&rec.n) / dir.length()).abs();
+
+    distance_squared / (cosine * area)
+}
+
+/// Samples a point uniformly on the rectangle, returning it with its pdf with respect to area
+fn sample_point(obj: &Object, rng: &mut dyn Sampler, _: f64) -> Option<(Intersection, f64)> {
+    let aux = if let AuxObjectData::XzRectangle(aux) = &obj.aux { aux } else { panic!("Could not extract XzRectangle from aux data") };
+
+    let (u, v) = rng.get_2d();
+    let p = Point3::new(aux.x0 + u * (aux.x1 - aux.x0), aux.y, aux.z0 + v * (aux.z1 - aux.z0));
+
+    let area = (aux.x1 - aux.x0) * (aux.z1 - aux.z0);
+    let mut rec = Intersection::new(0.0, p, Vec3::new(0.0,1.0,0.0), &aux.material, u, v);
+    rec.front_face = true;
+
+    Some((rec, 1.0 / area))
+}
\ No newline at end of file
@@ -1,16 +1,16 @@
-use std::{ops::Range, sync::Arc};
+use std::{error::Error, fmt, ops::Range, sync::Arc};
 use nalgebra::{Vector4, Matrix4};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
     vec3::Vec3, 
     ray::Ray, 
-    objects::{Intersection, Object, AuxObjectData}, 
+    objects::{Intersection, Object, AuxObjectData, no_sample, no_pdf_value, no_sample_point}, 
     point3::Point3, aabb::AABB, utils::{fmin, fmax}
 };
 
 /// Affine transformations
 pub struct Affine {
-    object: Arc<Object>,             // object being wrapped with transformation
+    pub(crate) object: Arc<Object>,  // object being wrapped with transformation
     transformed: bool,          // flag to denote non identity transform
     mat_t: Matrix4<f64>,        // note these matrices are stored as column vectors!
     mat_t_inv: Matrix4<f64>
@@ -27,6 +27,8 @@ pub fn new(object: Object) -> Object {
 
     Object {
         intersect, bounding_box,
+        sample: no_sample, pdf_value: no_pdf_value, sample_point: no_sample_point,
+        id: 0,
         aux: AuxObjectData::Affine(data)
     }
 }
@@ -75,98 +77,195 @@ fn point_transform(transformation: &Affine, p: &Point3) -> Point3 {
     Point3::new(o.x, o.y, o.z)
 }
 
-/// Sets the inverse transformation for this affine transformation
-pub fn set_inverse(obj: &mut Object) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
-
-    aux.mat_t_inv = aux.mat_t.try_inverse().unwrap()
+/// Transformation built up a step at a time, each step applied after those before it,
+/// e.g. Transform::new().scale_uniform(2.0).translate(1.0, 0.0, 5.0) scales and then moves.
+/// apply wraps an object in it with the inverse already computed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    mat_t: Matrix4<f64>         // applied to column vectors, like Affine's
 }
 
-/// Rotates this transformation by theta radians about the x axis
-pub fn rotate_x(obj: &mut Object, theta: f64) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
+/// Error for a transform that can not be undone, such as scaling by zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SingularTransform;
 
-    aux.transformed = true;
+impl fmt::Display for SingularTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transform can not be inverted")
+    }
+}
 
-    let mt = Matrix4::new(
-        1.0, 0.0, 0.0, 0.0,
-        0.0, theta.cos(), -theta.sin(), 0.0,
-        0.0, theta.sin(), theta.cos(), 0.0,
-        0.0, 0.0, 0.0, 1.0
-    );
+impl Error for SingularTransform {}
 
-    aux.mat_t = mt * aux.mat_t;
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Rotates this transformation by theta radians about the y axis
-pub fn rotate_y(obj: &mut Object, theta: f64) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
+impl Transform {
+    /// Creates the identity transform
+    pub fn new() -> Self {
+        Self { mat_t: Matrix4::identity() }
+    }
 
-    aux.transformed = true;
+    /// Creates a transform from a matrix that is applied to column vectors
+    pub fn from_matrix(mat_t: Matrix4<f64>) -> Self {
+        Self { mat_t }
+    }
 
-    let mt = Matrix4::new(
-        theta.cos(), 0.0, theta.sin(), 0.0,
-        0.0, 1.0, 0.0, 0.0,
-        -theta.sin(), 0.0, theta.cos(), 0.0,
-        0.0, 0.0, 0.0, 1.0
-    );
+    /// Returns the matrix of this transform, applied to column vectors
+    pub fn matrix(&self) -> Matrix4<f64> {
+        self.mat_t
+    }
 
-    aux.mat_t = mt * aux.mat_t;
-}
+    /// Applies mat_t, a matrix applied to column vectors, after this transform
+    pub fn then_matrix(self, mat_t: Matrix4<f64>) -> Self {
+        Self { mat_t: mat_t * self.mat_t }
+    }
 
-/// Rotates this transformation by theta radians about the z axis
-pub fn rotate_z(obj: &mut Object, theta: f64) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
+    /// Applies other after this transform
+    pub fn then(self, other: Transform) -> Self {
+        self.then_matrix(other.mat_t)
+    }
 
-    aux.transformed = true;
+    /// Translates by the given values for each dimension
+    pub fn translate(self, x: f64, y: f64, z: f64) -> Self {
+        self.then_matrix(translation(x, y, z))
+    }
 
-    let mt = Matrix4::new(
-        theta.cos(), -theta.sin(), 0.0, 0.0,
-        theta.sin(), theta.cos(), 0.0, 0.0,
-        0.0, 0.0, 1.0, 0.0,
-        0.0, 0.0, 0.0, 1.0
-    );
+    /// Scales by the given values for each dimension
+    pub fn scale(self, x: f64, y: f64, z: f64) -> Self {
+        self.then_matrix(scaling(x, y, z))
+    }
 
-    aux.mat_t = mt * aux.mat_t;
-}
+    /// Scales by scale in all dimensions
+    pub fn scale_uniform(self, scale: f64) -> Self {
+        self.scale(scale, scale, scale)
+    }
 
-/// Scales this transformation by given values for each dimension
-pub fn scale(obj: &mut Object, x_scale: f64, y_scale: f64, z_scale: f64) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
+    /// Rotates by theta radians about the x axis
+    pub fn rotate_x(self, theta: f64) -> Self {
+        self.then_matrix(rotation_x(theta))
+    }
 
-    aux.transformed = true;
+    /// Rotates by theta radians about the y axis
+    pub fn rotate_y(self, theta: f64) -> Self {
+        self.then_matrix(rotation_y(theta))
+    }
 
-    let mt = Matrix4::new(
-        x_scale, 0.0, 0.0, 0.0,
-        0.0, y_scale, 0.0, 0.0,
-        0.0, 0.0, z_scale, 0.0,
-        0.0, 0.0, 0.0, 1.0
-    );
+    /// Rotates by theta radians about the z axis
+    pub fn rotate_z(self, theta: f64) -> Self {
+        self.then_matrix(rotation_z(theta))
+    }
+
+    /// Rotates by theta radians about axis, anticlockwise looking down it towards the origin.
+    /// A zero axis leaves the transform singular
+    pub fn rotate(self, axis: &Vec3, theta: f64) -> Self {
+        let a = axis.normalized();
+        let (sin, cos) = theta.sin_cos();
+        let t = 1.0 - cos;
+
+        self.then_matrix(Matrix4::new(
+            t*a.x*a.x + cos, t*a.x*a.y - sin*a.z, t*a.x*a.z + sin*a.y, 0.0,
+            t*a.x*a.y + sin*a.z, t*a.y*a.y + cos, t*a.y*a.z - sin*a.x, 0.0,
+            t*a.x*a.z - sin*a.y, t*a.y*a.z + sin*a.x, t*a.z*a.z + cos, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ))
+    }
+
+    /// Rotates by Euler angles in radians, about the x axis first, then y, then z
+    pub fn rotate_euler(self, x: f64, y: f64, z: f64) -> Self {
+        self.rotate_x(x).rotate_y(y).rotate_z(z)
+    }
+
+    /// Moves the origin to from and turns the z axis to face at, keeping the y axis as close to up as it
+    /// can. from and at being the same, or up being along the line between them, leaves the transform singular
+    pub fn look_at(self, from: &Point3, at: &Point3, up: &Vec3) -> Self {
+        let w = (at - from).normalized();
+        let u = up.cross(&w).normalized();
+        let v = w.cross(&u);
+
+        self.then_matrix(Matrix4::new(
+            u.x, v.x, w.x, from.x,
+            u.y, v.y, w.y, from.y,
+            u.z, v.z, w.z, from.z,
+            0.0, 0.0, 0.0, 1.0
+        ))
+    }
 
-    aux.mat_t = mt * aux.mat_t;
+    /// Returns the transform undoing this one
+    pub fn inverse(&self) -> Result<Self, SingularTransform> {
+        let mat_t_inv = self.mat_t.try_inverse().ok_or(SingularTransform)?;
+
+        match mat_t_inv.iter().all(|v| v.is_finite()) {
+            true => Ok(Self { mat_t: mat_t_inv }),
+            false => Err(SingularTransform)
+        }
+    }
+
+    /// Wraps object in this transform
+    pub fn apply(&self, object: Object) -> Result<Object, SingularTransform> {
+        let inverse = self.inverse()?;
+        let mut transformed = new(object);
+
+        let aux = affine_data(&mut transformed);
+        aux.transformed = self.mat_t != Matrix4::identity();
+        aux.mat_t = self.mat_t;
+        aux.mat_t_inv = inverse.mat_t;
+
+        Ok(transformed)
+    }
 }
 
-/// Scales this transformation by scale in all dimensions
-pub fn scale_uniform(obj: &mut Object, uscale: f64) {
-    scale(obj, uscale, uscale, uscale)
+fn affine_data(obj: &mut Object) -> &mut Affine {
+    if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") }
 }
 
-/// Translates this transformation by given values for each dimension
-pub fn translate(obj: &mut Object, x_translate: f64, y_translate: f64, z_translate: f64) {
-    let aux = if let AuxObjectData::Affine(aux) = &mut obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
+fn translation(x: f64, y: f64, z: f64) -> Matrix4<f64> {
+    Matrix4::new(
+        1.0, 0.0, 0.0, x,
+        0.0, 1.0, 0.0, y,
+        0.0, 0.0, 1.0, z,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
 
-    aux.transformed = true;
+fn scaling(x: f64, y: f64, z: f64) -> Matrix4<f64> {
+    Matrix4::new(
+        x, 0.0, 0.0, 0.0,
+        0.0, y, 0.0, 0.0,
+        0.0, 0.0, z, 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
 
-    let mt = Matrix4::new(
-        1.0, 0.0, 0.0, x_translate,
-        0.0, 1.0, 0.0, y_translate,
-        0.0, 0.0, 1.0, z_translate,
+fn rotation_x(theta: f64) -> Matrix4<f64> {
+    Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, theta.cos(), -theta.sin(), 0.0,
+        0.0, theta.sin(), theta.cos(), 0.0,
         0.0, 0.0, 0.0, 1.0
-    );
+    )
+}
 
-    aux.mat_t = mt * aux.mat_t
+fn rotation_y(theta: f64) -> Matrix4<f64> {
+    Matrix4::new(
+        theta.cos(), 0.0, theta.sin(), 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        -theta.sin(), 0.0, theta.cos(), 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
 }
 
+fn rotation_z(theta: f64) -> Matrix4<f64> {
+    Matrix4::new(
+        theta.cos(), -theta.sin(), 0.0, 0.0,
+        theta.sin(), theta.cos(), 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
 
 fn bounding_box(obj: &Object, time: Range<f64>) -> Option<AABB> {
     let aux = if let AuxObjectData::Affine(aux) = &obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
@@ -216,7 +315,7 @@ fn bounding_box(obj: &Object, time: Range<f64>) -> Option<AABB> {
     }
 }
 
-fn intersect(obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, rng: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
     let aux = if let AuxObjectData::Affine(aux) = &obj.aux { aux } else { panic!("Could not extract Affine from aux data") };
 
     let rt = inverse_ray_transform(aux, r);
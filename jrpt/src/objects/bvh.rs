@@ -1,10 +1,11 @@
-use std::{ops::Range, cmp::Ordering, sync::Arc};
-use rand::rngs::SmallRng;
+use std::{ops::Range, cmp::Ordering, sync::Arc, time::Instant};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
 use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
     aabb::{surrounding_box, AABB},
     utils::sort_from,
-    objects::{Object, AuxObjectData, Intersection},
-    random::random_i32,
+    objects::{Object, AuxObjectData, Intersection, no_sample, no_pdf_value, no_sample_point},
     ray::Ray
 };
 
@@ -14,8 +15,13 @@ pub struct BvhNode {
     bounding_box: AABB
 }
 
-/// Create BVH tree from given ObjectList
+/// Create BVH tree from given ObjectList, always building the same tree for the same list
 pub fn new(list: Object, time: Range<f64>) -> Object {
+    with_seed(list, time, 0)
+}
+
+/// Create BVH tree from given ObjectList, with seed choosing the split axes
+pub fn with_seed(list: Object, time: Range<f64>, seed: u64) -> Object {
     let aux = if let AuxObjectData::ObjectList(aux) = list.aux { aux } else { panic!("Could not extract ObjectList from aux data") };
 
     if aux.objects.is_empty() {
@@ -25,13 +31,19 @@ pub fn new(list: Object, time: Range<f64>) -> Object {
     let len = aux.objects.len();
     let list: Vec<Arc<Object>> = aux.objects.into_iter().map(Arc::new).collect();
 
-    from_indexes(list, 0, len, time)
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    let start = Instant::now();
+    let bvh = from_indexes(&mut rng, list, 0, len, time);
+    stats::add_bvh_build(start.elapsed());
+
+    bvh
 }
 
-fn from_indexes(src_objects: Vec<Arc<Object>>, start: usize, end: usize, time: Range<f64>) -> Object {
+fn from_indexes(rng: &mut SmallRng, src_objects: Vec<Arc<Object>>, start: usize, end: usize, time: Range<f64>) -> Object {
     let mut objects = src_objects;
 
-    let axis = random_i32(0..3);
+    let axis = rng.gen_range(0..3);
 
     let comparator = |a: &Object, b: &Object| -> bool {
         let box_a = (a.bounding_box)(a, 0.0..0.0); // I know this looks wrong but its right!
@@ -72,8 +84,8 @@ fn from_indexes(src_objects: Vec<Arc<Object>>, start: usize, end: usize, time: R
         });
 
         let mid = start + object_span / 2;
-        left = Arc::new(from_indexes(objects.clone(), start, mid, time.clone()));
-        right = Arc::new(from_indexes(objects, mid, end, time.clone()));
+        left = Arc::new(from_indexes(rng, objects.clone(), start, mid, time.clone()));
+        right = Arc::new(from_indexes(rng, objects, mid, end, time.clone()));
     }
     
     let b0 = (left.bounding_box)(&left, time.clone());
@@ -91,30 +103,50 @@ fn from_indexes(src_objects: Vec<Arc<Object>>, start: usize, end: usize, time: R
 
     Object {
         intersect, bounding_box,
+        sample: no_sample, pdf_value: no_pdf_value, sample_point: no_sample_point,
+        id: 0,
         aux: AuxObjectData::BvhNode(data)
     }
 }
 
 
+/// Adds the objects the BVH tree obj was built from to objects
+pub(crate) fn leaves<'a>(obj: &'a Object, objects: &mut Vec<&'a Object>) {
+    let aux = if let AuxObjectData::BvhNode(aux) = &obj.aux { aux } else { panic!("Could not extract BvhNode from aux data") };
+
+    // a single object is put on both sides
+    let children = if Arc::ptr_eq(&aux.left, &aux.right) { vec![&aux.left] } else { vec![&aux.left, &aux.right] };
+
+    for child in children {
+        match child.aux {
+            // nodes of this tree have no id, unlike trees built from lists of their own and added to the list
+            AuxObjectData::BvhNode(_) if child.id == 0 => leaves(child, objects),
+            _ => objects.push(child)
+        }
+    }
+}
+
 fn bounding_box(obj: &Object, _: Range<f64>) -> Option<AABB> {
     let aux = if let AuxObjectData::BvhNode(aux) = &obj.aux { aux } else { panic!("Could not extract BvhNode from aux data") };
 
     Some(aux.bounding_box.clone())
 }
 
-fn intersect(obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, rng: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
     let aux = if let AuxObjectData::BvhNode(aux) = &obj.aux { aux } else { panic!("Could not extract BvhNode from aux data") };
 
+    stats::count(Counter::AabbTests);
+
     if !aux.bounding_box.intersect(r, t_min, t_max) {
         return None;
     }
 
-    let hit_left = (aux.left.intersect)(&aux.left, rng, r, t_min, t_max);
+    let hit_left = (aux.left.intersect)(&aux.left, rng, r, t_min, t_max).map(|rec| rec.with_object(&aux.left));
 
     let hit_right = match &hit_left {
         None => (aux.right.intersect)(&aux.right, rng, r, t_min, t_max),
         Some(rec) => (aux.right.intersect)(&aux.right, rng, r, t_min, rec.t)
-    };
+    }.map(|rec| rec.with_object(&aux.right));
 
     let hl = hit_left.is_some();
     let hr = hit_right.is_some();
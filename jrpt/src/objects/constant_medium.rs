@@ -1,7 +1,7 @@
 use std::{sync::Arc, ops::Range, f64::{NEG_INFINITY, INFINITY}};
-use rand::{rngs::SmallRng, Rng};
 use crate::{
-    objects::{Object, AuxObjectData, Intersection},
+    sampler::Sampler,
+    objects::{Object, AuxObjectData, Intersection, no_sample, no_pdf_value, no_sample_point},
     materials::{Material, isotropic::Isotropic}, 
     textures::{Texture, solid_colour::SolidColour}, 
     colour::Colour, 
@@ -12,7 +12,7 @@ use crate::{
 
 pub struct ConstantMedium {
     boundary: Arc<Object>,
-    phase_function: Arc<dyn Material>,
+    pub(crate) phase_function: Arc<dyn Material>,
     neg_inv_density: f64
 }
 
@@ -31,6 +31,8 @@ pub fn from_texture(obj: Object, density: f64, texture: Arc<dyn Texture>) -> Obj
 
     Object {
         intersect, bounding_box,
+        sample: no_sample, pdf_value: no_pdf_value, sample_point: no_sample_point,
+        id: 0,
         aux: AuxObjectData::ConstantMedium(data)
     }
 }
@@ -42,7 +44,7 @@ fn bounding_box(obj: &Object, time: Range<f64>) -> Option<AABB> {
     (aux.boundary.bounding_box)(&aux.boundary, time)
 }
 
-fn intersect(obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, rng: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
     let aux = if let AuxObjectData::ConstantMedium(aux) = &obj.aux { aux } else { panic!("Could not extract ConstantMedium from aux data") };
 
     let mut rec1 = (aux.boundary.intersect)(&aux.boundary, rng, r, NEG_INFINITY, INFINITY)?;
@@ -61,7 +63,7 @@ fn intersect(obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64)
 
     let ray_len = r.dir.length();
     let distance_inside_boundary = (rec2.t - rec1.t) * ray_len;
-    let hit_distance = aux.neg_inv_density * rng.gen::<f64>().ln();
+    let hit_distance = aux.neg_inv_density * (1.0 - rng.get_1d()).ln();
 
     if hit_distance > distance_inside_boundary {
         return None;
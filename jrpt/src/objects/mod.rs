@@ -1,6 +1,6 @@
 use std::{sync::Arc, ops::Range};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
     ray::Ray, 
     point3::Point3, 
     vec3::Vec3, 
@@ -25,7 +25,8 @@ pub struct Intersection {
     pub t: f64,                         // distance ray travelled
     pub front_face: bool,               // did the ray hit the outside
     pub material: Arc<dyn Material>,    // material hit
-    pub u: f64, pub v: f64              // texture u-v coordinates
+    pub u: f64, pub v: f64,             // texture u-v coordinates
+    pub object_id: u32                  // id of the outermost object hit that has one, 0 if none do
 }
 
 impl Intersection {
@@ -34,10 +35,21 @@ impl Intersection {
             t, p, n,
             front_face: false,
             material: material.clone(),
-            u, v
+            u, v,
+            object_id: 0
         }
     }
 
+    /// Records that this hit is on obj, a member of a list or BVH, if obj has an id.
+    /// Lists further out record theirs afterwards, so the outermost id is kept
+    pub(crate) fn with_object(mut self, obj: &Object) -> Self {
+        if obj.id != 0 {
+            self.object_id = obj.id;
+        }
+
+        self
+    }
+
     /// Ensure that the normal on this rec points against the ray
     pub fn set_face_normal(&mut self, r: &Ray) {
         self.front_face = r.dir.dot(&self.n) < 0.0;
@@ -48,12 +60,64 @@ impl Intersection {
     }
 }
 
+/// Point sampled on an object's surface, with its pdf with respect to area
+pub type PointSample = (Intersection, f64);
+
 pub struct Object {
     pub bounding_box: fn (obj: &Object, time: Range<f64>) -> Option<AABB>,
-    pub intersect: fn (obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection>,
+    pub intersect: fn (obj: &Object, rng: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection>,
+    pub sample: fn (obj: &Object, rng: &mut dyn Sampler, origin: &Point3, time: f64) -> Option<Vec3>,
+    pub pdf_value: fn (obj: &Object, rng: &mut dyn Sampler, origin: &Point3, dir: &Vec3, time: f64) -> f64,
+    pub sample_point: fn (obj: &Object, rng: &mut dyn Sampler, time: f64) -> Option<PointSample>,
+    pub id: u32,        // identifies the object in the object id AOV, given by object_list::add unless set before, 0 for none
     pub aux: AuxObjectData
 }
 
+/// Used by objects that can not be sampled as lights
+pub fn no_sample(_: &Object, _: &mut dyn Sampler, _: &Point3, _: f64) -> Option<Vec3> {
+    None
+}
+
+/// Used by objects that can not be sampled as lights
+pub fn no_pdf_value(_: &Object, _: &mut dyn Sampler, _: &Point3, _: &Vec3, _: f64) -> f64 {
+    0.0
+}
+
+/// Used by objects that can not be sampled as lights
+pub fn no_sample_point(_: &Object, _: &mut dyn Sampler, _: f64) -> Option<(Intersection, f64)> {
+    None
+}
+
+/// Calls f with every material of obj, taking the objects of lists and BVHs in order of their ids
+/// so that the order does not depend on how a BVH was built
+pub(crate) fn visit_materials(obj: &Object, f: &mut dyn FnMut(&Arc<dyn Material>)) {
+    let visit_in_id_order = |mut objects: Vec<&Object>, f: &mut dyn FnMut(&Arc<dyn Material>)| {
+        objects.sort_by_key(|obj| obj.id);
+        for obj in objects {
+            visit_materials(obj, f);
+        }
+    };
+
+    match &obj.aux {
+        AuxObjectData::Sphere(sphere) => f(&sphere.material),
+        AuxObjectData::Triangle(triangle) => f(&triangle.material),
+        AuxObjectData::MovingSphere(sphere) => f(&sphere.material),
+        AuxObjectData::XyRectangle(rect) => f(&rect.material),
+        AuxObjectData::XzRectangle(rect) => f(&rect.material),
+        AuxObjectData::YzRectangle(rect) => f(&rect.material),
+        AuxObjectData::ConstantMedium(medium) => f(&medium.phase_function),
+        AuxObjectData::RectangularPrism(prism) => visit_materials(&prism.sides, f),
+        AuxObjectData::Affine(affine) => visit_materials(&affine.object, f),
+        AuxObjectData::ObjectList(list) => visit_in_id_order(list.objects.iter().collect(), f),
+        AuxObjectData::BvhNode(_) => {
+            let mut leaves = vec![];
+            bvh::leaves(obj, &mut leaves);
+            visit_in_id_order(leaves, f);
+        },
+        AuxObjectData::NoData => ()
+    }
+}
+
 pub enum AuxObjectData {
     Sphere(Sphere),
     Triangle(Triangle),
@@ -1,12 +1,13 @@
 use std::{f64::consts::PI, ops::Range, sync::Arc};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
     aabb::{AABB, surrounding_box},
     point3::Point3,
     materials::Material,
     ray::Ray,
     utils::in_range,
-    objects::{Object, AuxObjectData, Intersection},
+    objects::{Object, AuxObjectData, Intersection, no_sample, no_pdf_value, no_sample_point},
     vec3::Vec3
 };
 
@@ -33,6 +34,8 @@ pub fn new(
 
     Object {
         intersect, bounding_box,
+        sample: no_sample, pdf_value: no_pdf_value, sample_point: no_sample_point,
+        id: 0,
         aux: AuxObjectData::MovingSphere(data)
     }
 }
@@ -57,7 +60,9 @@ fn bounding_box(obj: &Object, time: Range<f64>) -> Option<AABB> {
     Some(surrounding_box(box0, box1))
 }
 
-fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+    stats::count(Counter::PrimitiveTests);
+
     let aux = if let AuxObjectData::MovingSphere(aux) = &obj.aux { aux } else { panic!("Could not extract MovingSphere from aux data") };
 
     let oc = &r.origin - get_origin(aux, r.time);
@@ -1,9 +1,11 @@
 use crate::{
+    sampler::Sampler,
     aabb::{surrounding_box, AABB},
     objects::{AuxObjectData, Intersection, Object},
+    point3::Point3,
     ray::Ray,
+    vec3::Vec3,
 };
-use rand::rngs::SmallRng;
 use std::ops::Range;
 
 pub struct ObjectList {
@@ -17,21 +19,41 @@ pub fn new() -> Object {
     Object {
         intersect,
         bounding_box,
+        sample,
+        pdf_value,
+        sample_point,
+        id: 0,
         aux: AuxObjectData::ObjectList(data),
     }
 }
 
-/// Add object to this object list
-pub fn add(list: &mut Object, object: Object) {
+/// Add object to this object list. Objects without an id are given their position in the list, counting from 1,
+/// which they keep when the list is built into a BVH or added to another list
+pub fn add(list: &mut Object, mut object: Object) {
     let aux = if let AuxObjectData::ObjectList(aux) = &mut list.aux {
         aux
     } else {
         panic!("Could not extract ObjectList from aux data")
     };
 
+    if object.id == 0 {
+        object.id = aux.objects.len() as u32 + 1;
+    }
+
     aux.objects.push(object);
 }
 
+/// Returns true if this object list contains no objects
+pub fn is_empty(list: &Object) -> bool {
+    let aux = if let AuxObjectData::ObjectList(aux) = &list.aux {
+        aux
+    } else {
+        panic!("Could not extract ObjectList from aux data")
+    };
+
+    aux.objects.is_empty()
+}
+
 /// Clear this object list
 pub fn clear(obj: &mut Object) {
     let aux = if let AuxObjectData::ObjectList(aux) = &mut obj.aux {
@@ -74,7 +96,7 @@ fn bounding_box(obj: &Object, time: Range<f64>) -> Option<AABB> {
 /// Computes intersection of given ray with canonical list
 fn intersect(
     obj: &Object,
-    rng: &mut SmallRng,
+    rng: &mut dyn Sampler,
     r: &Ray,
     t_min: f64,
     t_max: f64,
@@ -88,12 +110,70 @@ fn intersect(
     let mut ret: Option<Intersection> = None;
     let mut closest_t = t_max;
 
-    for obj in aux.objects.iter() {
+    for obj in &aux.objects {
         if let Some(rec) = (obj.intersect)(obj, rng, r, t_min, closest_t) {
             closest_t = rec.t;
-            ret = Some(rec);
+            ret = Some(rec.with_object(obj));
         }
     }
 
     ret
 }
+
+/// Samples a direction towards one of the objects in the list, chosen uniformly
+fn sample(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, time: f64) -> Option<Vec3> {
+    let aux = if let AuxObjectData::ObjectList(aux) = &obj.aux {
+        aux
+    } else {
+        panic!("Could not extract ObjectList from aux data")
+    };
+
+    if aux.objects.is_empty() {
+        return None;
+    }
+
+    let obj = &aux.objects[random_index(rng, aux.objects.len())];
+    (obj.sample)(obj, rng, origin, time)
+}
+
+/// Pdf of sampling given direction, the average of the pdfs of each object in the list
+fn pdf_value(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, dir: &Vec3, time: f64) -> f64 {
+    let aux = if let AuxObjectData::ObjectList(aux) = &obj.aux {
+        aux
+    } else {
+        panic!("Could not extract ObjectList from aux data")
+    };
+
+    if aux.objects.is_empty() {
+        return 0.0;
+    }
+
+    let sum: f64 = aux.objects.iter()
+        .map(|obj| (obj.pdf_value)(obj, rng, origin, dir, time))
+        .sum();
+
+    sum / aux.objects.len() as f64
+}
+
+/// Samples a point on a uniformly chosen object in the list
+fn sample_point(obj: &Object, rng: &mut dyn Sampler, time: f64) -> Option<(Intersection, f64)> {
+    let aux = if let AuxObjectData::ObjectList(aux) = &obj.aux {
+        aux
+    } else {
+        panic!("Could not extract ObjectList from aux data")
+    };
+
+    if aux.objects.is_empty() {
+        return None;
+    }
+
+    let obj = &aux.objects[random_index(rng, aux.objects.len())];
+    let (rec, pdf) = (obj.sample_point)(obj, rng, time)?;
+
+    Some((rec, pdf / aux.objects.len() as f64))
+}
+
+/// Uniformly chosen index into a list of len objects
+fn random_index(rng: &mut dyn Sampler, len: usize) -> usize {
+    ((rng.get_1d() * len as f64) as usize).min(len - 1)
+}
@@ -1,6 +1,6 @@
 use std::{ops::Range, sync::Arc};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
     aabb::AABB,
     ray::Ray,
     point3::Point3,
@@ -8,7 +8,8 @@ use crate::{
     objects::{
         object_list, 
         Object,
-        Intersection, AuxObjectData, 
+        Intersection, AuxObjectData,
+        no_sample, no_pdf_value, no_sample_point,
         aa_rectangles::{xy_rect, xz_rect, yz_rect}
     }
 };
@@ -18,7 +19,7 @@ pub struct RectangularPrism {
     // corners of the prism
     min: Point3,
     max: Point3,
-    sides: Arc<Object> // object list
+    pub(crate) sides: Arc<Object> // object list
 }
 
 /// Create rectangular prism defined by the corners p0, p1
@@ -42,6 +43,8 @@ pub fn new(p0: Point3, p1: Point3, material: Arc<dyn Material>) -> Object {
 
     Object {
         intersect, bounding_box,
+        sample: no_sample, pdf_value: no_pdf_value, sample_point: no_sample_point,
+        id: 0,
         aux: AuxObjectData::RectangularPrism(data)
     }
 }
@@ -61,7 +64,7 @@ fn bounding_box(obj: &Object, _: Range<f64>) -> Option<AABB> {
     Some(AABB::new(aux.min, aux.max))
 }
 
-fn intersect(obj: &Object, rng: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, rng: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
     let aux = if let AuxObjectData::RectangularPrism(aux) = &obj.aux { aux } else { panic!("Could not extract RectangularPrism from aux data") };
 
     (aux.sides.intersect)(&aux.sides, rng, r, t_min, t_max)
@@ -1,11 +1,16 @@
 use std::{f64::consts::{PI, TAU}, ops::Range, sync::Arc};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
     aabb::AABB,
+    constants::{EPSILON, INFINITY},
     materials::Material,
+    onb::Onb,
     point3::Point3,
+    random::{random_to_sphere, random_unit_vector},
     ray::Ray,
     utils::in_range,
+    vec3::Vec3,
     objects::{Intersection, Object, AuxObjectData }, 
 };
 
@@ -23,7 +28,8 @@ pub fn new(origin: Point3, radius: f64, material: Arc<dyn Material>) -> Object {
     };
 
     Object {
-        intersect, bounding_box,
+        intersect, bounding_box, sample, pdf_value, sample_point,
+        id: 0,
         aux: AuxObjectData::Sphere(data)
     }
 }
@@ -50,7 +56,9 @@ fn bounding_box(obj: &Object, _: Range<f64>) -> Option<AABB> {
     Some(AABB::new(&aux.origin - Point3::from_value(aux.radius), &aux.origin + Point3::from_value(aux.radius)))
 }
 
-fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+    stats::count(Counter::PrimitiveTests);
+
     let aux = if let AuxObjectData::Sphere(aux) = &obj.aux { aux } else { panic!("Could not extract Sphere from aux data") };
 
     let oc = &r.origin - &aux.origin;
@@ -88,3 +96,58 @@ fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) ->
     
     Some(rec)
 }
+
+/// Samples a direction from origin towards the sphere, uniformly over the cone it subtends.
+/// If origin is inside the sphere a point is chosen uniformly on its surface instead
+fn sample(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, _: f64) -> Option<Vec3> {
+    let aux = if let AuxObjectData::Sphere(aux) = &obj.aux { aux } else { panic!("Could not extract Sphere from aux data") };
+
+    let direction = aux.origin - *origin;
+    let distance_squared = direction.length_squared();
+
+    if distance_squared <= aux.radius * aux.radius {
+        let p = aux.origin + aux.radius * random_unit_vector(rng);
+        return Some(p - origin);
+    }
+
+    let uvw = Onb::from_w(&direction);
+    Some(uvw.local(&random_to_sphere(rng, aux.radius, distance_squared)))
+}
+
+/// Pdf (with respect to solid angle) of sampling given direction from origin
+fn pdf_value(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, dir: &Vec3, time: f64) -> f64 {
+    let aux = if let AuxObjectData::Sphere(aux) = &obj.aux { aux } else { panic!("Could not extract Sphere from aux data") };
+
+    let rec = match intersect(obj, rng, &Ray::new(*origin, *dir, time), EPSILON, INFINITY) {
+        Some(rec) => rec,
+        None => return 0.0
+    };
+
+    let distance_squared = (aux.origin - *origin).length_squared();
+
+    if distance_squared <= aux.radius * aux.radius {
+        let area = 2.0 * TAU * aux.radius * aux.radius;
+        let cosine = (dir.dot(&rec.n) / dir.length()).abs();
+        return rec.t * rec.t * dir.length_squared() / (cosine * area);
+    }
+
+    let cos_theta_max = (1.0 - aux.radius * aux.radius / distance_squared).sqrt();
+    let solid_angle = TAU * (1.0 - cos_theta_max);
+
+    1.0 / solid_angle
+}
+
+/// Samples a point uniformly on the surface of the sphere, returning it with its pdf with respect to area
+fn sample_point(obj: &Object, rng: &mut dyn Sampler, _: f64) -> Option<(Intersection, f64)> {
+    let aux = if let AuxObjectData::Sphere(aux) = &obj.aux { aux } else { panic!("Could not extract Sphere from aux data") };
+
+    let n = random_unit_vector(rng);
+    let p = aux.origin + aux.radius * n;
+    let uv = get_sphere_uv(&p);
+
+    let area = 2.0 * TAU * aux.radius * aux.radius;
+    let mut rec = Intersection::new(0.0, p, n, &aux.material, uv.0, uv.1);
+    rec.front_face = true;
+
+    Some((rec, 1.0 / area))
+}
\ No newline at end of file
@@ -1,17 +1,19 @@
 // Rectangle aligned with X-Y Plane
 
 use std::{sync::Arc, ops::Range};
-use rand::rngs::SmallRng;
 use crate::{
+    sampler::Sampler,
+    stats::{self, Counter},
     materials::Material, 
     aabb::AABB, 
     point3::Point3, 
     ray::Ray, vec3::Vec3,
+    constants::{EPSILON, INFINITY},
     objects::{Object, AuxObjectData, Intersection}, utils::{fmin, fmax}
 };
 
 pub struct Triangle {
-    material: Arc<dyn Material>,
+    pub(crate) material: Arc<dyn Material>,
     p0: Point3,
     p1: Point3,
     p2: Point3,
@@ -32,7 +34,8 @@ pub fn new(p0: Point3, p1: Point3, p2: Point3, n: Option<Vec3>, uv: Option<(f64,
     };
 
     Object {
-        intersect, bounding_box,
+        intersect, bounding_box, sample, pdf_value, sample_point,
+        id: 0,
         aux: AuxObjectData::Triangle(data)
     }
 }
@@ -69,7 +72,9 @@ fn bounding_box(obj: &Object, _: Range<f64>) -> Option<AABB> {
     ))
 }
 
-fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+fn intersect(obj: &Object, _: &mut dyn Sampler, r: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+    stats::count(Counter::PrimitiveTests);
+
     let aux = if let AuxObjectData::Triangle(aux) = &obj.aux { aux } else { panic!("Could not extract Triangle from aux data") };
 
     let t = (&aux.p0 - &r.origin).dot(&aux.n)/(r.dir.dot(&aux.n));
@@ -116,3 +121,56 @@ fn intersect(obj: &Object, _: &mut SmallRng, r: &Ray, t_min: f64, t_max: f64) ->
     
     Some(rec)
 }
+
+/// Samples a direction from origin towards a point chosen uniformly on the triangle
+fn sample(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, _: f64) -> Option<Vec3> {
+    let aux = if let AuxObjectData::Triangle(aux) = &obj.aux { aux } else { panic!("Could not extract Triangle from aux data") };
+
+    let (a, b) = random_barycentric(rng);
+    let p = aux.p0 + a * (aux.p1 - aux.p0) + b * (aux.p2 - aux.p0);
+
+    Some(p - origin)
+}
+
+/// Pdf (with respect to solid angle) of sampling given direction from origin
+fn pdf_value(obj: &Object, rng: &mut dyn Sampler, origin: &Point3, dir: &Vec3, time: f64) -> f64 {
+    let aux = if let AuxObjectData::Triangle(aux) = &obj.aux { aux } else { panic!("Could not extract Triangle from aux data") };
+
+    let rec = match intersect(obj, rng, &Ray::new(*origin, *dir, time), EPSILON, INFINITY) {
+        Some(rec) => rec,
+        None => return 0.0
+    };
+
+    let area = 0.5 * (aux.p1 - aux.p0).cross(&(aux.p2 - aux.p0)).length();
+    let distance_squared = rec.t * rec.t * dir.length_squared();
+    let cosine = (dir.dot(&rec.n) / (dir.length() * rec.n.length())).abs();
+
+    distance_squared / (cosine * area)
+}
+
+/// Samples a point uniformly on the triangle, returning it with its pdf with respect to area
+fn sample_point(obj: &Object, rng: &mut dyn Sampler, _: f64) -> Option<(Intersection, f64)> {
+    let aux = if let AuxObjectData::Triangle(aux) = &obj.aux { aux } else { panic!("Could not extract Triangle from aux data") };
+
+    let (a, b) = random_barycentric(rng);
+    let p = aux.p0 + a * (aux.p1 - aux.p0) + b * (aux.p2 - aux.p0);
+    let uv = aux.uv.unwrap_or((a, b));
+
+    let area = 0.5 * (aux.p1 - aux.p0).cross(&(aux.p2 - aux.p0)).length();
+    let mut rec = Intersection::new(0.0, p, aux.n.normalized(), &aux.material, uv.0, uv.1);
+    rec.front_face = true;
+
+    Some((rec, 1.0 / area))
+}
+
+/// Uniform barycentric coordinates, folding the unit square onto the triangle
+fn random_barycentric(rng: &mut dyn Sampler) -> (f64, f64) {
+    let (mut a, mut b) = rng.get_2d();
+
+    if a + b > 1.0 {
+        a = 1.0 - a;
+        b = 1.0 - b;
+    }
+
+    (a, b)
+}
\ No newline at end of file
@@ -1,5 +1,4 @@
-use core::panic;
-use std::{sync::Arc};
+use std::{io, sync::Arc};
 use wavefront::Obj;
 use crate::{
     objects::{triangle, object_list, Object, AuxObjectData},
@@ -7,23 +6,13 @@ use crate::{
     vec3::Vec3, point3::Point3
 };
 
-/// Create a triangle mesh from .obj file at given filename
-pub fn new_mesh(filename: String, material: Arc<dyn Material>) -> Object {
-    let model = match Obj::from_file(filename.clone()) {
-        Err(err) => {
-            eprintln!("Error parsing {filename}: {err}");
-            panic!();
-        },
-        Ok(model) => model
-    };
+/// Create a triangle mesh from .obj file at given filename, whose vertices must all have normals and texture coordinates
+pub fn new_mesh(filename: String, material: Arc<dyn Material>) -> io::Result<Object> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
 
-    match create_mesh(model, material) {
-        Some(obj) => obj,
-        None => {
-            eprintln!("Error parsing {filename}");
-            panic!();
-        },
-    }
+    let model = Obj::from_file(&filename).map_err(|err| invalid(format!("could not parse {}: {}", filename, err)))?;
+
+    create_mesh(model, material).ok_or_else(|| invalid(format!("{} has vertices without normals or texture coordinates", filename)))
 }
 
 fn create_mesh(model: Obj, material: Arc<dyn Material>) -> Option<Object> {
@@ -0,0 +1,25 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis, used to sample directions relative to a given axis
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3
+}
+
+impl Onb {
+    /// Creates an orthonormal basis where w points along n
+    pub fn from_w(n: &Vec3) -> Self {
+        let w = n.normalized();
+        let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = w.cross(&a).normalized();
+        let u = w.cross(&v);
+
+        Self { u, v, w }
+    }
+
+    /// Converts the local coordinates a into world coordinates
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
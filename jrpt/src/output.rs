@@ -0,0 +1,105 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path
+};
+use image::{
+    ColorType, ImageBuffer, ImageEncoder, ImageError, ImageResult, Pixel, Rgb, Rgb32FImage,
+    imageops, codecs::{hdr::HdrEncoder, openexr::OpenExrEncoder, png::PngEncoder}
+};
+use crate::{renderer::Tile, tone_mapping::ToneMapping};
+
+/// File formats a rendered image can be saved as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Png,    // 8 bit, tone mapped
+    Hdr,    // Radiance RGBE
+    Pfm,    // portable float map, uncompressed 32 bit floats
+    Exr     // OpenEXR, 32 bit floats
+}
+
+impl OutputFormat {
+    /// Returns the format matching the extension of path, if there is one
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::from_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// Returns the format with the given file extension, ignoring case
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::Exr),
+            _ => None
+        }
+    }
+}
+
+/// Returns the part of image inside window, such as a render's crop window
+pub fn crop<P: Pixel + 'static>(image: &ImageBuffer<P, Vec<P::Subpixel>>, window: Tile) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    imageops::crop_imm(image, window.x0, window.y0, window.x1 - window.x0, window.y1 - window.y0).to_image()
+}
+
+/// Saves a linear HDR image to path, choosing the format from its extension.
+/// tone_mapping is only applied to 8 bit formats, HDR formats keep the linear values
+pub fn save<P: AsRef<Path>>(image: &Rgb32FImage, path: P, tone_mapping: &ToneMapping) -> ImageResult<()> {
+    let format = OutputFormat::from_path(&path).ok_or_else(|| {
+        ImageError::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("unknown output format for {}", path.as_ref().display())
+        ))
+    })?;
+
+    save_with_format(image, path, format, tone_mapping)
+}
+
+/// Saves a linear HDR image to path in the given format
+pub fn save_with_format<P: AsRef<Path>>(image: &Rgb32FImage, path: P, format: OutputFormat, tone_mapping: &ToneMapping) -> ImageResult<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    match format {
+        OutputFormat::Png => write_png(image, tone_mapping, &mut file)?,
+        OutputFormat::Hdr => write_hdr(image, &mut file)?,
+        OutputFormat::Pfm => write_pfm(image, &mut file)?,
+        OutputFormat::Exr => write_exr(image, &mut file)?
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes image as an 8 bit PNG after tone mapping
+pub fn write_png<W: Write>(image: &Rgb32FImage, tone_mapping: &ToneMapping, w: W) -> ImageResult<()> {
+    let ldr = tone_mapping.apply(image);
+    PngEncoder::new(w).write_image(ldr.as_raw(), ldr.width(), ldr.height(), ColorType::Rgb8)
+}
+
+/// Writes image in the Radiance .hdr format
+pub fn write_hdr<W: Write>(image: &Rgb32FImage, w: W) -> ImageResult<()> {
+    let pixels: Vec<Rgb<f32>> = image.pixels().copied().collect();
+    HdrEncoder::new(w).encode(&pixels, image.width() as usize, image.height() as usize)
+}
+
+/// Writes image as a little endian colour portable float map
+pub fn write_pfm<W: Write>(image: &Rgb32FImage, mut w: W) -> ImageResult<()> {
+    // negative scale marks the data as little endian
+    write!(w, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+
+    // rows are stored from the bottom of the image up
+    for j in (0..image.height()).rev() {
+        for i in 0..image.width() {
+            for v in image.get_pixel(i, j).0 {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes image in the OpenEXR format
+pub fn write_exr<W: Write + Seek>(image: &Rgb32FImage, w: W) -> ImageResult<()> {
+    let bytes: Vec<u8> = image.as_raw().iter().flat_map(|v| v.to_ne_bytes()).collect();
+    OpenExrEncoder::new(w).write_image(&bytes, image.width(), image.height(), ColorType::Rgb32F)
+}
@@ -0,0 +1,911 @@
+/// Scenes imported from a subset of the pbrt-v4 file format
+/*
+ Supported directives, with pbrt's defaults:
+
+   LookAt, Translate, Scale, Rotate, Transform, ConcatTransform, Identity,
+   CoordinateSystem, CoordSysTransform, AttributeBegin/End, TransformBegin/End,
+   WorldBegin, Include and Import
+
+   Camera "perspective"         # fov = 90, lensradius = 0, focaldistance = 1e6
+   Film "rgb"                   # xresolution = 1280, yresolution = 720
+   Sampler "zsobol"             # pixelsamples = 16. Also "sobol", "paddedsobol", "halton",
+                                # "independent" and "stratified", with xsamples * ysamples samples
+   Integrator "volpath"         # maxdepth = 5. Also "path" and "bdpt"
+   PixelFilter "gaussian"       # radius = 1.5. Also "box", "triangle", "mitchell" and "sinc"
+
+   Material "diffuse"           # rgb reflectance = [0.5 0.5 0.5]
+   Material "conductor"         # rgb reflectance, or rgb eta and k, or spectrum eta naming a metal,
+                                # roughness = 0. Conductors become jrpt metals, coloured by their
+                                # reflectance head on
+   Material "dielectric"        # eta = 1.5
+   Material "interface"         # shapes with it are left out
+   MakeNamedMaterial and NamedMaterial
+
+   Shape "sphere"               # radius = 1
+   Shape "trianglemesh"         # indices, P, uv
+   Shape "plymesh"              # filename, relative to the scene file
+
+   LightSource "point"          # rgb I = [1 1 1], scale = 1, from = [0 0 0], becomes a small glowing sphere
+   LightSource "infinite"       # rgb L = [1 1 1], scale = 1, becomes the background colour
+   AreaLightSource "diffuse"    # rgb L = [1 1 1], scale = 1, makes the shapes that follow glow
+
+ Anything else, including parameters that are not listed, is left out with a warning.
+ pbrt's cameras are left-handed, so images are mirrored to match what pbrt renders.
+*/
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    f64::consts::PI,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc
+};
+use nalgebra::{Matrix4, Vector4};
+use crate::{
+    scene_file::{RenderSettings, SceneFileError, position},
+    renderer::Integrator,
+    sampler::SamplerKind,
+    filter::{Filter, FilterKind},
+    scene::Scene,
+    camera::Camera,
+    objects::{Object, object_list, sphere, triangle, bvh, affine::Transform},
+    materials::{
+        Material, lambertian::Lambertian, metal::Metal, dialetric::Dialetric, diffuse_light::DiffuseLight
+    },
+    ply,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+/// Radius of the spheres standing in for point lights
+pub const POINT_LIGHT_RADIUS: f64 = 0.01;
+
+/// Scene imported from a pbrt file, along with the settings to render it with and what was left out
+pub struct PbrtScene {
+    pub scene: Scene,
+    pub settings: RenderSettings,
+    pub warnings: Vec<Warning>
+}
+
+/// Part of a pbrt file that could not be imported, at a line and column counting from 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneFileError>;
+
+/// Imports the pbrt file at path. Files it refers to are found relative to it
+pub fn load<P: AsRef<Path>>(path: P) -> Result<PbrtScene> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+
+    parse(&source, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Parses a pbrt file, finding the files it refers to relative to base_dir
+pub fn parse(source: &str, base_dir: &Path) -> Result<PbrtScene> {
+    let mut importer = Importer {
+        base_dir: base_dir.to_path_buf(),
+        settings: RenderSettings {
+            width: 1280,
+            height: 720,
+            samples_per_pixel: 16,
+            max_depth: 5,
+            sampler: SamplerKind::Sobol,
+            filter: Filter::with_default_radius(FilterKind::Gaussian),
+            ..RenderSettings::default()
+        },
+        camera: None,
+        background: Colour::zero(),
+        state: State {
+            ctm: Transform::new(),
+            material: Some(Arc::new(Lambertian::new(Colour::from_value(0.5)))),
+            area_light: None
+        },
+        stack: vec![],
+        includes: vec![],
+        coordinate_systems: HashMap::new(),
+        materials: HashMap::new(),
+        objects: object_list::new(),
+        lights: vec![],
+        warnings: vec![]
+    };
+
+    importer.run(&mut Input::new(source, None)?)?;
+
+    let camera = importer.camera();
+    let mut objects = importer.objects;
+    if !object_list::is_empty(&objects) {
+        objects = bvh::new(objects, 0.0..0.0);
+    }
+
+    let mut scene = Scene::new(camera, objects, importer.background);
+    for light in importer.lights {
+        scene.add_light(light);
+    }
+
+    Ok(PbrtScene { scene, settings: importer.settings, warnings: importer.warnings })
+}
+
+/// Word, string or bracket of a pbrt file, at a byte offset of its source
+#[derive(Clone, Copy)]
+struct Token<'s> {
+    text: &'s str,
+    offset: usize,
+    quoted: bool
+}
+
+impl Token<'_> {
+    fn is_directive(&self) -> bool {
+        !self.quoted && self.text.starts_with(|c: char| c.is_ascii_uppercase())
+    }
+}
+
+/// Tokens of one file, which is either the one being imported or one it includes
+struct Input<'s> {
+    source: &'s str,
+    file: Option<String>,   // name of an included file
+    tokens: Vec<Token<'s>>,
+    next: usize
+}
+
+impl<'s> Input<'s> {
+    fn new(source: &'s str, file: Option<String>) -> Result<Self> {
+        let mut input = Self { source, file, tokens: vec![], next: 0 };
+        let bytes = source.as_bytes();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'#' => i = source[i..].find('\n').map_or(bytes.len(), |end| i + end),
+                b'"' => {
+                    let end = source[i + 1..].find('"').ok_or_else(|| input.error(i, "string is never closed"))?;
+                    input.tokens.push(Token { text: &source[i + 1..i + 1 + end], offset: i, quoted: true });
+                    i += end + 2;
+                },
+                b'[' | b']' => {
+                    input.tokens.push(Token { text: &source[i..i + 1], offset: i, quoted: false });
+                    i += 1;
+                },
+                c if c.is_ascii_whitespace() => i += 1,
+                _ => {
+                    let end = source[i..].find(|c: char| c.is_ascii_whitespace() || "[]\"#".contains(c)).map_or(bytes.len(), |end| i + end);
+                    input.tokens.push(Token { text: &source[i..end], offset: i, quoted: false });
+                    i = end;
+                }
+            }
+        }
+
+        Ok(input)
+    }
+
+    fn error(&self, offset: usize, message: &str) -> SceneFileError {
+        let (line, column) = position(self.source, Some(offset..offset));
+        let message = match &self.file {
+            Some(file) => format!("in {}: {}", file, message),
+            None => message.to_string()
+        };
+
+        SceneFileError::Syntax { line, column, message }
+    }
+
+    fn peek(&self) -> Option<Token<'s>> {
+        self.tokens.get(self.next).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'s>> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    /// Offset to report problems at when the file ends early
+    fn end(&self) -> usize {
+        self.source.len()
+    }
+
+    fn string(&mut self) -> Result<&'s str> {
+        match self.next() {
+            Some(token) if token.quoted => Ok(token.text),
+            Some(token) => Err(self.error(token.offset, "expected a string")),
+            None => Err(self.error(self.end(), "expected a string"))
+        }
+    }
+
+    /// Reads N numbers, which may be in brackets
+    fn numbers<const N: usize>(&mut self) -> Result<[f64; N]> {
+        let bracketed = self.peek().is_some_and(|token| !token.quoted && token.text == "[");
+        if bracketed {
+            self.next();
+        }
+
+        let mut numbers = [0.0; N];
+        for n in numbers.iter_mut() {
+            *n = match self.next() {
+                Some(token) => number(self, token)?,
+                None => return Err(self.error(self.end(), &format!("expected {} numbers", N)))
+            };
+        }
+
+        if bracketed {
+            match self.next() {
+                Some(token) if !token.quoted && token.text == "]" => (),
+                Some(token) => return Err(self.error(token.offset, &format!("expected ] after {} numbers", N))),
+                None => return Err(self.error(self.end(), "expected ]"))
+            }
+        }
+
+        Ok(numbers)
+    }
+
+    /// Reads the parameter list following a directive's fixed arguments
+    fn params(&mut self) -> Result<Params<'s>> {
+        let mut params = vec![];
+
+        while let Some(declaration) = self.peek().filter(|token| token.quoted) {
+            let (ty, name) = match declaration.text.split_whitespace().collect::<Vec<_>>()[..] {
+                [ty, name] => (ty, name),
+                _ => return Err(self.error(declaration.offset, &format!("expected a parameter, not \"{}\"", declaration.text)))
+            };
+            self.next();
+
+            let ty = match ty {
+                "point" => "point3",
+                "vector" => "vector3",
+                "normal" => "normal3",
+                "color" => "rgb",
+                ty => ty
+            };
+
+            let mut values = vec![];
+            match self.next() {
+                Some(token) if !token.quoted && token.text == "[" => loop {
+                    match self.next() {
+                        Some(token) if !token.quoted && token.text == "]" => break,
+                        Some(token) => values.push(token),
+                        None => return Err(self.error(token.offset, "[ is never closed"))
+                    }
+                },
+                Some(token) if token.quoted || !token.is_directive() => values.push(token),
+                _ => return Err(self.error(declaration.offset, &format!("{} has no value", name)))
+            }
+
+            params.push(Param { ty, name, values, offset: declaration.offset, read: Cell::new(false) });
+        }
+
+        Ok(Params(params))
+    }
+
+    /// Skips the arguments of a directive that is left out
+    fn skip(&mut self) {
+        while self.peek().is_some_and(|token| !token.is_directive()) {
+            self.next();
+        }
+    }
+}
+
+fn number(input: &Input, token: Token) -> Result<f64> {
+    match token.quoted {
+        false => token.text.parse().map_err(|_| input.error(token.offset, &format!("expected a number, not {}", token.text))),
+        true => Err(input.error(token.offset, "expected a number, not a string"))
+    }
+}
+
+/// Parameter of a directive, such as "float radius" [2]
+struct Param<'s> {
+    ty: &'s str,
+    name: &'s str,
+    values: Vec<Token<'s>>,
+    offset: usize,
+    read: Cell<bool>        // parameters never read are reported as left out
+}
+
+struct Params<'s>(Vec<Param<'s>>);
+
+impl<'s> Params<'s> {
+    /// Finds the parameter called name with one of the given types
+    fn get(&self, name: &str, types: &[&str]) -> Option<&Param<'s>> {
+        let param = self.0.iter().find(|param| param.name == name && types.contains(&param.ty))?;
+        param.read.set(true);
+        Some(param)
+    }
+
+    fn numbers(&self, input: &Input, name: &str, types: &[&str]) -> Result<Option<Vec<f64>>> {
+        match self.get(name, types) {
+            Some(param) => Ok(Some(param.values.iter().map(|&token| number(input, token)).collect::<Result<_>>()?)),
+            None => Ok(None)
+        }
+    }
+
+    fn float(&self, input: &Input, name: &str, default: f64) -> Result<f64> {
+        self.single(input, name, &["float"], default)
+    }
+
+    fn integer(&self, input: &Input, name: &str, default: u32) -> Result<u32> {
+        let value = self.single(input, name, &["integer"], default as f64)?;
+
+        match value >= 0.0 && value.fract() == 0.0 {
+            true => Ok(value as u32),
+            false => Err(input.error(self.get(name, &["integer"]).unwrap().offset, &format!("{} must be a non-negative integer", name)))
+        }
+    }
+
+    fn single(&self, input: &Input, name: &str, types: &[&str], default: f64) -> Result<f64> {
+        match self.numbers(input, name, types)? {
+            Some(values) if values.len() == 1 => Ok(values[0]),
+            Some(_) => Err(input.error(self.get(name, types).unwrap().offset, &format!("{} expects one value", name))),
+            None => Ok(default)
+        }
+    }
+
+    fn triple(&self, input: &Input, name: &str, types: &[&str]) -> Result<Option<Vec3>> {
+        match self.numbers(input, name, types)? {
+            Some(values) if values.len() == 3 => Ok(Some(Vec3::new(values[0], values[1], values[2]))),
+            Some(_) => Err(input.error(self.get(name, types).unwrap().offset, &format!("{} expects three values", name))),
+            None => Ok(None)
+        }
+    }
+
+    fn rgb(&self, input: &Input, name: &str) -> Result<Option<Colour>> {
+        self.triple(input, name, &["rgb"])
+    }
+
+    fn string(&self, input: &Input, name: &str) -> Result<Option<&'s str>> {
+        match self.get(name, &["string"]) {
+            Some(param) => match param.values[..] {
+                [token] if token.quoted => Ok(Some(token.text)),
+                _ => Err(input.error(param.offset, &format!("{} expects one string", name)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Marks the parameter called name as never read, for values that turn out to be left out
+    fn forget(&self, name: &str, types: &[&str]) {
+        if let Some(param) = self.0.iter().find(|param| param.name == name && types.contains(&param.ty)) {
+            param.read.set(false);
+        }
+    }
+
+    /// Returns the name of a spectrum given by name, such as "metal-Cu-eta"
+    fn named_spectrum(&self, name: &str) -> Option<&'s str> {
+        let param = self.0.iter().find(|param| param.name == name && param.ty == "spectrum")?;
+
+        match param.values[..] {
+            [token] if token.quoted => {
+                param.read.set(true);
+                Some(token.text)
+            },
+            _ => None
+        }
+    }
+}
+
+/// Everything AttributeBegin saves and AttributeEnd restores
+#[derive(Clone)]
+struct State {
+    ctm: Transform,                         // current transformation matrix, from the object to the world
+    material: Option<Arc<dyn Material>>,    // None for interfaces, which are left out
+    area_light: Option<Colour>              // emitted radiance of the shapes that follow
+}
+
+struct Importer {
+    base_dir: PathBuf,
+    settings: RenderSettings,
+    camera: Option<(Transform, f64, f64, f64)>,     // world from camera, fov, lens radius and focal distance
+    background: Colour,
+    state: State,
+    stack: Vec<(State, bool)>,      // saved states, and whether only their transforms are restored
+    includes: Vec<PathBuf>,         // files being included, the innermost last
+    coordinate_systems: HashMap<String, Transform>,
+    materials: HashMap<String, Option<Arc<dyn Material>>>,
+    objects: Object,
+    lights: Vec<Object>,
+    warnings: Vec<Warning>
+}
+
+impl Importer {
+    fn warn(&mut self, input: &Input, offset: usize, message: &str) {
+        let (line, column) = position(input.source, Some(offset..offset));
+        let message = match &input.file {
+            Some(file) => format!("in {}: {}", file, message),
+            None => message.to_string()
+        };
+
+        self.warnings.push(Warning { line, column, message });
+    }
+
+    /// Warns about each parameter of directive that was never read
+    fn finish(&mut self, input: &Input, directive: &str, params: Params) {
+        for param in params.0.iter().filter(|param| !param.read.get()) {
+            self.warn(input, param.offset, &format!("unsupported parameter \"{} {}\" of {} is left out", param.ty, param.name, directive));
+        }
+    }
+
+    /// Applies transform to objects before the current transformation matrix
+    fn concat(&mut self, transform: Transform) {
+        self.state.ctm = transform.then(self.state.ctm);
+    }
+
+    fn run(&mut self, input: &mut Input) -> Result<()> {
+        while let Some(token) = input.next() {
+            if !token.is_directive() {
+                return Err(input.error(token.offset, &format!("expected a directive, not {}", token.text)));
+            }
+
+            match token.text {
+                "AttributeBegin" | "TransformBegin" => self.stack.push((self.state.clone(), token.text == "TransformBegin")),
+                "AttributeEnd" | "TransformEnd" => {
+                    let (state, transform_only) = self.stack.pop().ok_or_else(|| input.error(token.offset, &format!("{} without a matching begin", token.text)))?;
+
+                    match transform_only {
+                        true => self.state.ctm = state.ctm,
+                        false => self.state = state
+                    }
+                },
+                "Identity" => self.state.ctm = Transform::new(),
+                "Translate" => {
+                    let [x, y, z] = input.numbers()?;
+                    self.concat(Transform::new().translate(x, y, z));
+                },
+                "Scale" => {
+                    let [x, y, z] = input.numbers()?;
+                    self.concat(Transform::new().scale(x, y, z));
+                },
+                "Rotate" => {
+                    let [angle, x, y, z] = input.numbers()?;
+                    self.concat(Transform::new().rotate(&Vec3::new(x, y, z), angle.to_radians()));
+                },
+                "LookAt" => {
+                    let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = input.numbers()?;
+                    let world_from_camera = Transform::new().look_at(&Point3::new(ex, ey, ez), &Point3::new(lx, ly, lz), &Vec3::new(ux, uy, uz));
+                    let camera_from_world = world_from_camera.inverse().map_err(|_| input.error(token.offset, "LookAt needs an up direction away from the view direction"))?;
+                    self.concat(camera_from_world);
+                },
+                "Transform" | "ConcatTransform" => {
+                    // given column by column
+                    let m = input.numbers::<16>()?;
+                    let transform = Transform::from_matrix(Matrix4::from_column_slice(&m));
+
+                    match token.text {
+                        "Transform" => self.state.ctm = transform,
+                        _ => self.concat(transform)
+                    }
+                },
+                "CoordinateSystem" => {
+                    let name = input.string()?;
+                    self.coordinate_systems.insert(name.to_string(), self.state.ctm);
+                },
+                "CoordSysTransform" => {
+                    let name = input.string()?;
+                    match self.coordinate_systems.get(name) {
+                        Some(ctm) => self.state.ctm = *ctm,
+                        None => self.warn(input, token.offset, &format!("no coordinate system named {}", name))
+                    }
+                },
+                "WorldBegin" => {
+                    self.state.ctm = Transform::new();
+                    self.coordinate_systems.insert("world".to_string(), self.state.ctm);
+                },
+                "Include" | "Import" => {
+                    let name = input.string()?;
+                    let path = self.base_dir.join(name);
+                    let source = fs::read_to_string(&path).map_err(|err| input.error(token.offset, &format!("{}: {}", path.display(), err)))?;
+
+                    // files are told apart by where they really are, however they are named
+                    let file = fs::canonicalize(&path).unwrap_or(path);
+                    if self.includes.contains(&file) {
+                        return Err(input.error(token.offset, &format!("{} includes itself", name)));
+                    }
+
+                    self.includes.push(file);
+                    self.run(&mut Input::new(&source, Some(name.to_string()))?)?;
+                    self.includes.pop();
+                },
+                "Camera" => self.camera_directive(input, token)?,
+                "Film" | "Sampler" | "Integrator" | "PixelFilter" => self.settings_directive(input, token)?,
+                "Material" => {
+                    let kind = input.string()?;
+                    let params = input.params()?;
+                    self.state.material = self.material(input, token.offset, kind, &params)?;
+                    self.finish(input, "Material", params);
+                },
+                "MakeNamedMaterial" => {
+                    let name = input.string()?;
+                    let params = input.params()?;
+                    let kind = params.string(input, "type")?.ok_or_else(|| input.error(token.offset, &format!("material {} has no type", name)))?;
+                    let material = self.material(input, token.offset, kind, &params)?;
+                    self.materials.insert(name.to_string(), material);
+                    self.finish(input, "MakeNamedMaterial", params);
+                },
+                "NamedMaterial" => {
+                    let name = input.string()?;
+                    self.state.material = self.materials.get(name).cloned().ok_or_else(|| input.error(token.offset, &format!("no material named {}", name)))?;
+                },
+                "Shape" => self.shape(input, token)?,
+                "LightSource" => self.light(input, token)?,
+                "AreaLightSource" => {
+                    let kind = input.string()?;
+                    let params = input.params()?;
+
+                    match kind {
+                        "diffuse" => {
+                            let emit = params.rgb(input, "L")?.unwrap_or(Colour::from_value(1.0));
+                            self.state.area_light = Some(params.float(input, "scale", 1.0)? * emit);
+                        },
+                        _ => {
+                            self.warn(input, token.offset, &format!("unsupported area light {} is left out", kind));
+                            continue;
+                        }
+                    }
+
+                    self.finish(input, "AreaLightSource", params);
+                },
+                other => {
+                    self.warn(input, token.offset, &format!("unsupported directive {} is left out", other));
+                    input.skip();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn camera_directive(&mut self, input: &mut Input, token: Token) -> Result<()> {
+        let kind = input.string()?;
+        let params = input.params()?;
+
+        if kind != "perspective" {
+            self.warn(input, token.offset, &format!("unsupported camera {} is replaced by a perspective camera", kind));
+        }
+
+        let fov = params.float(input, "fov", 90.0)?;
+        let lens_radius = params.float(input, "lensradius", 0.0)?;
+        let focal_distance = params.float(input, "focaldistance", 1e6)?;
+
+        let world_from_camera = self.state.ctm.inverse().map_err(|_| input.error(token.offset, "camera transform can not be inverted"))?;
+        self.coordinate_systems.insert("camera".to_string(), world_from_camera);
+        self.camera = Some((world_from_camera, fov, lens_radius, focal_distance));
+
+        self.finish(input, "Camera", params);
+        Ok(())
+    }
+
+    fn settings_directive(&mut self, input: &mut Input, token: Token) -> Result<()> {
+        let kind = input.string()?;
+        let params = input.params()?;
+        let settings = &mut self.settings;
+        let mut supported = true;
+
+        match token.text {
+            "Film" => {
+                supported = matches!(kind, "rgb" | "gbuffer" | "spectral");
+                settings.width = params.integer(input, "xresolution", 1280)?;
+                settings.height = params.integer(input, "yresolution", 720)?;
+                params.string(input, "filename")?;     // images are written where the command line says
+
+                if settings.width == 0 || settings.height == 0 {
+                    return Err(input.error(token.offset, "image must be at least 1 by 1"));
+                }
+            },
+            "Sampler" => {
+                settings.sampler = match kind {
+                    "independent" => SamplerKind::Independent,
+                    "stratified" => SamplerKind::Stratified,
+                    "halton" => SamplerKind::Halton,
+                    "sobol" | "zsobol" | "paddedsobol" => SamplerKind::Sobol,
+                    _ => {
+                        supported = false;
+                        SamplerKind::Sobol
+                    }
+                };
+
+                settings.samples_per_pixel = match kind {
+                    "stratified" => params.integer(input, "xsamples", 4)? * params.integer(input, "ysamples", 4)?,
+                    _ => params.integer(input, "pixelsamples", 16)?
+                }.max(1);
+                settings.seed = params.integer(input, "seed", 0)? as u64;
+            },
+            "Integrator" => {
+                settings.integrator = match kind {
+                    "path" | "volpath" => Integrator::PathTracer,
+                    "bdpt" => Integrator::Bidirectional,
+                    _ => {
+                        supported = false;
+                        Integrator::PathTracer
+                    }
+                };
+                settings.max_depth = params.integer(input, "maxdepth", 5)?;
+            },
+            _ => {
+                let filter_kind = match kind {
+                    "box" => FilterKind::Box,
+                    "triangle" => FilterKind::Tent,
+                    "gaussian" => FilterKind::Gaussian,
+                    "mitchell" => FilterKind::Mitchell,
+                    "sinc" => FilterKind::Lanczos,
+                    _ => {
+                        supported = false;
+                        FilterKind::Gaussian
+                    }
+                };
+
+                // pbrt gives a radius for each axis, jrpt's filters are round and take the widest.
+                // Each is checked where it is, as the widest alone would let a NaN through
+                let filters = match params.get("radius", &["float"]) {
+                    Some(param) => param.values.iter().map(|&value| {
+                        Filter::new(filter_kind, number(input, value)?).map_err(|err| input.error(value.offset, &err.to_string()))
+                    }).collect::<Result<Vec<_>>>()?,
+                    None => Vec::new()
+                };
+                settings.filter = filters.into_iter().max_by(|a, b| a.radius().total_cmp(&b.radius())).unwrap_or(Filter::with_default_radius(filter_kind));
+            }
+        }
+
+        if !supported {
+            self.warn(input, token.offset, &format!("unsupported {} {} is replaced by the default", token.text, kind));
+        }
+
+        self.finish(input, token.text, params);
+        Ok(())
+    }
+
+    /// Builds a material of the given type, or None for interfaces
+    fn material(&mut self, input: &Input, offset: usize, kind: &str, params: &Params) -> Result<Option<Arc<dyn Material>>> {
+        // only read by the materials that use it, so the others warn that it is left out
+        let reflectance = || params.rgb(input, "reflectance");
+
+        let material: Arc<dyn Material> = match kind {
+            "diffuse" => Arc::new(Lambertian::new(reflectance()?.unwrap_or(Colour::from_value(0.5)))),
+            "conductor" => {
+                let albedo = match reflectance()? {
+                    Some(reflectance) => reflectance,
+                    None => match (params.rgb(input, "eta")?, params.rgb(input, "k")?) {
+                        (Some(eta), Some(k)) => fresnel_conductor(&eta, &k),
+                        _ => {
+                            // one without the other is left out
+                            params.forget("eta", &["rgb"]);
+                            params.forget("k", &["rgb"]);
+
+                            match params.named_spectrum("eta") {
+                                Some(name) => metal_colour(name).unwrap_or_else(|| {
+                                    self.warn(input, offset, &format!("unknown metal {} is replaced by copper", name));
+                                    metal_colour("metal-Cu-eta").unwrap()
+                                }),
+                                None => metal_colour("metal-Cu-eta").unwrap()
+                            }
+                        }
+                    }
+                };
+                params.named_spectrum("k");
+
+                let roughness = match params.numbers(input, "roughness", &["float"])? {
+                    Some(_) => params.float(input, "roughness", 0.0)?,
+                    None => (params.float(input, "uroughness", 0.0)? + params.float(input, "vroughness", 0.0)?) / 2.0
+                };
+                params.get("remaproughness", &["bool"]);
+
+                Arc::new(Metal::new(albedo, roughness.clamp(0.0, 1.0)))
+            },
+            "dielectric" | "thindielectric" => {
+                if kind == "thindielectric" {
+                    self.warn(input, offset, "thindielectric is treated as a solid dielectric");
+                }
+
+                let eta = match params.named_spectrum("eta") {
+                    Some(name) => {
+                        self.warn(input, offset, &format!("spectrum {} is replaced by an index of refraction of 1.5", name));
+                        1.5
+                    },
+                    None => params.float(input, "eta", 1.5)?
+                };
+
+                Arc::new(Dialetric::new(eta))
+            },
+            "interface" => return Ok(None),
+            _ => {
+                self.warn(input, offset, &format!("unsupported material {} is replaced by a diffuse material", kind));
+                Arc::new(Lambertian::new(reflectance()?.unwrap_or(Colour::from_value(0.5))))
+            }
+        };
+
+        Ok(Some(material))
+    }
+
+    fn shape(&mut self, input: &mut Input, token: Token) -> Result<()> {
+        let kind = input.string()?;
+        let params = input.params()?;
+
+        let material: Arc<dyn Material> = match (&self.state.area_light, &self.state.material) {
+            (Some(emit), _) => Arc::new(DiffuseLight::new(*emit)),
+            (None, Some(material)) => material.clone(),
+            (None, None) => {
+                self.warn(input, token.offset, &format!("{} with an interface material is left out", kind));
+                return Ok(());
+            }
+        };
+        let light = self.state.area_light.is_some();
+        let ctm = self.state.ctm;
+
+        match kind {
+            "sphere" => {
+                let radius = params.float(input, "radius", 1.0)?;
+
+                match similarity(&ctm) {
+                    Some((centre, scale)) => {
+                        // built twice for lights, once for the scene and once for sampling
+                        object_list::add(&mut self.objects, sphere::new(centre, radius * scale, material.clone()));
+                        if light {
+                            self.lights.push(sphere::new(centre, radius * scale, material));
+                        }
+                    },
+                    None => {
+                        let sphere = ctm.apply(sphere::new(Point3::zero(), radius, material))
+                            .map_err(|err| input.error(token.offset, &err.to_string()))?;
+                        object_list::add(&mut self.objects, sphere);
+
+                        if light {
+                            self.warn(input, token.offset, "stretched spheres are not sampled as lights");
+                        }
+                    }
+                }
+            },
+            "trianglemesh" | "plymesh" => {
+                let (positions, triangles, uv) = match kind {
+                    "trianglemesh" => {
+                        let positions = params.numbers(input, "P", &["point3"])?.ok_or_else(|| input.error(token.offset, "trianglemesh has no P"))?;
+                        if positions.len() % 3 != 0 {
+                            return Err(input.error(token.offset, "trianglemesh P must come in threes"));
+                        }
+                        let positions: Vec<Point3> = positions.chunks(3).map(|p| Point3::new(p[0], p[1], p[2])).collect();
+
+                        let indices = match params.numbers(input, "indices", &["integer"])? {
+                            Some(indices) if indices.iter().all(|&i| i >= 0.0 && i.fract() == 0.0) => indices.into_iter().map(|i| i as usize).collect(),
+                            Some(_) => return Err(input.error(token.offset, "trianglemesh indices must be non-negative integers")),
+                            None if positions.len() == 3 => vec![0, 1, 2],
+                            None => return Err(input.error(token.offset, "trianglemesh has no indices"))
+                        };
+
+                        if indices.len() % 3 != 0 || indices.iter().any(|&i| i >= positions.len()) {
+                            return Err(input.error(token.offset, "trianglemesh indices must come in threes and refer to points of P"));
+                        }
+
+                        (positions, indices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect(), params.numbers(input, "uv", &["point2"])?)
+                    },
+                    _ => {
+                        let name = params.string(input, "filename")?.ok_or_else(|| input.error(token.offset, "plymesh has no filename"))?;
+                        let path = self.base_dir.join(name);
+                        let mesh = ply::load(&path).map_err(|err| input.error(token.offset, &format!("{}: {}", path.display(), err)))?;
+                        (mesh.positions, mesh.triangles, None)
+                    }
+                };
+
+                if uv.as_ref().is_some_and(|uv| uv.len() != 2 * positions.len()) {
+                    return Err(input.error(token.offset, "trianglemesh needs a uv for each point of P"));
+                }
+
+                // triangles take a single uv, the average of their corners'
+                let uv = |corners: [usize; 3]| match &uv {
+                    Some(uv) => corners.iter().fold((0.0, 0.0), |(u, v), &i| (u + uv[2 * i] / 3.0, v + uv[2 * i + 1] / 3.0)),
+                    None => (0.0, 0.0)
+                };
+
+                let positions: Vec<Point3> = positions.iter().map(|p| transform_point(&ctm, p)).collect();
+                for corners in triangles {
+                    let [p0, p1, p2] = corners.map(|i| positions[i]);
+
+                    object_list::add(&mut self.objects, triangle::new(p0, p1, p2, None, Some(uv(corners)), material.clone()));
+                    if light {
+                        self.lights.push(triangle::new(p0, p1, p2, None, Some(uv(corners)), material.clone()));
+                    }
+                }
+            },
+            _ => {
+                self.warn(input, token.offset, &format!("unsupported shape {} is left out", kind));
+                return Ok(());
+            }
+        }
+
+        self.finish(input, "Shape", params);
+        Ok(())
+    }
+
+    fn light(&mut self, input: &mut Input, token: Token) -> Result<()> {
+        let kind = input.string()?;
+        let params = input.params()?;
+
+        match kind {
+            "point" => {
+                let intensity = params.float(input, "scale", 1.0)? * params.rgb(input, "I")?.unwrap_or(Colour::from_value(1.0));
+                let from = params.triple(input, "from", &["point3"])?.unwrap_or(Point3::zero());
+                let centre = transform_point(&self.state.ctm, &from);
+
+                // a sphere glowing with radiance L gives off the power of a point light of intensity L * pi * r^2
+                let emit = intensity / (PI * POINT_LIGHT_RADIUS * POINT_LIGHT_RADIUS);
+                let material: Arc<dyn Material> = Arc::new(DiffuseLight::new(emit));
+
+                object_list::add(&mut self.objects, sphere::new(centre, POINT_LIGHT_RADIUS, material.clone()));
+                self.lights.push(sphere::new(centre, POINT_LIGHT_RADIUS, material));
+            },
+            "infinite" => {
+                let radiance = params.rgb(input, "L")?.unwrap_or(Colour::from_value(1.0));
+                self.background = params.float(input, "scale", 1.0)? * radiance;
+            },
+            _ => {
+                self.warn(input, token.offset, &format!("unsupported light {} is left out", kind));
+                return Ok(());
+            }
+        }
+
+        self.finish(input, "LightSource", params);
+        Ok(())
+    }
+
+    /// Builds the camera, looking down the z axis from the origin if the file has none
+    fn camera(&self) -> Camera {
+        let (world_from_camera, fov, lens_radius, focal_distance) = self.camera.unwrap_or((Transform::new(), 90.0, 0.0, 1e6));
+
+        let eye = transform_point(&world_from_camera, &Point3::zero());
+        let at = transform_point(&world_from_camera, &Point3::new(0.0, 0.0, 1.0));
+        let up = transform_point(&world_from_camera, &Point3::new(0.0, 1.0, 0.0)) - eye;
+
+        // fov spans the shorter side of the image
+        let aspect_ratio = self.settings.width as f64 / self.settings.height as f64;
+        let vfov = match aspect_ratio < 1.0 {
+            true => 2.0 * ((fov.to_radians() / 2.0).tan() / aspect_ratio).atan().to_degrees(),
+            false => fov
+        };
+
+        let mut camera = Camera::new(eye, at, up, vfov, aspect_ratio, 2.0 * lens_radius, focal_distance, 0.0..0.0);
+        camera.mirror();
+        camera
+    }
+}
+
+fn transform_point(transform: &Transform, p: &Point3) -> Point3 {
+    let v = transform.matrix() * Vector4::new(p.x, p.y, p.z, 1.0);
+    Point3::new(v.x, v.y, v.z)
+}
+
+/// Returns where the origin moves to and how much things grow, if transform keeps shapes the same
+fn similarity(transform: &Transform) -> Option<(Point3, f64)> {
+    let m = transform.matrix();
+    let axes: Vec<Vec3> = (0..3).map(|i| Vec3::new(m[(0, i)], m[(1, i)], m[(2, i)])).collect();
+    let scale = axes[0].length();
+
+    let same_length = axes.iter().all(|axis| (axis.length() - scale).abs() <= 1e-9 * scale);
+    let square = axes[0].dot(&axes[1]).abs() + axes[1].dot(&axes[2]).abs() + axes[0].dot(&axes[2]).abs() <= 1e-9 * scale * scale;
+    let affine = m[(3, 0)] == 0.0 && m[(3, 1)] == 0.0 && m[(3, 2)] == 0.0 && m[(3, 3)] == 1.0;
+
+    (scale > 0.0 && same_length && square && affine).then(|| (transform_point(transform, &Point3::zero()), scale))
+}
+
+/// Reflectance head on of a conductor with complex index of refraction eta + ik for each channel
+fn fresnel_conductor(eta: &Colour, k: &Colour) -> Colour {
+    let f0 = |eta: f64, k: f64| ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+    Colour::new(f0(eta.x, k.x), f0(eta.y, k.y), f0(eta.z, k.z))
+}
+
+/// Reflectance head on of the metals pbrt names by their eta spectra
+fn metal_colour(name: &str) -> Option<Colour> {
+    match name {
+        "metal-Ag-eta" => Some(Colour::new(0.97, 0.96, 0.92)),
+        "metal-Al-eta" => Some(Colour::new(0.91, 0.92, 0.92)),
+        "metal-Au-eta" => Some(Colour::new(1.0, 0.78, 0.34)),
+        "metal-Cu-eta" => Some(Colour::new(0.96, 0.64, 0.54)),
+        "metal-CuZn-eta" => Some(Colour::new(0.94, 0.80, 0.55)),
+        "metal-MgO-eta" => Some(Colour::new(0.07, 0.07, 0.07)),
+        "metal-TiO2-eta" => Some(Colour::new(0.22, 0.20, 0.18)),
+        _ => None
+    }
+}
@@ -0,0 +1,233 @@
+/// Photon mapping for caustics
+/*
+ Photons are emitted from the scene's lights and followed through mirrors and
+ glass until they land on the first surface that can evaluate its BSDF, where
+ they are stored if they bounced off at least one specular surface on the way.
+ The stored photons only make up light along light -> specular+ -> diffuse
+ paths, which path tracing finds poorly, so the path tracer estimates that part
+ of the light from the photon map and leaves the rest to its own paths.
+*/
+use std::{cmp::Ordering, f64::consts::PI};
+use rand::{rngs::SmallRng, SeedableRng};
+use rayon::prelude::*;
+use crate::{
+    sampler::Sampler,
+    stats::{self, Counter, RenderStats},
+    scene::Scene,
+    ray::Ray,
+    constants::{EPSILON, INFINITY},
+    objects::{Intersection, object_list},
+    renderer::russian_roulette,
+    bidirectional::{emission_direction, emission_pdf},
+    random::stream_seed,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+// photons traced with each random stream
+const BATCH_SIZE: u32 = 1024;
+
+// photons are only gathered onto surfaces facing close to the way the surface they landed on faced
+const MIN_NORMAL_COSINE: f64 = 0.9;
+
+struct Photon {
+    p: Point3,
+    dir: Vec3,              // direction the photon was travelling in
+    n: Vec3,                // normal of the surface it landed on, on the side it arrived from
+    power: Colour,          // flux carried by the photon
+    axis: i32               // axis this photon splits its subtree along in the kd-tree
+}
+
+/// Caustic photons from one pass, stored in a kd-tree for gathering within a fixed radius
+pub(crate) struct PhotonMap {
+    photons: Vec<Photon>,   // implicit kd-tree, each subslice has its splitting photon in the middle
+    radius: f64,
+    pub stats: RenderStats  // work done tracing the photons
+}
+
+impl PhotonMap {
+    /// Emits num_photons photons from the scene's lights and stores those making caustics.
+    /// Photons are traced in batches with their own random streams derived from seed,
+    /// so the map does not depend on how the batches are scheduled
+    pub(crate) fn build(scene: &Scene, num_photons: u32, (depth, roulette_depth): (u32, u32), radius: f64, (seed, multithreading): (u64, bool)) -> Self {
+        let num_batches = num_photons.div_ceil(BATCH_SIZE);
+
+        let trace_batch = |batch: u32| stats::measure(|| {
+            let rng = &mut SmallRng::seed_from_u64(stream_seed(seed, &[batch as u64]));
+            let count = BATCH_SIZE.min(num_photons - batch * BATCH_SIZE);
+
+            (0..count).filter_map(|_| trace_photon(rng, scene, (depth, roulette_depth))).collect::<Vec<Photon>>()
+        });
+
+        let batches: Vec<(Vec<Photon>, RenderStats)> = if multithreading {
+            (0..num_batches).into_par_iter().map(trace_batch).collect()
+        } else {
+            (0..num_batches).map(trace_batch).collect()
+        };
+
+        // photons carry the light's whole power, shared between all photons emitted
+        let scale = 1.0 / num_photons.max(1) as f64;
+        let mut stats = RenderStats::default();
+        let mut photons = vec![];
+
+        for (batch, batch_stats) in batches {
+            photons.extend(batch.into_iter().map(|photon| Photon { power: photon.power * scale, ..photon }));
+            stats += &batch_stats;
+        }
+
+        build_tree(&mut photons);
+
+        Self { photons, radius, stats }
+    }
+
+    /// Returns true if no photons were stored, as in scenes without mirrors or glass
+    pub(crate) fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Estimates the light reflected towards the start of r at rec by caustic photons landing within the map's radius.
+    /// Returns zero for surfaces that can't evaluate their BSDF
+    pub(crate) fn radiance(&self, r: &Ray, rec: &Intersection) -> Colour {
+        let mut total = Colour::zero();
+
+        if self.is_empty() {
+            return total;
+        }
+
+        let n = rec.n.normalized();
+
+        gather(&self.photons, &rec.p, self.radius * self.radius, &mut |photon| {
+            if photon.n.dot(&n) < MIN_NORMAL_COSINE {
+                return;
+            }
+
+            // eval includes the cosine at rec, which the photon's flux already accounts for
+            let wi = -photon.dir;
+            let cosine = n.dot(&wi);
+
+            if cosine <= 0.0 {
+                return;
+            }
+
+            if let Some(f) = rec.material.eval(r, rec, &wi) {
+                total += f * photon.power / cosine;
+            }
+        });
+
+        total / (PI * self.radius * self.radius)
+    }
+}
+
+/// Whether rec is on a surface photons are stored on and gathered from.
+/// Mirrors, glass and media are not
+pub(crate) fn is_diffuse(r: &Ray, rec: &Intersection) -> bool {
+    !rec.material.is_volumetric() && rec.material.eval(r, rec, &rec.n).is_some()
+}
+
+/// Follows one photon from a random point on a light, returning it if it lands on a diffuse surface after a specular bounce
+fn trace_photon(rng: &mut dyn Sampler, scene: &Scene, (depth, roulette_depth): (u32, u32)) -> Option<Photon> {
+    let lights = &scene.lights;
+
+    if object_list::is_empty(lights) {
+        return None;
+    }
+
+    let time = scene.camera.random_time(rng);
+    let (light, pdf_area) = (lights.sample_point)(lights, rng, time)?;
+
+    if pdf_area <= 0.0 {
+        return None;
+    }
+
+    let dir = emission_direction(rng, &light.n);
+    let pdf_dir = emission_pdf(&light, &(light.p + dir));
+
+    if pdf_dir <= 0.0 {
+        return None;
+    }
+
+    // the cosine at the light over pdf_dir is 2pi
+    let mut power = light.material.emitted(light.u, light.v, &light.p) * (2.0 * PI / pdf_area);
+    let mut ray = Ray::new(light.p, dir, time);
+
+    for bounce in 0..depth {
+        stats::count(Counter::SecondaryRays);
+        let rec = (scene.objects.intersect)(&scene.objects, rng, &ray, EPSILON, INFINITY)?;
+
+        // photons in media are not stored, the path tracer handles media
+        if rec.material.is_volumetric() {
+            return None;
+        }
+
+        if is_diffuse(&ray, &rec) {
+            if bounce == 0 {
+                return None;
+            }
+
+            return Some(Photon { p: rec.p, dir: ray.dir.normalized(), n: rec.n.normalized(), power, axis: 0 });
+        }
+
+        let (attenuation, scattered) = rec.material.scatter(rng, ray, &rec)?;
+        stats::count(Counter::ScatterEvents);
+        power = power * attenuation;
+
+        if bounce >= roulette_depth {
+            power *= russian_roulette(rng, &power)?;
+        }
+
+        ray = scattered;
+    }
+
+    None
+}
+
+/// Arranges photons into a balanced kd-tree, splitting each subslice at its median along the axis it is widest in
+fn build_tree(photons: &mut [Photon]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let (mut min, mut max) = (Point3::from_value(INFINITY), Point3::from_value(-INFINITY));
+    for photon in photons.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(photon.p[axis]);
+            max[axis] = max[axis].max(photon.p[axis]);
+        }
+    }
+
+    let extent = max - min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.p[axis].partial_cmp(&b.p[axis]).unwrap_or(Ordering::Equal));
+    photons[mid].axis = axis;
+
+    let (left, right) = photons.split_at_mut(mid);
+    build_tree(left);
+    build_tree(&mut right[1..]);
+}
+
+/// Calls f on every photon within squared distance radius_squared of p
+fn gather<F: FnMut(&Photon)>(photons: &[Photon], p: &Point3, radius_squared: f64, f: &mut F) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let mid = photons.len() / 2;
+    let photon = &photons[mid];
+
+    if (photon.p - *p).length_squared() <= radius_squared {
+        f(photon);
+    }
+
+    let d = p[photon.axis] - photon.p[photon.axis];
+    let (near, far) = if d < 0.0 { (&photons[..mid], &photons[mid + 1..]) } else { (&photons[mid + 1..], &photons[..mid]) };
+
+    gather(near, p, radius_squared, f);
+
+    // the other side can only hold photons in range if the splitting plane is
+    if d * d <= radius_squared {
+        gather(far, p, radius_squared, f);
+    }
+}
@@ -0,0 +1,236 @@
+/// Triangle meshes read from PLY files, as used by pbrt scenes
+use std::{
+    fs,
+    io::{self, BufRead, Read},
+    path::Path
+};
+use crate::point3::Point3;
+
+/// Vertex positions of a mesh and the corners of its triangles, polygons being split into fans
+pub struct PlyMesh {
+    pub positions: Vec<Point3>,
+    pub triangles: Vec<[usize; 3]>
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8, U8, I16, U16, I32, U32, F32, F64
+}
+
+struct Property {
+    name: String,
+    scalar: Scalar,
+    count: Option<Scalar>       // type of the length of list properties
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+/// Reads the mesh at path, taking its vertex x, y, z and face vertex_indices
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PlyMesh> {
+    parse(&fs::read(path)?)
+}
+
+/// Parses the bytes of a PLY file
+pub fn parse(bytes: &[u8]) -> io::Result<PlyMesh> {
+    let mut reader = bytes;
+    let (encoding, elements) = header(&mut reader)?;
+
+    let mut mesh = PlyMesh { positions: vec![], triangles: vec![] };
+    let mut values = Values { encoding, reader, words: vec![].into_iter() };
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+
+            for property in &element.properties {
+                match property.count {
+                    Some(count) => {
+                        // lengths are not trusted to reserve space, a corrupt one runs out of data instead
+                        let len = index(values.next(count)?)?;
+                        let mut list = vec![];
+                        for _ in 0..len {
+                            list.push(values.next(property.scalar)?);
+                        }
+
+                        if element.name == "face" && matches!(property.name.as_str(), "vertex_indices" | "vertex_index") {
+                            let list = list.into_iter().map(index).collect::<io::Result<Vec<usize>>>()?;
+                            for k in 2..list.len() {
+                                mesh.triangles.push([list[0], list[k - 1], list[k]]);
+                            }
+                        }
+                    },
+                    None => {
+                        let value = values.next(property.scalar)?;
+
+                        if element.name == "vertex" {
+                            match property.name.as_str() {
+                                "x" => position[0] = value,
+                                "y" => position[1] = value,
+                                "z" => position[2] = value,
+                                _ => ()
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                mesh.positions.push(Point3::new(position[0], position[1], position[2]));
+            }
+        }
+    }
+
+    if let Some(corner) = mesh.triangles.iter().flatten().find(|&&i| i >= mesh.positions.len()) {
+        return Err(invalid(&format!("face refers to vertex {} of {}", corner, mesh.positions.len())));
+    }
+
+    Ok(mesh)
+}
+
+/// Checks that value, read as a list length or vertex index, is a whole number that is not negative
+fn index(value: f64) -> io::Result<usize> {
+    match value >= 0.0 && value.fract() == 0.0 {
+        true => Ok(value as usize),
+        false => Err(invalid(&format!("{} is not a valid length or index", value)))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("PLY: {}", message))
+}
+
+/// Reads the header, leaving reader at the start of the data
+fn header(reader: &mut &[u8]) -> io::Result<(Encoding, Vec<Element>)> {
+    let mut encoding = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut first = true;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("header has no end_header"));
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first {
+            if words != ["ply"] {
+                return Err(invalid("not a PLY file"));
+            }
+            first = false;
+            continue;
+        }
+
+        match words[..] {
+            ["format", format, _] => encoding = Some(match format {
+                "ascii" => Encoding::Ascii,
+                "binary_little_endian" => Encoding::LittleEndian,
+                "binary_big_endian" => Encoding::BigEndian,
+                other => return Err(invalid(&format!("unknown format {}", other)))
+            }),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid(&format!("bad element count {}", count)))?,
+                properties: vec![]
+            }),
+            ["property", "list", count, scalar, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before any element"))?;
+                element.properties.push(Property { name: name.to_string(), scalar: scalar_type(scalar)?, count: Some(scalar_type(count)?) });
+            },
+            ["property", scalar, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before any element"))?;
+                element.properties.push(Property { name: name.to_string(), scalar: scalar_type(scalar)?, count: None });
+            },
+            ["end_header"] => break,
+            _ => ()     // comments and obj_info
+        }
+    }
+
+    // every property read takes up some data, so the counts of elements that have them are bounded by
+    // the file's length. Elements without any would be read from nothing, however many there are said to be
+    if let Some(element) = elements.iter().find(|element| element.properties.is_empty() && element.count > 0) {
+        return Err(invalid(&format!("element {} has no properties", element.name)));
+    }
+
+    Ok((encoding.ok_or_else(|| invalid("header has no format"))?, elements))
+}
+
+fn scalar_type(name: &str) -> io::Result<Scalar> {
+    Ok(match name {
+        "char" | "int8" => Scalar::I8,
+        "uchar" | "uint8" => Scalar::U8,
+        "short" | "int16" => Scalar::I16,
+        "ushort" | "uint16" => Scalar::U16,
+        "int" | "int32" => Scalar::I32,
+        "uint" | "uint32" => Scalar::U32,
+        "float" | "float32" => Scalar::F32,
+        "double" | "float64" => Scalar::F64,
+        other => return Err(invalid(&format!("unknown property type {}", other)))
+    })
+}
+
+/// Values of the data section, read one at a time
+struct Values<'a> {
+    encoding: Encoding,
+    reader: &'a [u8],
+    words: std::vec::IntoIter<String>      // rest of the current line of an ascii file
+}
+
+impl Values<'_> {
+    fn next(&mut self, scalar: Scalar) -> io::Result<f64> {
+        if self.encoding == Encoding::Ascii {
+            return self.next_word()?.parse().map_err(|_| invalid("expected a number"));
+        }
+
+        let mut bytes = [0; 8];
+        let size = match scalar {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8
+        };
+        let bytes = &mut bytes[..size];
+        self.reader.read_exact(bytes).map_err(|_| invalid("data ends early"))?;
+
+        if self.encoding == Encoding::BigEndian {
+            bytes.reverse();
+        }
+
+        let value = match scalar {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes.try_into().unwrap())
+        };
+
+        Ok(value)
+    }
+
+    fn next_word(&mut self) -> io::Result<String> {
+        loop {
+            if let Some(word) = self.words.next() {
+                return Ok(word);
+            }
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(invalid("data ends early"));
+            }
+            self.words = line.split_whitespace().map(str::to_string).collect::<Vec<_>>().into_iter();
+        }
+    }
+}
@@ -4,9 +4,8 @@
  during the actual rendering. Those are utility random functions to be used for
  actions that are usually done once, like creating objects / materials / textures.
 */ 
-use std::ops::Range;
-use rand::{rngs::SmallRng, Rng, SeedableRng, thread_rng};
-use crate::vec3::Vec3;
+use rand::{rngs::SmallRng, Rng};
+use crate::{sampler::Sampler, utils::fmax, vec3::Vec3};
 
 // Returns random Vec3 where all dimensions are random from [0,1)
 pub fn random(rng: &mut SmallRng) -> Vec3 {
@@ -19,22 +18,23 @@ pub fn random_in_range(rng: &mut SmallRng, min: f64, max: f64) -> Vec3 {
 }
 
 /// Returns random vector in the unit sphere
-pub fn random_in_unit_sphere(rng: &mut SmallRng) -> Vec3 {
-    loop {
-        let p = random_in_range(rng, -1.0, 1.0);
-        if p.length_squared() >= 1.0 { continue; }
-
-        return p;
-    }
+pub fn random_in_unit_sphere(rng: &mut dyn Sampler) -> Vec3 {
+    // volume within radius r grows with r^3
+    random_unit_vector(rng) * rng.get_1d().cbrt()
 }
 
 /// Returns random unit vector
-pub fn random_unit_vector(rng: &mut SmallRng) -> Vec3 {
-    random_in_unit_sphere(rng).normalized()
+pub fn random_unit_vector(rng: &mut dyn Sampler) -> Vec3 {
+    let (u, v) = rng.get_2d();
+    let z = 1.0 - 2.0 * u;
+    let r = fmax(0.0, 1.0 - z * z).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * v;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 /// Returns random vector in hemisphere aligned with normal
-pub fn random_in_hemisphere(rng: &mut SmallRng, normal: &Vec3) -> Vec3 {
+pub fn random_in_hemisphere(rng: &mut dyn Sampler, normal: &Vec3) -> Vec3 {
     let in_unit_sphere = random_in_unit_sphere(rng);
     if in_unit_sphere.dot(normal) > 0.0 {
         in_unit_sphere
@@ -43,19 +43,48 @@ pub fn random_in_hemisphere(rng: &mut SmallRng, normal: &Vec3) -> Vec3 {
     }
 }
 
-/// Returns random vector in unit disk on x-y plane
-pub fn random_in_unit_disk(rng: &mut SmallRng) -> Vec3 {
-    loop {
-        let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-        if p.length_squared() >= 1.0 {
-            continue;
-        }
+/// Returns random vector in unit disk on x-y plane, using Shirley's concentric mapping
+/// so that evenly spread samples stay evenly spread over the disk
+pub fn random_in_unit_disk(rng: &mut dyn Sampler) -> Vec3 {
+    let (u, v) = rng.get_2d();
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
 
-        return p;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::zero();
     }
+
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, quarter_pi * (b / a))
+    } else {
+        (b, 2.0 * quarter_pi - quarter_pi * (a / b))
+    };
+
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
-pub fn random_i32(range: Range<i32>) -> i32 {
-    let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
-    rng.gen_range(range)
+/// Derives a seed for an independent random stream from seed and values, e.g. a pixel and sample index
+pub fn stream_seed(seed: u64, values: &[u64]) -> u64 {
+    values.iter().fold(splitmix64(seed), |h, &v| splitmix64(h ^ v))
+}
+
+/// splitmix64 finalizer, scrambling x so that nearby inputs give unrelated outputs
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Returns random direction towards a sphere of given radius at given squared distance,
+/// uniformly distributed over the cone the sphere subtends (z axis points at the sphere center)
+pub fn random_to_sphere(rng: &mut dyn Sampler, radius: f64, distance_squared: f64) -> Vec3 {
+    let (r1, r2) = rng.get_2d();
+    let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let x = phi.cos() * (1.0 - z * z).sqrt();
+    let y = phi.sin() * (1.0 - z * z).sqrt();
+
+    Vec3::new(x, y, z)
 }
@@ -3,6 +3,7 @@ use crate::{
     vec3::Vec3
 };
 
+#[derive(Clone)]
 pub struct Ray {
     pub origin: Point3,
     pub dir: Vec3,
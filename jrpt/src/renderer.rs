@@ -1,24 +1,376 @@
-use image::{ImageBuffer, RgbImage, Rgb, buffer::EnumeratePixelsMut};
-use rand::{SeedableRng, Rng, thread_rng, rngs::SmallRng};
-use rayon::prelude::*;
+use std::{
+    cell::Cell,
+    cmp::min,
+    mem::swap,
+    ops::Range,
+    sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}},
+    io,
+    time::{Duration, Instant}
+};
+use image::Rgb32FImage;
 use crate::{
     scene::Scene,
+    film::{Film, PixelSamples, Splats},
+    filter::Filter,
     ray::Ray,
     constants::{INFINITY, EPSILON},
-    utils::max,
+    objects::{Intersection, object_list},
+    utils::{max, fmin, fmax},
+    random::stream_seed,
+    sampler::{Sampler, SamplerKind},
+    checkpoint::Checkpoint,
+    stats::{self, Counter, RenderStats},
+    bidirectional,
+    photon_map::{self, PhotonMap},
+    aov::Aov,
     colour::Colour
 };
 
+// random streams of photon maps are kept apart from those of pixels
+const PHOTON_STREAM: u64 = u64::MAX;
+
+thread_local! {
+    // vertices of the path being traced, kept between paths so each does not allocate its own
+    static PATH_VERTICES: Cell<Vec<(Colour, Colour)>> = const { Cell::new(Vec::new()) };
+}
+
+/// Heuristic used to weight light samples and scattered rays against each other
+#[derive(Clone, Copy)]
+pub enum MisHeuristic {
+    Balance,
+    Power
+}
+
+impl MisHeuristic {
+    /// Weight of a sample drawn with pdf_a, which could also have been drawn with pdf_b
+    pub fn weight(&self, pdf_a: f64, pdf_b: f64) -> f64 {
+        if pdf_a <= 0.0 {
+            return 0.0;
+        }
+
+        match self {
+            Self::Balance => pdf_a / (pdf_a + pdf_b),
+            Self::Power => pdf_a * pdf_a / (pdf_a * pdf_a + pdf_b * pdf_b)
+        }
+    }
+}
+
+/// Randomly terminates a path with given throughput, so that low contribution paths stop early.
+/// Returns None if the path is terminated, otherwise the factor to scale the surviving path by
+/// so that its expected value is unchanged
+pub(crate) fn russian_roulette(rng: &mut dyn Sampler, throughput: &Colour) -> Option<f64> {
+    let p = fmin(fmax(fmax(throughput.x, throughput.y), throughput.z), 1.0);
+
+    if p <= 0.0 || rng.get_1d() >= p {
+        return None;
+    }
+
+    Some(1.0 / p)
+}
+
+/// Rectangle of pixels [x0,x1) x [y0,y1) rendered as one unit of work, rows counting from the top
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32
+}
+
+/// Share of a render taken by one of several workers, such as separate processes, each saving
+/// a checkpoint of its film for checkpoint::merge to combine. Workers count from 0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Partition {
+    Samples { worker: u32, workers: u32 },  // a contiguous share of every pixel's sample indices
+    Tiles { worker: u32, workers: u32 }     // every workers'th tile starting from tile number worker, with all their samples
+}
+
+impl Partition {
+    /// Returns the worker number and the number of workers
+    pub fn worker(&self) -> (u32, u32) {
+        match *self {
+            Partition::Samples { worker, workers } | Partition::Tiles { worker, workers } => (worker, workers)
+        }
+    }
+}
+
+/// Order that tiles are handed out to render threads
+#[derive(Clone, Copy)]
+pub enum TileOrder {
+    Scanline,   // row by row from the top left
+    Spiral,     // outwards from the center of the image
+    Hilbert     // along a hilbert curve, keeping consecutive tiles close together
+}
+
+/// Splits region into tiles of at most tile_size x tile_size pixels, in the given order
+pub fn generate_tiles(region: Tile, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = max(1, tile_size);
+    let nx = (region.x1 - region.x0).div_ceil(tile_size);
+    let ny = (region.y1 - region.y0).div_ceil(tile_size);
+
+    // tiles given by their position in the grid of tiles
+    let grid: Vec<(u32, u32)> = match order {
+        TileOrder::Scanline => (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect(),
+        TileOrder::Spiral => spiral_order(nx, ny),
+        TileOrder::Hilbert => {
+            let n = max(nx, ny).next_power_of_two();
+            let mut grid: Vec<(u32, u32)> = (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+            grid
+        }
+    };
+
+    grid.into_iter().map(|(tx, ty)| {
+        let x0 = region.x0 + tx * tile_size;
+        let y0 = region.y0 + ty * tile_size;
+
+        Tile {
+            x0, y0,
+            x1: min(x0 + tile_size, region.x1),
+            y1: min(y0 + tile_size, region.y1)
+        }
+    }).collect()
+}
+
+/// Walks a square spiral out from the center of an nx by ny grid, keeping cells inside the grid
+fn spiral_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let total = (nx * ny) as usize;
+    let mut cells = Vec::with_capacity(total);
+
+    let (mut x, mut y) = ((nx / 2) as i64, (ny / 2) as i64);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut step_length = 1;
+    let mut direction = 0;
+
+    while cells.len() < total {
+        // each step length is walked twice before growing
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction];
+
+            for _ in 0..step_length {
+                if x >= 0 && y >= 0 && x < nx as i64 && y < ny as i64 {
+                    cells.push((x as u32, y as u32));
+                }
+
+                x += dx;
+                y += dy;
+            }
+
+            direction = (direction + 1) % 4;
+        }
+
+        step_length += 1;
+    }
+
+    cells
+}
+
+/// Distance along the hilbert curve filling an n x n grid (n a power of two) of cell (x,y)
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = ((x & s) > 0) as u32;
+        let ry = ((y & s) > 0) as u32;
+        d += (s as u64) * (s as u64) * ((3 * rx) ^ ry) as u64;
+
+        // rotate quadrant so the curve inside it has the standard orientation
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+
+            swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
+
+/// Token that can stop a render from another thread. Tiles already being rendered are finished,
+/// the remaining tiles and passes are skipped
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop renders using this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// State carried along a path as it bounces through the scene
+struct PathState {
+    ray: Ray,               // ray leaving the last vertex of the path
+    throughput: Colour,     // product of attenuations along the path so far
+    vertices: Vec<(Colour, Colour)>,    // light found at each vertex, and the attenuation of the light found after it
+    emitted_weight: f64,    // MIS weight of light hit by ray, which may also have been reached by sampling lights
+    after_diffuse: bool,    // the last vertex that was not specular was on a diffuse surface
+    caustic: bool,          // ray follows specular bounces after a diffuse surface, so lights it finds are in the photon map
+    bounces: u32
+}
+
+impl PathState {
+    /// Starts a path along r, keeping its vertices in the emptied buffer vertices
+    fn new(r: Ray, mut vertices: Vec<(Colour, Colour)>) -> Self {
+        vertices.clear();
+
+        Self {
+            ray: r,
+            throughput: Colour::from_value(1.0),
+            vertices,
+            emitted_weight: 1.0,
+            after_diffuse: false,
+            caustic: false,
+            bounces: 0
+        }
+    }
+
+    /// Returns the light carried back along the path, summed from its last vertex back to the camera
+    /// as the recursive path tracer did, so that a fixed seed gives exactly the same result
+    fn radiance(&self) -> Colour {
+        self.vertices.iter().rev().fold(Colour::zero(), |rest, (light, attenuation)| *light + *attenuation * rest)
+    }
+}
+
+/// Algorithm used to find the light arriving along camera rays
+#[derive(Clone, Copy)]
+pub enum Integrator {
+    PathTracer,                     // paths from the camera, sampling lights at each bounce
+    Bidirectional,                  // paths from both the camera and the lights, joined at every pair of vertices
+    PhotonMapping(PhotonMapping)    // path tracing, with caustics gathered from a photon map
+}
+
+/// Settings for rendering caustics from photon maps. Every pass of a render traces its own photons,
+/// so caustics get less noisy as passes are added
+#[derive(Clone, Copy)]
+pub struct PhotonMapping {
+    pub photons: u32,       // photons emitted from the lights each pass
+    pub radius: f64,        // radius photons are gathered from on the first pass
+    pub alpha: Option<f64>  // progressive photon mapping: shrink the radius each pass, keeping this fraction (0,1) of the photons
+}
+
+impl PhotonMapping {
+    /// Returns the gather radius of the given pass, counting from 0.
+    /// Without progressive photon mapping the radius stays fixed, which blurs caustics however many passes are taken,
+    /// shrinking it lets the bias vanish as the number of passes grows
+    pub fn radius(&self, pass: u32) -> f64 {
+        let alpha = match self.alpha {
+            Some(alpha) => alpha,
+            None => return self.radius
+        };
+
+        // r_(i+1)^2 = r_i^2 (i + alpha) / (i + 1), from Knaus and Zwicker's probabilistic progressive photon mapping
+        let radius_squared = (1..=pass).fold(self.radius * self.radius, |r2, i| r2 * (i as f64 - 1.0 + alpha) / i as f64);
+
+        radius_squared.sqrt()
+    }
+}
+
+/// Settings for adaptive sampling, where pixels stop being sampled once their estimate has converged
+/// and the samples they leave unused are spent on the pixels that have not
+#[derive(Clone, Copy)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,   // samples every pixel takes before it can be considered converged
+    pub max_samples: u32,   // most samples any pixel takes
+    pub threshold: f64      // relative error below which a pixel has converged
+}
+
+impl AdaptiveSampling {
+    /// Returns whether pixel (i,j) of film still needs samples
+    fn is_active(&self, film: &Film, i: u32, j: u32) -> bool {
+        let n = film.pixel_samples(i, j);
+
+        n < self.min_samples || (n < self.max_samples && film.relative_error(i, j) > self.threshold)
+    }
+
+    /// Leaves at most n of the pixels marked in active, keeping those below min_samples and then
+    /// those with the largest relative error
+    fn keep_noisiest(&self, film: &Film, active: &mut [bool], n: usize) {
+        let width = film.width();
+        let mut pixels: Vec<usize> = (0..active.len()).filter(|&idx| active[idx]).collect();
+
+        if pixels.len() <= n {
+            return;
+        }
+
+        let key = |idx: usize| {
+            let (i, j) = (idx as u32 % width, idx as u32 / width);
+            (film.pixel_samples(i, j) >= self.min_samples, film.relative_error(i, j))
+        };
+
+        // ties keep pixel order, so the same pixels are chosen however the render is scheduled
+        pixels.sort_by(|&a, &b| {
+            let ((a_floor, a_error), (b_floor, b_error)) = (key(a), key(b));
+            a_floor.cmp(&b_floor).then(b_error.total_cmp(&a_error))
+        });
+
+        for &idx in &pixels[n..] {
+            active[idx] = false;
+        }
+    }
+}
+
+/// Settings for rendering for a length of time rather than a number of samples
+#[derive(Clone, Copy)]
+pub struct TimeBudget {
+    pub duration: Duration,     // wall clock time from the start of the render to stop taking samples by
+    pub min_samples: u32        // samples every render takes, however long they take
+}
+
 pub struct Renderer {
     num_samples: u32,
-    depth: u32,
-    multithreading: bool
+    depth: u32,             // hard limit on path length
+    roulette_depth: u32,    // bounces before paths may be terminated by russian roulette
+    multithreading: bool,
+    mis_heuristic: MisHeuristic,
+    integrator: Integrator,
+    tile_size: u32,
+    tile_order: TileOrder,
+    cancellation_token: CancellationToken,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    time_budget: Option<TimeBudget>,
+    aovs: Vec<Aov>,         // extra buffers kept in the film besides the image
+    filter: Filter,         // shares each sample between the pixels around it
+    sampler: SamplerKind,
+    partition: Option<Partition>,   // the share of the render to take, or all of it if None
+    crop_window: Option<Tile>,      // the pixels to render, or all of them if None
+    seed: u64               // every sample's random numbers are derived from this
 }
 
 impl Renderer {
     pub fn new(num_samples: u32, depth: u32, multithreading: bool) -> Self {
         Self {
-            num_samples, depth, multithreading
+            num_samples, depth, multithreading,
+            roulette_depth: 3,
+            mis_heuristic: MisHeuristic::Power,
+            integrator: Integrator::PathTracer,
+            tile_size: 16,
+            tile_order: TileOrder::Spiral,
+            cancellation_token: CancellationToken::new(),
+            adaptive_sampling: None,
+            time_budget: None,
+            aovs: vec![],
+            filter: Filter::default(),
+            sampler: SamplerKind::Independent,
+            partition: None,
+            crop_window: None,
+            seed: 0
         }
     }
 
@@ -28,77 +380,585 @@ impl Renderer {
         self.num_samples = max(1, num_samples);
     }
 
-    /// Set the recursion depth
+    /// Set the recursion depth, paths are always cut off here
     pub fn set_depth(&mut self, depth: u32) {
         self.depth = max(1, depth);
     }
 
+    /// Set the number of bounces a path takes before russian roulette may terminate it
+    pub fn set_roulette_depth(&mut self, roulette_depth: u32) {
+        self.roulette_depth = roulette_depth;
+    }
+
     /// Allow this render to be multithreaded
     pub fn set_multithreading(&mut self, multithreading: bool) {
         self.multithreading = multithreading;
     }
 
-    pub fn render(&self, scene: &Scene, image_height: u32, image_width: u32) -> RgbImage {
-        let mut img = ImageBuffer::new(image_width, image_height);
+    /// Set the heuristic combining light sampling and material sampling
+    pub fn set_mis_heuristic(&mut self, mis_heuristic: MisHeuristic) {
+        self.mis_heuristic = mis_heuristic;
+    }
+
+    /// Set the algorithm used to render
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Set the width and height of the tiles the image is split into
+    pub fn set_tile_size(&mut self, tile_size: u32) {
+        self.tile_size = max(1, tile_size);
+    }
+
+    /// Set the order tiles are rendered in
+    pub fn set_tile_order(&mut self, tile_order: TileOrder) {
+        self.tile_order = tile_order;
+    }
+
+    /// Set adaptive sampling, or None to take num_samples samples for every pixel.
+    /// When set, num_samples is the average taken per pixel: pixels that converge early leave
+    /// their samples to those that have not, which may take up to max_samples
+    pub fn set_adaptive_sampling(&mut self, adaptive_sampling: Option<AdaptiveSampling>) {
+        self.adaptive_sampling = adaptive_sampling;
+    }
+
+    /// Set a time budget, or None to take every sample. Passes are taken until the next one would
+    /// not finish in time, with the last pass cut short to fit, and num_samples, or max_samples with
+    /// adaptive sampling, still caps the samples taken. Film::samples gives the samples reached
+    pub fn set_time_budget(&mut self, time_budget: Option<TimeBudget>) {
+        self.time_budget = time_budget;
+    }
+
+    /// Set the seed random numbers are derived from. Renders with the same seed and settings
+    /// are identical, whatever the thread count or tile order. With a filter reaching past the
+    /// pixel a sample is taken in, the tile size and order must match as well
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Set the AOVs kept in the film returned by render_progressive, alongside the rendered image
+    pub fn set_aovs(&mut self, aovs: &[Aov]) {
+        self.aovs = aovs.to_vec();
+    }
+
+    /// Set the reconstruction filter samples are splatted onto nearby pixels with
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    /// Set the kind of sampler taking each sample's random numbers
+    pub fn set_sampler(&mut self, sampler: SamplerKind) {
+        self.sampler = sampler;
+    }
+
+    /// Set the share of the render this renderer takes, or None to take all of it.
+    /// With adaptive sampling, each worker decides which of its pixels have converged on its own
+    pub fn set_partition(&mut self, partition: Option<Partition>) {
+        self.partition = partition;
+    }
+
+    /// Set the rectangle of pixels to render, or None to render the whole image. Pixels outside it
+    /// are left black, apart from those within the filter's reach of its edge.
+    /// output::crop cuts the window out of the finished image
+    pub fn set_crop_window(&mut self, crop_window: Option<Tile>) {
+        self.crop_window = crop_window;
+    }
+
+    /// Set the token used to cancel renders
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = cancellation_token;
+    }
+
+    /// Returns a token that cancels this renderer's renders, which can be sent to another thread.
+    /// Once cancelled, renders stop immediately until a new token is set
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    /// Renders the scene into a linear floating point image, along with the work done rendering it
+    pub fn render(&self, scene: &Scene, image_height: u32, image_width: u32) -> (Rgb32FImage, RenderStats) {
+        // adaptive sampling needs several passes to find which pixels have converged, and a time budget
+        // needs passes short enough to stop close to the deadline
+        let samples_per_pass = match (&self.adaptive_sampling, &self.time_budget) {
+            (Some(adaptive), _) => adaptive.min_samples,
+            (None, Some(budget)) => budget.min_samples,
+            (None, None) => self.num_samples
+        };
+
+        let film = self.render_progressive(scene, image_height, image_width, samples_per_pass, |_| true);
+        (film.to_hdr_image(), *film.stats())
+    }
+
+    /// Renders in passes of samples_per_pass samples per pixel, accumulating into a film until
+    /// num_samples samples have been taken. After each pass, callback is given the film so far
+    /// and may return false to stop the render early.
+    /// With adaptive sampling, passes only sample pixels that have not converged, and the render
+    /// ends once every pixel has converged or taken max_samples samples, or num_samples samples
+    /// per pixel have been taken in all
+    pub fn render_progressive<F>(&self, scene: &Scene, image_height: u32, image_width: u32, samples_per_pass: u32, callback: F) -> Film
+        where F: FnMut(&Film) -> bool
+    {
+        let film = Film::with_aovs(image_width, image_height, &self.aovs);
+        self.render_passes(scene, film, max(1, samples_per_pass), callback)
+    }
+
+    /// Continues the render saved in checkpoint as render_progressive would have, until num_samples
+    /// samples have been taken. Raising num_samples above the checkpoint's samples adds more samples
+    /// to a finished render.
+    /// The renderer should be set up as it was for the checkpointed render, and the seed, AOVs and partition must match
+    pub fn resume<F>(&self, scene: &Scene, checkpoint: Checkpoint, callback: F) -> io::Result<Film>
+        where F: FnMut(&Film) -> bool
+    {
+        if checkpoint.seed != self.seed {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("checkpoint was rendered with seed {}, not {}", checkpoint.seed, self.seed)));
+        }
+
+        if checkpoint.film.aovs() != self.aovs {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "checkpoint keeps different AOVs"));
+        }
+
+        if checkpoint.partition != self.partition {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "checkpoint took a different share of the render"));
+        }
+
+        Ok(self.render_passes(scene, checkpoint.film, max(1, checkpoint.samples_per_pass), callback))
+    }
+
+    /// Takes passes of samples_per_pass samples per pixel into film until the render is done
+    fn render_passes<F>(&self, scene: &Scene, mut film: Film, samples_per_pass: u32, mut callback: F) -> Film
+        where F: FnMut(&Film) -> bool
+    {
+        let (image_width, image_height) = (film.width(), film.height());
+
+        let region = match self.crop_window {
+            Some(window) => {
+                let (x0, y0) = (min(window.x0, image_width), min(window.y0, image_height));
+                Tile { x0, y0, x1: window.x1.clamp(x0, image_width), y1: window.y1.clamp(y0, image_height) }
+            },
+            None => Tile { x0: 0, y0: 0, x1: image_width, y1: image_height }
+        };
+        let mut tiles = generate_tiles(region, self.tile_size, self.tile_order);
+
+        // sample indices taken by this renderer, the film counts from the first
+        let mut samples = 0..self.total_samples();
+
+        match self.partition {
+            Some(Partition::Samples { worker, workers }) => {
+                let share = |worker: u32| (samples.end as u64 * worker as u64 / workers.max(1) as u64) as u32;
+                samples = share(worker)..share(worker + 1);
+            },
+            Some(Partition::Tiles { worker, workers }) => {
+                tiles = tiles.into_iter().skip(worker as usize).step_by(workers.max(1) as usize).collect();
+            },
+            None => ()
+        }
+
+        // samples adaptive sampling may take over all of this renderer's pixels, its share of num_samples per pixel
+        let budget = self.adaptive_sampling.map(|adaptive| {
+            let pixels: u64 = tiles.iter().map(|tile| ((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as u64).sum();
+            let per_pixel = max(self.num_samples, adaptive.min_samples) as u64;
+
+            pixels * per_pixel * samples.len() as u64 / self.total_samples() as u64
+        });
 
-        let f = |(_, cols): (u32, EnumeratePixelsMut<Rgb<u8>>)| {
-            let mut rng = SmallRng::from_rng(thread_rng()).unwrap();    
-            
-            cols.for_each(|(i, j, pixel): (u32, u32, &mut Rgb<u8>)| {
-                let j = image_height - j;
-                        // because from top down
-                
-                let col = self.sample_pixel(&mut rng, scene, i, j, image_height, image_width);
+        let start = Instant::now();
+        let mut sample_time = None;     // seconds per sample per pixel taken by the last pass
 
-                *pixel = col.to_rgb();
+        while film.samples() < samples.len() as u32 && !self.cancellation_token.is_cancelled() {
+            // passes line up with those of the whole render, so a worker sharing out samples gathers
+            // each sample's photons with the radius of the pass the sample falls in
+            let first_sample = samples.start + film.samples();
+            let pass = first_sample / samples_per_pass;
+            let mut num_samples = min((pass + 1) * samples_per_pass - first_sample, samples.end - first_sample);
+
+            // the first pass always goes ahead, as it is needed to tell how long samples take
+            if let (Some(budget), Some(sample_time)) = (&self.time_budget, sample_time) {
+                let remaining = budget.duration.saturating_sub(start.elapsed()).as_secs_f64();
+                let fit = (remaining / sample_time) as u32;
+
+                num_samples = min(num_samples, max(fit, budget.min_samples.saturating_sub(film.samples())));
+
+                if num_samples == 0 {
+                    break;
+                }
+            }
+
+            // pixels outside this renderer's tiles are never active
+            let active = self.adaptive_sampling.map(|adaptive| {
+                let mut active = vec![false; (image_width * image_height) as usize];
+                let mut used = 0;
+
+                for tile in &tiles {
+                    for y in tile.y0..tile.y1 {
+                        for x in tile.x0..tile.x1 {
+                            active[(y * image_width + x) as usize] = adaptive.is_active(&film, x, y);
+                            used += film.pixel_samples(x, y) as u64;
+                        }
+                    }
+                }
+
+                // once the budget runs short, what is left goes to the pixels furthest from converging
+                let affordable = budget.unwrap_or(0).saturating_sub(used) / num_samples as u64;
+                adaptive.keep_noisiest(&film, &mut active, affordable as usize);
+
+                active
             });
+
+            if active.as_ref().is_some_and(|active| !active.contains(&true)) {
+                break;
+            }
+
+            let pass_start = Instant::now();
+            let photons = self.build_photon_map(scene, pass, pass * samples_per_pass);
+
+            if let Some(photons) = &photons {
+                film.stats += &photons.stats;
+                film.stats.photon_tracing += pass_start.elapsed();
+            }
+
+            let completed = self.render_pass(scene, &mut film, &tiles, active.as_deref(), first_sample..first_sample + num_samples, photons.as_ref());
+            film.stats.render += pass_start.elapsed();
+            sample_time = Some(pass_start.elapsed().as_secs_f64() / num_samples as f64);
+
+            if !completed {
+                break;
+            }
+
+            if !callback(&film) {
+                break;
+            }
+        }
+
+        film
+    }
+
+    /// Returns the most samples any pixel takes
+    fn total_samples(&self) -> u32 {
+        match &self.adaptive_sampling {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.num_samples
+        }
+    }
+
+    /// Traces the photons for pass number pass of the whole render, whose first sample is first_sample, if the integrator uses them
+    fn build_photon_map(&self, scene: &Scene, pass: u32, first_sample: u32) -> Option<PhotonMap> {
+        let settings = match self.integrator {
+            Integrator::PhotonMapping(settings) => settings,
+            _ => return None
         };
 
+        let seed = stream_seed(self.seed, &[PHOTON_STREAM, first_sample as u64]);
+
+        Some(PhotonMap::build(scene, settings.photons, (self.depth, self.roulette_depth), settings.radius(pass), (seed, self.multithreading)))
+    }
+
+    /// Takes the given sample indices for every pixel in tiles, or only for the pixels marked in active if given.
+    /// Returns false if the pass was cancelled
+    fn render_pass(&self, scene: &Scene, film: &mut Film, tiles: &[Tile], active: Option<&[bool]>, samples: Range<u32>, photons: Option<&PhotonMap>) -> bool {
+        let size = (film.width(), film.height());
+
+        // splats landing outside their own tile are added once the pass is over, in tile order,
+        // so pixels shared between tiles sum them in the same order however the tiles were scheduled
+        let mut borders = vec![vec![]; tiles.len()];
+
         if self.multithreading {
-            img.enumerate_rows_mut().par_bridge().for_each(f);
+            // threads take the next tile in order as they finish, so the tile order is kept
+            let next_tile = AtomicUsize::new(0);
+            let shared = Mutex::new((&mut *film, &mut borders));
+
+            rayon::scope(|s| {
+                for _ in 0..rayon::current_num_threads() {
+                    s.spawn(|_| loop {
+                        let idx = next_tile.fetch_add(1, Ordering::Relaxed);
+
+                        if idx >= tiles.len() || self.cancellation_token.is_cancelled() {
+                            break;
+                        }
+
+                        let ((tile_samples, splats), tile_stats) = stats::measure(|| self.render_tile(scene, &tiles[idx], size, active, samples.clone(), photons));
+
+                        let (film, borders) = &mut *shared.lock().unwrap();
+                        borders[idx] = add_tile(film, &tiles[idx], &tile_samples, &splats);
+                        film.stats += &tile_stats;
+                    });
+                }
+            });
         } else {
-            img.enumerate_rows_mut().for_each(f);
+            for (tile, border) in tiles.iter().zip(borders.iter_mut()) {
+                if self.cancellation_token.is_cancelled() {
+                    break;
+                }
+
+                let ((tile_samples, splats), tile_stats) = stats::measure(|| self.render_tile(scene, tile, size, active, samples.clone(), photons));
+                *border = add_tile(film, tile, &tile_samples, &splats);
+                film.stats += &tile_stats;
+            }
+        }
+
+        for (i, j, weighted_sum, weight) in borders.into_iter().flatten() {
+            film.add_splat(i, j, weighted_sum, weight);
+        }
+
+        if self.cancellation_token.is_cancelled() {
+            return false;
+        }
+
+        film.samples += samples.len() as u32;
+        true
+    }
+
+    /// Takes the given samples for every pixel in tile that is marked in active, or every pixel if
+    /// active is None, returning the samples of each pixel row by row and their splats onto the
+    /// pixels within the filter's reach
+    fn render_tile(&self, scene: &Scene, tile: &Tile, (width, height): (u32, u32), active: Option<&[bool]>, sample_range: Range<u32>, photons: Option<&PhotonMap>) -> (Vec<PixelSamples>, Splats) {
+        let mut samples = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+
+        let margin = self.filter.margin();
+        let mut splats = Splats::new(
+            tile.x0.saturating_sub(margin), tile.y0.saturating_sub(margin),
+            min(tile.x1.saturating_add(margin), width), min(tile.y1.saturating_add(margin), height)
+        );
+
+        for y in tile.y0..tile.y1 {
+            let j = height - y;
+                    // because from top down
+
+            for i in tile.x0..tile.x1 {
+                let pixel = y * width + i;
+
+                if active.is_none_or(|active| active[pixel as usize]) {
+                    samples.push(self.sample_pixel(scene, (i, j), (width, height), sample_range.clone(), photons, &mut splats));
+                } else {
+                    samples.push(PixelSamples::default());
+                }
+            }
         }
 
-        img
+        (samples, splats)
     }
 
-    /// Antialias num_samples times on pixel (i,j)
-    fn sample_pixel(&self, rng: &mut SmallRng, scene: &Scene, i: u32, j: u32, height: u32, width: u32) -> Colour {
-        let mut col = Colour::zero();
+    /// Antialias on pixel (i,j), taking the samples with the given indices and splatting them into splats.
+    /// Each sample has its own sampler derived from the seed, pixel and sample index so results do not
+    /// depend on scheduling. photons holds the caustics of this pass when rendering with photon mapping
+    fn sample_pixel(&self, scene: &Scene, (i, j): (u32, u32), (width, height): (u32, u32), sample_range: Range<u32>, photons: Option<&PhotonMap>, splats: &mut Splats) -> PixelSamples {
+        let mut samples = PixelSamples::default();
+        let y = height - j;
+        let pixel = y * width + i;
+
+        // a time budget stops at a sample that is not known in advance, so its passes are spread out on their own
+        let planned = if self.time_budget.is_some() { sample_range.len() as u32 } else { self.total_samples() };
 
-        (0..self.num_samples).for_each(|_| {
-            let u_ = ((i as f64) + rng.gen::<f64>()) / (width - 1) as f64;
-            let v_ = ((j as f64) + rng.gen::<f64>()) / (height - 1) as f64;
+        sample_range.for_each(|sample| {
+            let rng = &mut *self.sampler.create(self.seed, pixel as u64, sample, planned);
+
+            let (dx, dy) = rng.get_2d();
+            let u_ = ((i as f64) + dx) / (width - 1) as f64;
+            let v_ = ((j as f64) + dy) / (height - 1) as f64;
 
             let r = scene.camera.get_ray(rng, u_, v_);
-            col += self.path_trace(rng, scene, r, self.depth);
+            let col = match self.integrator {
+                Integrator::PathTracer | Integrator::PhotonMapping(_) => self.path_trace(rng, scene, r.clone(), photons),
+                Integrator::Bidirectional => bidirectional::trace(rng, scene, r.clone(), self.depth, self.roulette_depth)
+            };
+
+            samples.add(col);
+            self.splat(splats, (i, y), (dx, 1.0 - dy), col);
+
+            // AOVs describe the first hit, found after the image's sample so they do not change it
+            if !self.aovs.is_empty() {
+                stats::count(Counter::AovRays);
+                if let Some(rec) = (scene.objects.intersect)(&scene.objects, rng, &r, EPSILON, INFINITY) {
+                    for aov in &self.aovs {
+                        samples.add_aov(*aov, aov.evaluate(scene, &r, &rec));
+                    }
+                }
+            }
         });
 
-        col / self.num_samples as f64
+        samples
+    }
+
+    /// Splats sample, taken at offset (dx,dy) from the top left corner of pixel (x,y), onto the pixels
+    /// around it weighted by the filter. Rows count from the top
+    fn splat(&self, splats: &mut Splats, (x, y): (u32, u32), (dx, dy): (f64, f64), sample: Colour) {
+        let margin = self.filter.margin() as i64;
+        let (x, y) = (x as i64, y as i64);
+
+        // only pixels the splats cover are visited
+        let (columns, rows) = splats.bounds();
+        for py in (y - margin).max(rows.start)..(y + margin + 1).min(rows.end) {
+            for px in (x - margin).max(columns.start)..(x + margin + 1).min(columns.end) {
+                let weight = self.filter.evaluate(dx - 0.5 - (px - x) as f64, dy - 0.5 - (py - y) as f64);
+
+                if weight != 0.0 {
+                    splats.add(px, py, sample, weight);
+                }
+            }
+        }
     }
 
-    fn path_trace(&self, rng: &mut SmallRng, scene: &Scene, r: Ray, depth: u32) -> Colour {
-        // max recursion limit reached
-        if depth <= 0 {
-            return Colour::zero();
+    /// Traces a path starting with camera ray r through the scene, returning the light carried back along it.
+    /// If photons is given, caustics are taken from it instead of from the path
+    fn path_trace(&self, rng: &mut dyn Sampler, scene: &Scene, r: Ray, photons: Option<&PhotonMap>) -> Colour {
+        let mut path = PathState::new(r, PATH_VERTICES.take());
+        stats::count(Counter::Paths);
+
+        // max depth is a hard limit, paths normally end through russian roulette
+        while path.bounces < self.depth {
+            if !self.path_step(rng, scene, &mut path, photons) {
+                break;
+            }
+
+            path.bounces += 1;
         }
 
+        let radiance = path.radiance();
+        PATH_VERTICES.set(path.vertices);
+        radiance
+    }
+
+    /// Extends path by one bounce, adding a vertex with the light found there.
+    /// Returns false if the path ended
+    fn path_step(&self, rng: &mut dyn Sampler, scene: &Scene, path: &mut PathState, photons: Option<&PhotonMap>) -> bool {
+        let r = &path.ray;
+
         // intersect ray with scene
-        let rec = match (scene.objects.intersect)(&scene.objects, rng, &r, EPSILON, INFINITY) {
+        stats::count(if path.bounces == 0 { Counter::PrimaryRays } else { Counter::SecondaryRays });
+        let rec = match (scene.objects.intersect)(&scene.objects, rng, r, EPSILON, INFINITY) {
             Some(rec) => rec,
-            None => return scene.background_colour
+            None => {
+                path.vertices.push((scene.background_colour, Colour::zero()));
+                return false;
+            }
         };
+        stats::count(Counter::PathVertices);
 
         // get emitted light from object hit
-        let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+        let mut emitted = path.emitted_weight * rec.material.emitted(rec.u, rec.v, &rec.p);
+
+        // photons were emitted from the scene's lights, so only those lights are left out
+        if path.caustic && emitted != Colour::zero() {
+            let lights = &scene.lights;
+
+            if (lights.pdf_value)(lights, rng, &r.origin, &r.dir, r.time) > 0.0 {
+                emitted = Colour::zero();
+            }
+        }
 
         // get scattered ray from the material
-        let (attenuation, scattered) = match rec.material.scatter(rng, r, &rec) {
+        let (mut attenuation, scattered) = match rec.material.scatter(rng, r.clone(), &rec) {
             Some((attenuation, scattered)) => (attenuation, scattered),
-            None => return emitted // if light doesnt scatter off this object, return the light emitted from it
+            None => { // if light doesnt scatter off this object, only the light emitted from it is left
+                path.vertices.push((emitted, Colour::zero()));
+                return false;
+            }
         };
+        stats::count(Counter::ScatterEvents);
+
+        // next event estimation, only on surfaces that can evaluate their BSDF
+        let (direct, emitted_weight) = match self.sample_lights(rng, scene, r, &rec) {
+            Some(direct) => {
+                let lights = &scene.lights;
+                let light_pdf = (lights.pdf_value)(lights, rng, &scattered.origin, &scattered.dir, scattered.time);
+                let scatter_pdf = rec.material.pdf(r, &rec, &scattered.dir);
 
-        emitted + attenuation * self.path_trace(rng, scene, scattered, depth - 1)
+                // light that can't be reached by light sampling is left entirely to the scattered ray
+                let weight = if light_pdf > 0.0 { self.mis_heuristic.weight(scatter_pdf, light_pdf) } else { 1.0 };
+                (direct, weight)
+            },
+            None => (Colour::zero(), 1.0)
+        };
+
+        let mut light = emitted + direct;
+
+        if let Some(photons) = photons {
+            if photon_map::is_diffuse(r, &rec) {
+                light += photons.radiance(r, &rec);
+                path.after_diffuse = true;
+                path.caustic = false;
+            } else if rec.material.is_volumetric() {
+                path.after_diffuse = false;
+                path.caustic = false;
+            } else {
+                path.caustic = path.after_diffuse;
+            }
+        }
+
+        path.throughput = path.throughput * attenuation;
+
+        if path.bounces >= self.roulette_depth {
+            match russian_roulette(rng, &path.throughput) {
+                Some(scale) => {
+                    attenuation *= scale;
+                    path.throughput *= scale;
+                },
+                None => {
+                    path.vertices.push((light, Colour::zero()));
+                    return false;
+                }
+            }
+        }
+
+        path.vertices.push((light, attenuation));
+        path.ray = scattered;
+        path.emitted_weight = emitted_weight;
+
+        true
+    }
+
+    /// Estimates light arriving at rec directly from the scene's lights with a single shadow ray,
+    /// weighted against the chance of the scattered ray finding the same light.
+    /// Returns None if the surface hit can't sample lights (specular) or the scene has no lights
+    fn sample_lights(&self, rng: &mut dyn Sampler, scene: &Scene, r: &Ray, rec: &Intersection) -> Option<Colour> {
+        let lights = &scene.lights;
+
+        if object_list::is_empty(lights) {
+            return None;
+        }
+
+        let dir = match (lights.sample)(lights, rng, &rec.p, r.time) {
+            Some(dir) => dir,
+            None => return Some(Colour::zero())
+        };
+
+        let f = rec.material.eval(r, rec, &dir)?;
+        let light_pdf = (lights.pdf_value)(lights, rng, &rec.p, &dir, r.time);
+
+        // light is below the surface, no need to trace a shadow ray
+        if light_pdf <= 0.0 || (f.x <= 0.0 && f.y <= 0.0 && f.z <= 0.0) {
+            return Some(Colour::zero());
+        }
+
+        let shadow_ray = Ray::new(rec.p, dir, r.time);
+        stats::count(Counter::ShadowRays);
+        let light = match (scene.objects.intersect)(&scene.objects, rng, &shadow_ray, EPSILON, INFINITY) {
+            Some(light_rec) => light_rec.material.emitted(light_rec.u, light_rec.v, &light_rec.p),
+            None => Colour::zero()
+        };
+
+        let weight = self.mis_heuristic.weight(light_pdf, rec.material.pdf(r, rec, &dir));
+
+        Some(weight * f * light / light_pdf)
     }
 }
+
+/// Adds the samples taken for each pixel of tile to film, along with the splats onto pixels in tile.
+/// Returns the splats onto pixels outside tile, which are left for the caller to add
+fn add_tile(film: &mut Film, tile: &Tile, samples: &[PixelSamples], splats: &Splats) -> Vec<(u32, u32, Colour, f64)> {
+    let tile_width = tile.x1 - tile.x0;
+
+    for (k, pixel) in samples.iter().enumerate() {
+        let k = k as u32;
+        film.add_samples(tile.x0 + k % tile_width, tile.y0 + k / tile_width, pixel);
+    }
+
+    let (inside, outside): (Vec<_>, Vec<_>) = splats.pixels()
+        .filter(|&(_, _, _, weight)| weight != 0.0)
+        .partition(|&(i, j, _, _)| i >= tile.x0 && i < tile.x1 && j >= tile.y0 && j < tile.y1);
+
+    for (i, j, weighted_sum, weight) in inside {
+        film.add_splat(i, j, weighted_sum, weight);
+    }
+
+    outside
+}
@@ -0,0 +1,330 @@
+/// Samplers handing out the random numbers used to render each pixel sample
+/*
+ Every decision a sample makes, such as where in the pixel it goes, where on
+ the lens, which way it scatters and how far it gets through a medium, takes
+ its numbers from the sampler's dimensions in turn, one for 1D decisions and a
+ pair for 2D ones. Independent random numbers clump together, while the other
+ samplers spread each dimension's values over the pixel's samples so that they
+ cover the domain evenly, which converges faster for the same number of samples.
+
+ The stratified and Sobol samplers are padded: each dimension, or pair of
+ dimensions, is spread out over the pixel's samples on its own, and shuffled
+ differently for every pixel and dimension so that dimensions do not line up
+ with each other. Halton points are spread out over all dimensions together.
+ Dimensions past those a sampler has values for, and decisions that do not need
+ well spread values, use the sampler as a plain random number generator.
+*/
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+use crate::random::stream_seed;
+
+// most strata the stratified sampler spreads a pixel's samples over, later samples go through them again
+const MAX_STRATA: u32 = 1 << 16;
+
+/// Source of the random numbers for one sample of a pixel
+pub trait Sampler: RngCore {
+    /// Returns the next dimension, in [0,1)
+    fn get_1d(&mut self) -> f64;
+
+    /// Returns the next two dimensions, in [0,1)^2
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// Independent uniform random numbers, with nothing spreading them out
+impl Sampler for SmallRng {
+    fn get_1d(&mut self) -> f64 {
+        self.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.gen(), self.gen())
+    }
+}
+
+/// Kinds of sampler the renderer can take samples with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent,    // uniform random numbers
+    Stratified,     // one jittered sample in each of samples_per_pixel strata, or cells of a grid for pairs, up to MAX_STRATA
+    Halton,         // Halton sequence, with its digits scrambled per pixel
+    Sobol           // Sobol (0,2)-sequence per pair of dimensions, Owen scrambled per pixel
+}
+
+impl SamplerKind {
+    /// Creates the sampler for sample number index of the pixel, out of samples_per_pixel planned.
+    /// The values only depend on seed, pixel and index, so samples can be taken in any order
+    pub fn create(&self, seed: u64, pixel: u64, index: u32, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        let rng = SmallRng::seed_from_u64(stream_seed(seed, &[pixel, index as u64]));
+
+        // shared by every sample of the pixel, so that its samples are spread out together
+        let pixel_seed = stream_seed(seed, &[pixel, u64::MAX]);
+
+        match self {
+            SamplerKind::Independent => Box::new(rng),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                sequence: Sequence::new(rng, pixel_seed, index),
+                samples_per_pixel: samples_per_pixel.clamp(1, MAX_STRATA)
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler { sequence: Sequence::new(rng, pixel_seed, index) }),
+            SamplerKind::Sobol => Box::new(SobolSampler { sequence: Sequence::new(rng, pixel_seed, index) })
+        }
+    }
+}
+
+/// State shared by the samplers that spread values over a pixel's samples
+struct Sequence {
+    rng: SmallRng,      // for jitter and dimensions past the end of the sequence
+    pixel_seed: u64,
+    index: u32,         // sample index within the pixel
+    dimension: u32      // next dimension to hand out
+}
+
+impl Sequence {
+    fn new(rng: SmallRng, pixel_seed: u64, index: u32) -> Self {
+        Self { rng, pixel_seed, index, dimension: 0 }
+    }
+
+    /// Moves on by count dimensions, returning the first along with a seed for it unique to the pixel
+    fn next(&mut self, count: u32) -> (u32, u64) {
+        let dimension = self.dimension;
+        self.dimension += count;
+
+        (dimension, stream_seed(self.pixel_seed, &[dimension as u64]))
+    }
+}
+
+/// Forwards the plain random number generator to the sequence's rng
+macro_rules! impl_rng_core {
+    ($sampler:ty) => {
+        impl RngCore for $sampler {
+            fn next_u32(&mut self) -> u32 {
+                self.sequence.rng.next_u32()
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                self.sequence.rng.next_u64()
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                self.sequence.rng.fill_bytes(dest)
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                self.sequence.rng.try_fill_bytes(dest)
+            }
+        }
+    };
+}
+
+struct StratifiedSampler {
+    sequence: Sequence,
+    samples_per_pixel: u32
+}
+
+impl_rng_core!(StratifiedSampler);
+
+impl Sampler for StratifiedSampler {
+    fn get_1d(&mut self) -> f64 {
+        let (_, seed) = self.sequence.next(1);
+        let n = self.samples_per_pixel;
+        let stratum = permutation_element(self.sequence.index % n, n, seed);
+
+        (stratum as f64 + self.sequence.rng.gen::<f64>()) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, seed) = self.sequence.next(2);
+
+        // the smallest grid of at least samples_per_pixel cells, each sample takes a different cell
+        let nx = (self.samples_per_pixel as f64).sqrt().ceil() as u32;
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let cell = permutation_element(self.sequence.index % (nx * ny), nx * ny, seed);
+
+        let rng = &mut self.sequence.rng;
+        (((cell % nx) as f64 + rng.gen::<f64>()) / nx as f64, ((cell / nx) as f64 + rng.gen::<f64>()) / ny as f64)
+    }
+}
+
+// places in radical inverses are scrambled down to this size, enough for 2^16 samples per pixel
+const MIN_SCRAMBLED_SCALE: f64 = 1.0 / 65536.0;
+
+// largest f64 below 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// bases of the Halton sequence's dimensions
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131
+];
+
+struct HaltonSampler {
+    sequence: Sequence
+}
+
+impl_rng_core!(HaltonSampler);
+
+impl HaltonSampler {
+    /// Scrambled radical inverse of the sample index in the dimension's base
+    fn dimension(&mut self, dimension: u32, seed: u64) -> f64 {
+        if dimension as usize >= PRIMES.len() {
+            return self.sequence.rng.gen();
+        }
+
+        scrambled_radical_inverse(PRIMES[dimension as usize], self.sequence.index, seed)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn get_1d(&mut self) -> f64 {
+        let (dimension, seed) = self.sequence.next(1);
+        self.dimension(dimension, seed)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (dimension, seed) = self.sequence.next(2);
+        (self.dimension(dimension, seed), self.dimension(dimension + 1, splitmix(seed)))
+    }
+}
+
+struct SobolSampler {
+    sequence: Sequence
+}
+
+impl_rng_core!(SobolSampler);
+
+impl SobolSampler {
+    /// Index into the sequence for this sample, shuffled per pixel and dimension
+    fn shuffled_index(&self, seed: u64) -> u32 {
+        nested_uniform_scramble(self.sequence.index, seed as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_1d(&mut self) -> f64 {
+        let (_, seed) = self.sequence.next(1);
+        let index = self.shuffled_index(seed);
+
+        to_unit_u32(nested_uniform_scramble(sobol_0(index), (seed >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, seed) = self.sequence.next(2);
+        let index = self.shuffled_index(seed);
+        let scramble = splitmix(seed);
+
+        (
+            to_unit_u32(nested_uniform_scramble(sobol_0(index), scramble as u32)),
+            to_unit_u32(nested_uniform_scramble(sobol_1(index), (scramble >> 32) as u32))
+        )
+    }
+}
+
+/// First dimension of the Sobol sequence, the van der Corput sequence in base 2, as a 32 bit fraction
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// Second dimension of the Sobol sequence as a 32 bit fraction, whose generator matrix is Pascal's triangle mod 2
+fn sobol_1(mut index: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut x = 0;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+
+        index >>= 1;
+        v ^= v >> 1;
+    }
+
+    x
+}
+
+/// Owen scrambling of a 32 bit fraction, using Laine and Karras' hash as in Burley's "Practical Hash-based Owen Scrambling".
+/// Each bit is flipped depending on the bits above it, which keeps the sequence's stratification
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+
+    x.reverse_bits()
+}
+
+/// Mirrors the digits of index in base about the decimal point, adding a random amount mod base chosen by seed
+/// to the digits in each place. As every place's digits are permuted, points that were in different
+/// intervals of the sequence stay in different intervals
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let (mut reversed, mut scale, mut state) = (0.0, inv_base, seed);
+
+    // zero digits after the index's last are scrambled too, down to places fine enough to tell apart
+    // the pixel's samples, so that samples with fewer digits are scrambled the same way as the rest
+    while index > 0 || scale > MIN_SCRAMBLED_SCALE {
+        state = splitmix(state);
+        let digit = (index % base + (state % base as u64) as u32) % base;
+
+        reversed += digit as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+    }
+
+    // the rest of the places would be scrambled into random digits, filling in a random amount below the last
+    reversed += scale * base as f64 * to_unit(splitmix(state));
+
+    reversed.min(ONE_MINUS_EPSILON)
+}
+
+/// Returns element i of a random permutation of 0..n chosen by seed, from Kensler's "Correlated Multi-Jittered Sampling"
+fn permutation_element(mut i: u32, n: u32, seed: u64) -> u32 {
+    let p = seed as u32;
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // permutes within the next power of two, repeating until the result lands in 0..n
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < n {
+            return i.wrapping_add(p) % n;
+        }
+    }
+}
+
+fn splitmix(seed: u64) -> u64 {
+    stream_seed(seed, &[])
+}
+
+/// Maps the top 53 bits of x to [0,1)
+fn to_unit(x: u64) -> f64 {
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Maps a 32 bit fraction to [0,1)
+fn to_unit_u32(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
@@ -1,28 +1,58 @@
+use std::{collections::HashMap, sync::Arc};
 use crate::{
     colour::Colour,
-    objects::Object,
+    objects::{self, Object, object_list},
+    materials::Material,
     camera::Camera
 };
 
 pub struct Scene {
     pub objects: Object,
     pub camera: Camera,
-    pub background_colour: Colour
+    pub background_colour: Colour,
+    pub lights: Object,             // emissive objects sampled directly, kept as an object list
+    material_ids: HashMap<usize, u32>   // ids of the materials of objects, by address
 }
 
 impl Scene {
     pub fn new(camera: Camera, objects: Object, background_colour: Colour) -> Self {
+        // materials are numbered in the order of the ids of the objects using them, which
+        // does not change when the objects are built into a BVH
+        let mut material_ids = HashMap::new();
+        objects::visit_materials(&objects, &mut |material| {
+            let id = material_ids.len() as u32 + 1;
+            material_ids.entry(address(material)).or_insert(id);
+        });
+
         Self {
             camera,
             objects,
-            background_colour
+            background_colour,
+            lights: object_list::new(),
+            material_ids
         }
     }
 
+    /// Returns the id of material, counting from 1 over the materials of the objects the scene
+    /// was created with, or 0 if it is not one of them
+    pub fn material_id(&self, material: &Arc<dyn Material>) -> u32 {
+        self.material_ids.get(&address(material)).copied().unwrap_or(0)
+    }
+
     /// Set background colour for this render
     pub fn set_background_colour(&mut self, colour: Colour) {
         self.background_colour = colour;
     }
+
+    /// Register an emissive object so that the renderer can send shadow rays towards it.
+    /// The object is only used for sampling and should match geometry already in the scene
+    pub fn add_light(&mut self, light: Object) {
+        object_list::add(&mut self.lights, light);
+    }
+}
+
+fn address(material: &Arc<dyn Material>) -> usize {
+    Arc::as_ptr(material) as *const () as usize
 }
 
 
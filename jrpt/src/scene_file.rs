@@ -0,0 +1,738 @@
+/// Scenes described in TOML files, loaded without recompiling
+/*
+ A scene file has up to six parts, all optional apart from the camera:
+
+   background = [0.7, 0.8, 1.0]     # colour of rays that hit nothing, defaults to black
+   bvh = true                       # put the scene's objects in a BVH, the default
+
+   [render]                         # settings for the renderer, with these defaults
+   width = 400
+   height = 400
+   samples_per_pixel = 100
+   samples_per_pass = 50
+   max_depth = 64
+   roulette_depth = 3
+   seed = 0
+   integrator = "path_tracer"       # "bidirectional", or { type = "photon_mapping", photons = 100000, radius = 1.0, alpha = 0.7 }
+   sampler = "independent"          # "stratified", "halton" or "sobol"
+   filter = "box"                   # or { type = "mitchell", radius = 2.0 }, also "tent", "gaussian" and "lanczos"
+
+   [camera]
+   lookfrom = [278, 278, -800]
+   lookat = [278, 278, 0]
+   up = [0, 1, 0]                   # default
+   vfov = 40                        # vertical field of view in degrees
+   aperture = 0.0                   # default, no depth of field
+   focus_distance = 10.0            # default
+   time = [0.0, 1.0]                # shutter interval for motion blur, defaults to [0, 0]
+
+   [textures.<name>]                # referred to by name from materials and other textures
+   type = "solid"                   # colour = [r, g, b]
+   type = "checker"                 # odd, even: colours or names of textures
+   type = "noise"                   # frequency
+   type = "image"                   # file, relative to the scene file
+
+   [materials.<name>]               # referred to by name from objects
+   type = "lambertian"              # albedo: a colour or the name of a texture
+   type = "metal"                   # albedo = [r, g, b], fuzz = 0.0
+   type = "dielectric"              # ior
+   type = "diffuse_light"           # emit: a colour or the name of a texture
+   type = "isotropic"               # albedo: a colour or the name of a texture
+
+   [[objects]]
+   type = "sphere"                  # center, radius, material
+   type = "moving_sphere"           # center0, center1, time = [t0, t1], radius, material
+   type = "xy_rect"                 # x = [x0, x1], y = [y0, y1], z, material; likewise "xz_rect" and "yz_rect"
+   type = "box"                     # min, max, material
+   type = "triangle"                # p0, p1, p2, material
+   type = "mesh"                    # file, a Wavefront .obj relative to the scene file, material
+   type = "constant_medium"         # boundary: an object table, density, albedo: a colour or the name of a texture
+   type = "group"                   # objects: objects of its own, bvh = false
+
+ Any object may also have a transform, a list applied in order of tables holding one
+ of rotate_x, rotate_y or rotate_z in degrees, scale as a number or [x, y, z], or
+ translate = [x, y, z]. Spheres, rectangles and triangles may be marked light = true
+ so that they are sampled directly, which needs them to be untransformed.
+
+ Errors point at the line and column of the value, key or table at fault.
+*/
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc
+};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+use crate::{
+    renderer::{Renderer, Integrator, PhotonMapping},
+    sampler::SamplerKind,
+    filter::{Filter, FilterKind},
+    scene::Scene,
+    camera::Camera,
+    objects::{
+        Object, object_list, sphere, moving_sphere, triangle, rect_prism, wavefront_obj,
+        constant_medium, affine::Transform, bvh, aa_rectangles::{xy_rect, xz_rect, yz_rect}
+    },
+    materials::{
+        Material, lambertian::Lambertian, metal::Metal, dialetric::Dialetric,
+        diffuse_light::DiffuseLight, isotropic::Isotropic
+    },
+    textures::{
+        Texture, solid_colour::SolidColour, checker_texture::CheckerTexture,
+        noise_texture::NoiseTexture, image_texture::ImageTexture
+    },
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+/// Scene loaded from a file, along with the settings to render it with
+pub struct SceneFile {
+    pub scene: Scene,
+    pub settings: RenderSettings
+}
+
+/// Settings from the render table of a scene file
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub samples_per_pass: u32,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+    pub seed: u64,
+    pub integrator: Integrator,
+    pub sampler: SamplerKind,
+    pub filter: Filter
+}
+
+impl RenderSettings {
+    /// Creates a renderer with these settings
+    pub fn renderer(&self, multithreading: bool) -> Renderer {
+        let mut renderer = Renderer::new(self.samples_per_pixel, self.max_depth, multithreading);
+        renderer.set_roulette_depth(self.roulette_depth);
+        renderer.set_seed(self.seed);
+        renderer.set_integrator(self.integrator);
+        renderer.set_sampler(self.sampler);
+        renderer.set_filter(self.filter);
+        renderer
+    }
+}
+
+/// Error loading a scene file
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    Syntax { line: usize, column: usize, message: String },     // position counts from 1
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(err) => write!(f, "{}", err),
+            SceneFileError::Syntax { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message)
+        }
+    }
+}
+
+impl Error for SceneFileError {}
+
+impl From<io::Error> for SceneFileError {
+    fn from(err: io::Error) -> Self {
+        SceneFileError::Io(err)
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneFileError>;
+
+/// Loads the scene file at path. Files it refers to are found relative to it
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+
+    parse(&source, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Parses a scene file, finding the files it refers to relative to base_dir
+pub fn parse(source: &str, base_dir: &Path) -> Result<SceneFile> {
+    let document = ImDocument::parse(source).map_err(|err| {
+        syntax_error(source, err.span(), err.message().trim_end())
+    })?;
+
+    let mut parser = Parser { source, base_dir: base_dir.to_path_buf(), textures: HashMap::new(), materials: HashMap::new() };
+    let mut root = Table::new(document.as_table(), Some(0..0));
+
+    let settings = match root.get("render") {
+        Some(item) => parser.render_settings(item)?,
+        None => RenderSettings::default()
+    };
+
+    let (camera, time) = parser.camera(root.required("camera", &parser)?, &settings)?;
+
+    let background = match root.get("background") {
+        Some(item) => parser.vec3(item)?,
+        None => Colour::zero()
+    };
+
+    let use_bvh = match root.get("bvh") {
+        Some(item) => parser.bool(item)?,
+        None => true
+    };
+
+    // textures may refer to each other and materials to textures, so they are built as they are referred to
+    let texture_items = match root.get("textures") {
+        Some(item) => parser.named(item)?,
+        None => HashMap::new()
+    };
+    let material_items = match root.get("materials") {
+        Some(item) => parser.named(item)?,
+        None => HashMap::new()
+    };
+    let items = Items { textures: texture_items, materials: material_items };
+
+    let mut objects = object_list::new();
+    let mut lights = vec![];
+
+    if let Some(item) = root.get("objects") {
+        for table in parser.tables(item)? {
+            let (object, light) = parser.object(table, &items, &time, true)?;
+            object_list::add(&mut objects, object);
+            lights.extend(light);
+        }
+    }
+
+    parser.finish(root)?;
+
+    // named textures and materials nothing refers to may still have errors
+    for name in items.textures.keys() {
+        parser.texture(name, None, &items)?;
+    }
+    for name in items.materials.keys() {
+        parser.material(name, None, &items)?;
+    }
+
+    if use_bvh && !object_list::is_empty(&objects) {
+        objects = bvh::new(objects, time);
+    }
+
+    let mut scene = Scene::new(camera, objects, background);
+    for light in lights {
+        scene.add_light(light);
+    }
+
+    Ok(SceneFile { scene, settings })
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: 400,
+            samples_per_pixel: 100,
+            samples_per_pass: 50,
+            max_depth: 64,
+            roulette_depth: 3,
+            seed: 0,
+            integrator: Integrator::PathTracer,
+            sampler: SamplerKind::Independent,
+            filter: Filter::default()
+        }
+    }
+}
+
+/// Finds the line and column of byte offset span.start of source, both counting from 1
+pub(crate) fn position(source: &str, span: Option<Range<usize>>) -> (usize, usize) {
+    let offset = span.map_or(0, |span| span.start).min(source.len());
+    let before = &source[..offset];
+
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+
+    (line, column)
+}
+
+fn syntax_error(source: &str, span: Option<Range<usize>>, message: &str) -> SceneFileError {
+    let (line, column) = position(source, span);
+    SceneFileError::Syntax { line, column, message: message.to_string() }
+}
+
+/// Table of a scene file, keeping track of the keys read so that any others can be reported
+struct Table<'a> {
+    table: &'a dyn TableLike,
+    span: Option<Range<usize>>,
+    read: Vec<&'a str>
+}
+
+impl<'a> Table<'a> {
+    fn new(table: &'a dyn TableLike, span: Option<Range<usize>>) -> Self {
+        Self { table, span, read: vec![] }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&'a Item> {
+        let (key, item) = self.table.get_key_value(key)?;
+        self.read.push(key.get());
+        Some(item)
+    }
+
+    fn required(&mut self, key: &str, parser: &Parser) -> Result<&'a Item> {
+        self.get(key).ok_or_else(|| parser.error(self.span.clone(), &format!("missing {}", key)))
+    }
+}
+
+/// Tables of the named textures and materials
+struct Items<'a> {
+    textures: HashMap<String, &'a Item>,
+    materials: HashMap<String, &'a Item>
+}
+
+struct Parser<'s> {
+    source: &'s str,
+    base_dir: PathBuf,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>
+}
+
+impl<'s> Parser<'s> {
+    fn error(&self, span: Option<Range<usize>>, message: &str) -> SceneFileError {
+        syntax_error(self.source, span, message)
+    }
+
+    /// Reports the first key of table that was not read
+    fn finish(&self, table: Table) -> Result<()> {
+        for (key, _) in table.table.iter() {
+            if !table.read.contains(&key) {
+                let span = table.table.get_key_value(key).and_then(|(key, _)| key.span());
+                return Err(self.error(span.or(table.span), &format!("unknown key {}", key)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn table<'a>(&self, item: &'a Item) -> Result<Table<'a>> {
+        match item.as_table_like() {
+            Some(table) => Ok(Table::new(table, item.span())),
+            None => Err(self.error(item.span(), "expected a table"))
+        }
+    }
+
+    /// Reads an array of tables, either as [[name]] sections or an array of inline tables
+    fn tables<'a>(&self, item: &'a Item) -> Result<Vec<Table<'a>>> {
+        if let Some(tables) = item.as_array_of_tables() {
+            return Ok(tables.iter().map(|table| Table::new(table, table.span())).collect());
+        }
+
+        let array = item.as_array().ok_or_else(|| self.error(item.span(), "expected an array of tables"))?;
+
+        array.iter().map(|value| match value.as_inline_table() {
+            Some(table) => Ok(Table::new(table, value.span())),
+            None => Err(self.error(value.span(), "expected a table"))
+        }).collect()
+    }
+
+    /// Reads a table of named tables
+    fn named<'a>(&self, item: &'a Item) -> Result<HashMap<String, &'a Item>> {
+        let table = item.as_table_like().ok_or_else(|| self.error(item.span(), "expected a table"))?;
+        Ok(table.iter().map(|(name, item)| (name.to_string(), item)).collect())
+    }
+
+    /// Reads a table with a type, or a string giving just the type
+    fn typed<'a>(&self, item: &'a Item) -> Result<(String, Option<Table<'a>>)> {
+        if let Some(kind) = item.as_str() {
+            return Ok((kind.to_string(), None));
+        }
+
+        let mut table = self.table(item)?;
+        let kind = self.string(table.required("type", self)?)?;
+        Ok((kind, Some(table)))
+    }
+
+    fn f64(&self, item: &Item) -> Result<f64> {
+        match item.as_value() {
+            Some(Value::Float(v)) => Ok(*v.value()),
+            Some(Value::Integer(v)) => Ok(*v.value() as f64),
+            _ => Err(self.error(item.span(), "expected a number"))
+        }
+    }
+
+    fn u32(&self, item: &Item) -> Result<u32> {
+        item.as_integer().and_then(|v| u32::try_from(v).ok()).ok_or_else(|| self.error(item.span(), "expected a non-negative integer"))
+    }
+
+    fn bool(&self, item: &Item) -> Result<bool> {
+        item.as_bool().ok_or_else(|| self.error(item.span(), "expected true or false"))
+    }
+
+    fn string(&self, item: &Item) -> Result<String> {
+        item.as_str().map(str::to_string).ok_or_else(|| self.error(item.span(), "expected a string"))
+    }
+
+    fn numbers<const N: usize>(&self, item: &Item) -> Result<[f64; N]> {
+        let error = || self.error(item.span(), &format!("expected an array of {} numbers", N));
+        let array = item.as_array().filter(|array| array.len() == N).ok_or_else(error)?;
+
+        let mut numbers = [0.0; N];
+        for (n, value) in numbers.iter_mut().zip(array.iter()) {
+            *n = match value {
+                Value::Float(v) => *v.value(),
+                Value::Integer(v) => *v.value() as f64,
+                _ => return Err(self.error(value.span(), "expected a number"))
+            };
+        }
+
+        Ok(numbers)
+    }
+
+    fn vec3(&self, item: &Item) -> Result<Vec3> {
+        let [x, y, z] = self.numbers::<3>(item)?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    fn range(&self, item: &Item) -> Result<Range<f64>> {
+        let [a, b] = self.numbers::<2>(item)?;
+        Ok(a..b)
+    }
+
+    fn path(&self, item: &Item) -> Result<PathBuf> {
+        let path = self.base_dir.join(self.string(item)?);
+
+        if !path.is_file() {
+            return Err(self.error(item.span(), &format!("no such file {}", path.display())));
+        }
+
+        Ok(path)
+    }
+
+    fn render_settings(&self, item: &Item) -> Result<RenderSettings> {
+        let mut table = self.table(item)?;
+        let mut settings = RenderSettings::default();
+
+        for (key, value) in [
+            ("width", &mut settings.width),
+            ("height", &mut settings.height),
+            ("samples_per_pixel", &mut settings.samples_per_pixel),
+            ("samples_per_pass", &mut settings.samples_per_pass),
+            ("max_depth", &mut settings.max_depth),
+            ("roulette_depth", &mut settings.roulette_depth)
+        ] {
+            if let Some(item) = table.get(key) {
+                *value = self.u32(item)?;
+            }
+        }
+
+        if let Some(item) = table.get("seed") {
+            settings.seed = item.as_integer().and_then(|v| u64::try_from(v).ok())
+                .ok_or_else(|| self.error(item.span(), "expected a non-negative integer"))?;
+        }
+
+        if let Some(item) = table.get("integrator") {
+            settings.integrator = self.integrator(item)?;
+        }
+
+        if let Some(item) = table.get("sampler") {
+            settings.sampler = match self.string(item)?.as_str() {
+                "independent" => SamplerKind::Independent,
+                "stratified" => SamplerKind::Stratified,
+                "halton" => SamplerKind::Halton,
+                "sobol" => SamplerKind::Sobol,
+                other => return Err(self.error(item.span(), &format!("unknown sampler {}", other)))
+            };
+        }
+
+        if let Some(item) = table.get("filter") {
+            settings.filter = self.filter(item)?;
+        }
+
+        if settings.width == 0 || settings.height == 0 {
+            return Err(self.error(table.span.clone(), "image must be at least one pixel wide and high"));
+        }
+
+        self.finish(table)?;
+        Ok(settings)
+    }
+
+    fn integrator(&self, item: &Item) -> Result<Integrator> {
+        let (kind, table) = self.typed(item)?;
+
+        let integrator = match (kind.as_str(), table) {
+            ("path_tracer", None) => Integrator::PathTracer,
+            ("bidirectional", None) => Integrator::Bidirectional,
+            ("photon_mapping", Some(mut table)) => {
+                let photons = self.u32(table.required("photons", self)?)?;
+                let radius = self.f64(table.required("radius", self)?)?;
+                let alpha = table.get("alpha").map(|item| self.f64(item)).transpose()?;
+
+                self.finish(table)?;
+                Integrator::PhotonMapping(PhotonMapping { photons, radius, alpha })
+            },
+            ("path_tracer" | "bidirectional", Some(table)) => {
+                self.finish(table)?;
+                return self.integrator(&Item::Value(Value::from(kind)));
+            },
+            ("photon_mapping", None) => return Err(self.error(item.span(), "photon_mapping needs photons and radius")),
+            (other, _) => return Err(self.error(item.span(), &format!("unknown integrator {}", other)))
+        };
+
+        Ok(integrator)
+    }
+
+    fn filter(&self, item: &Item) -> Result<Filter> {
+        let (kind, table) = self.typed(item)?;
+
+        let kind = match kind.as_str() {
+            "box" => FilterKind::Box,
+            "tent" => FilterKind::Tent,
+            "gaussian" => FilterKind::Gaussian,
+            "mitchell" => FilterKind::Mitchell,
+            "lanczos" => FilterKind::Lanczos,
+            other => return Err(self.error(item.span(), &format!("unknown filter {}", other)))
+        };
+
+        let (mut radius, mut span) = (kind.default_radius(), item.span());
+        if let Some(mut table) = table {
+            if let Some(item) = table.get("radius") {
+                radius = self.f64(item)?;
+                span = item.span();
+            }
+            self.finish(table)?;
+        }
+
+        Filter::new(kind, radius).map_err(|err| self.error(span, &err.to_string()))
+    }
+
+    fn camera(&self, item: &Item, settings: &RenderSettings) -> Result<(Camera, Range<f64>)> {
+        let mut table = self.table(item)?;
+
+        let lookfrom = self.vec3(table.required("lookfrom", self)?)?;
+        let lookat = self.vec3(table.required("lookat", self)?)?;
+        let vfov = self.f64(table.required("vfov", self)?)?;
+
+        let up = table.get("up").map(|item| self.vec3(item)).transpose()?.unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+        let aperture = table.get("aperture").map(|item| self.f64(item)).transpose()?.unwrap_or(0.0);
+        let focus_distance = table.get("focus_distance").map(|item| self.f64(item)).transpose()?.unwrap_or(10.0);
+        let time = table.get("time").map(|item| self.range(item)).transpose()?.unwrap_or(0.0..0.0);
+
+        self.finish(table)?;
+
+        let aspect_ratio = settings.width as f64 / settings.height as f64;
+        Ok((Camera::new(lookfrom, lookat, up, vfov, aspect_ratio, aperture, focus_distance, time.clone()), time))
+    }
+
+    /// Returns the named texture, building it the first time it is asked for.
+    /// at is the span of whatever refers to it, if anything
+    fn texture(&mut self, name: &str, at: Option<Range<usize>>, items: &Items) -> Result<Arc<dyn Texture>> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+
+        let item = *items.textures.get(name).ok_or_else(|| self.error(at.clone(), &format!("no texture named {}", name)))?;
+
+        // a placeholder stops textures that refer to themselves from recursing forever
+        if at.is_some() && self.textures.contains_key(&format!("#{}", name)) {
+            return Err(self.error(at, &format!("texture {} refers to itself", name)));
+        }
+        self.textures.insert(format!("#{}", name), Arc::new(SolidColour::new(Colour::zero())));
+
+        let mut table = self.table(item)?;
+        let kind = self.string(table.required("type", self)?)?;
+
+        let texture: Arc<dyn Texture> = match kind.as_str() {
+            "solid" => Arc::new(SolidColour::new(self.vec3(table.required("colour", self)?)?)),
+            "checker" => {
+                let odd = self.colour_or_texture(table.required("odd", self)?, items)?;
+                let even = self.colour_or_texture(table.required("even", self)?, items)?;
+                Arc::new(CheckerTexture::from_texture(odd, even))
+            },
+            "noise" => Arc::new(NoiseTexture::new(self.f64(table.required("frequency", self)?)?)),
+            "image" => {
+                let file = table.required("file", self)?;
+                let path = self.path(file)?;
+                Arc::new(ImageTexture::new(&path.to_string_lossy()).map_err(|err| self.error(file.span(), &err.to_string()))?)
+            },
+            other => return Err(self.error(item.span(), &format!("unknown texture type {}", other)))
+        };
+
+        self.finish(table)?;
+        self.textures.remove(&format!("#{}", name));
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    /// Reads a colour, or the name of a texture
+    fn colour_or_texture(&mut self, item: &Item, items: &Items) -> Result<Arc<dyn Texture>> {
+        match item.as_str() {
+            Some(name) => self.texture(name, item.span(), items),
+            None => Ok(Arc::new(SolidColour::new(self.vec3(item)?)))
+        }
+    }
+
+    /// Returns the named material, building it the first time it is asked for
+    fn material(&mut self, name: &str, at: Option<Range<usize>>, items: &Items) -> Result<Arc<dyn Material>> {
+        if let Some(material) = self.materials.get(name) {
+            return Ok(material.clone());
+        }
+
+        let item = *items.materials.get(name).ok_or_else(|| self.error(at, &format!("no material named {}", name)))?;
+        let mut table = self.table(item)?;
+        let kind = self.string(table.required("type", self)?)?;
+
+        let material: Arc<dyn Material> = match kind.as_str() {
+            "lambertian" => Arc::new(Lambertian::from_texture(self.colour_or_texture(table.required("albedo", self)?, items)?)),
+            "metal" => {
+                let albedo = self.vec3(table.required("albedo", self)?)?;
+                let fuzz = table.get("fuzz").map(|item| self.f64(item)).transpose()?.unwrap_or(0.0);
+                Arc::new(Metal::new(albedo, fuzz))
+            },
+            "dielectric" => Arc::new(Dialetric::new(self.f64(table.required("ior", self)?)?)),
+            "diffuse_light" => Arc::new(DiffuseLight::from_texture(self.colour_or_texture(table.required("emit", self)?, items)?)),
+            "isotropic" => Arc::new(Isotropic::from_texture(self.colour_or_texture(table.required("albedo", self)?, items)?)),
+            other => return Err(self.error(item.span(), &format!("unknown material type {}", other)))
+        };
+
+        self.finish(table)?;
+        self.materials.insert(name.to_string(), material.clone());
+        Ok(material)
+    }
+
+    /// Builds an object, along with a copy of it to sample if it is marked as a light.
+    /// Boundaries of media do not need a material
+    fn object(&mut self, mut table: Table, items: &Items, time: &Range<f64>, needs_material: bool) -> Result<(Object, Option<Object>)> {
+        let kind = self.string(table.required("type", self)?)?;
+
+        let material = match table.get("material") {
+            Some(item) => self.material(&self.string(item)?, item.span(), items)?,
+            None if needs_material && !matches!(kind.as_str(), "group" | "constant_medium") => {
+                return Err(self.error(table.span.clone(), "missing material"));
+            },
+            None => Arc::new(Lambertian::new(Colour::zero())) as Arc<dyn Material>
+        };
+
+        let light = match table.get("light") {
+            Some(item) => self.bool(item)?,
+            None => false
+        };
+
+        // built twice for lights, once for the scene and once for sampling
+        let mut build = |table: &mut Table| -> Result<Object> {
+            self.shape(&kind, table, &material, items, time)
+        };
+
+        let object = build(&mut table)?;
+        let light = match light {
+            true if !matches!(kind.as_str(), "sphere" | "xy_rect" | "xz_rect" | "yz_rect" | "triangle") => {
+                return Err(self.error(table.span.clone(), &format!("{} can not be sampled as a light", kind)));
+            },
+            true => Some(build(&mut Table::new(table.table, table.span.clone()))?),
+            false => None
+        };
+
+        let object = match table.get("transform") {
+            Some(item) if light.is_some() => return Err(self.error(item.span(), "lights can not be transformed")),
+            Some(item) => self.transform(item, object)?,
+            None => object
+        };
+
+        self.finish(table)?;
+        Ok((object, light))
+    }
+
+    fn shape(&mut self, kind: &str, table: &mut Table, material: &Arc<dyn Material>, items: &Items, time: &Range<f64>) -> Result<Object> {
+        let material = material.clone();
+
+        let object = match kind {
+            "sphere" => sphere::new(self.vec3(table.required("center", self)?)?, self.f64(table.required("radius", self)?)?, material),
+            "moving_sphere" => {
+                let center0 = self.vec3(table.required("center0", self)?)?;
+                let center1 = self.vec3(table.required("center1", self)?)?;
+                let time = self.range(table.required("time", self)?)?;
+                moving_sphere::new(center0, center1, time, self.f64(table.required("radius", self)?)?, material)
+            },
+            "xy_rect" => {
+                let (x, y) = (self.range(table.required("x", self)?)?, self.range(table.required("y", self)?)?);
+                xy_rect::new(x.start, x.end, y.start, y.end, self.f64(table.required("z", self)?)?, material)
+            },
+            "xz_rect" => {
+                let (x, z) = (self.range(table.required("x", self)?)?, self.range(table.required("z", self)?)?);
+                xz_rect::new(x.start, x.end, z.start, z.end, self.f64(table.required("y", self)?)?, material)
+            },
+            "yz_rect" => {
+                let (y, z) = (self.range(table.required("y", self)?)?, self.range(table.required("z", self)?)?);
+                yz_rect::new(y.start, y.end, z.start, z.end, self.f64(table.required("x", self)?)?, material)
+            },
+            "box" => rect_prism::new(self.vec3(table.required("min", self)?)?, self.vec3(table.required("max", self)?)?, material),
+            "triangle" => {
+                let p0: Point3 = self.vec3(table.required("p0", self)?)?;
+                let p1: Point3 = self.vec3(table.required("p1", self)?)?;
+                let p2: Point3 = self.vec3(table.required("p2", self)?)?;
+                triangle::new(p0, p1, p2, None, None, material)
+            },
+            "mesh" => {
+                let file = table.required("file", self)?;
+                let path = self.path(file)?;
+                wavefront_obj::new_mesh(path.to_string_lossy().into_owned(), material).map_err(|err| self.error(file.span(), &err.to_string()))?
+            },
+            "constant_medium" => {
+                let boundary = self.table(table.required("boundary", self)?)?;
+                let (boundary, _) = self.object(boundary, items, time, false)?;
+                let density = self.f64(table.required("density", self)?)?;
+                constant_medium::from_texture(boundary, density, self.colour_or_texture(table.required("albedo", self)?, items)?)
+            },
+            "group" => {
+                let mut group = object_list::new();
+                for child in self.tables(table.required("objects", self)?)? {
+                    let (child, light) = self.object(child, items, time, true)?;
+                    if light.is_some() {
+                        return Err(self.error(table.span.clone(), "lights can not be grouped"));
+                    }
+                    object_list::add(&mut group, child);
+                }
+
+                let use_bvh = table.get("bvh").map(|item| self.bool(item)).transpose()?.unwrap_or(false);
+                if use_bvh && !object_list::is_empty(&group) { bvh::new(group, time.clone()) } else { group }
+            },
+            other => return Err(self.error(table.span.clone(), &format!("unknown object type {}", other)))
+        };
+
+        Ok(object)
+    }
+
+    /// Wraps object in the transforms listed in item, applied in order
+    fn transform(&self, item: &Item, object: Object) -> Result<Object> {
+        let mut transform = Transform::new();
+
+        for mut step in self.tables(item)? {
+            let (key, _) = step.table.iter().next().ok_or_else(|| self.error(step.span.clone(), "expected a transform"))?;
+            let value = step.get(key).unwrap();
+
+            match key {
+                "rotate_x" => transform = transform.rotate_x(self.f64(value)?.to_radians()),
+                "rotate_y" => transform = transform.rotate_y(self.f64(value)?.to_radians()),
+                "rotate_z" => transform = transform.rotate_z(self.f64(value)?.to_radians()),
+                "translate" => {
+                    let t = self.vec3(value)?;
+                    transform = transform.translate(t.x, t.y, t.z);
+                },
+                "scale" => {
+                    let s = match value.as_array() {
+                        Some(_) => self.vec3(value)?,
+                        None => Vec3::from_value(self.f64(value)?)
+                    };
+
+                    if s.x == 0.0 || s.y == 0.0 || s.z == 0.0 {
+                        return Err(self.error(value.span(), "can not scale by zero"));
+                    }
+
+                    transform = transform.scale(s.x, s.y, s.z);
+                },
+                other => return Err(self.error(step.span.clone(), &format!("unknown transform {}", other)))
+            }
+
+            self.finish(step)?;
+        }
+
+        transform.apply(object).map_err(|err| self.error(item.span(), &err.to_string()))
+    }
+}
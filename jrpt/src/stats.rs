@@ -0,0 +1,162 @@
+/// Counts of the work done by a render
+/*
+ Counters are kept per thread and bumped without any synchronisation. Work done
+ on a thread is measured by reading its counters before and after, and the
+ totals are added up with the rest of the results the work produces, e.g. when
+ a tile is added to the film.
+*/
+use std::{cell::Cell, fmt, ops::AddAssign, time::Duration};
+
+/// Counters kept for each thread
+#[derive(Clone, Copy)]
+pub(crate) enum Counter {
+    PrimaryRays,
+    AovRays,
+    SecondaryRays,
+    ShadowRays,
+    AabbTests,
+    PrimitiveTests,
+    ScatterEvents,
+    Paths,
+    PathVertices
+}
+
+const NUM_COUNTERS: usize = 9;
+
+thread_local! {
+    static COUNTS: [Cell<u64>; NUM_COUNTERS] = const { [const { Cell::new(0) }; NUM_COUNTERS] };
+    static BVH_BUILD: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Adds one to counter on this thread
+pub(crate) fn count(counter: Counter) {
+    add(counter, 1);
+}
+
+/// Adds n to counter on this thread
+pub(crate) fn add(counter: Counter, n: u64) {
+    COUNTS.with(|counts| {
+        let count = &counts[counter as usize];
+        count.set(count.get() + n);
+    });
+}
+
+/// Adds time spent building a BVH to this thread's total
+pub(crate) fn add_bvh_build(time: Duration) {
+    BVH_BUILD.with(|total| total.set(total.get() + time));
+}
+
+/// Returns the time this thread has spent building BVHs, for filling in RenderStats::bvh_build
+pub fn bvh_build_time() -> Duration {
+    BVH_BUILD.with(Cell::get)
+}
+
+/// Runs f, returning its result along with the counts of the work it did on this thread
+pub(crate) fn measure<T>(f: impl FnOnce() -> T) -> (T, RenderStats) {
+    let before = snapshot();
+    let result = f();
+    let after = snapshot();
+
+    let mut counts = [0; NUM_COUNTERS];
+    for (count, (a, b)) in counts.iter_mut().zip(after.iter().zip(&before)) {
+        *count = a - b;
+    }
+
+    (result, RenderStats::from_counts(counts))
+}
+
+fn snapshot() -> [u64; NUM_COUNTERS] {
+    COUNTS.with(|counts| counts.each_ref().map(Cell::get))
+}
+
+/// Totals and timings of a render
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub primary_rays: u64,      // rays from the camera
+    pub aov_rays: u64,          // rays from the camera finding AOVs, traced again after the image's
+    pub secondary_rays: u64,    // rays scattered off surfaces and through media, and photons
+    pub shadow_rays: u64,       // rays testing whether a light can be seen
+    pub aabb_tests: u64,        // BVH nodes visited
+    pub primitive_tests: u64,   // rays tested against spheres, rectangles and triangles
+    pub scatter_events: u64,    // rays scattered by materials
+    pub paths: u64,             // paths traced from the camera
+    pub path_vertices: u64,     // surfaces and media hit by paths from the camera
+    pub bvh_build: Duration,    // left to the caller, as scenes are built before the renderer sees them, see bvh_build_time
+    pub photon_tracing: Duration,
+    pub render: Duration,       // time spent in passes, including tracing photons
+    pub write_out: Duration     // left to the caller
+}
+
+impl RenderStats {
+    fn from_counts(counts: [u64; NUM_COUNTERS]) -> Self {
+        Self {
+            primary_rays: counts[Counter::PrimaryRays as usize],
+            aov_rays: counts[Counter::AovRays as usize],
+            secondary_rays: counts[Counter::SecondaryRays as usize],
+            shadow_rays: counts[Counter::ShadowRays as usize],
+            aabb_tests: counts[Counter::AabbTests as usize],
+            primitive_tests: counts[Counter::PrimitiveTests as usize],
+            scatter_events: counts[Counter::ScatterEvents as usize],
+            paths: counts[Counter::Paths as usize],
+            path_vertices: counts[Counter::PathVertices as usize],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of rays of every kind
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.aov_rays + self.secondary_rays + self.shadow_rays
+    }
+
+    /// Returns the mean number of surfaces and media hit by paths from the camera
+    pub fn average_path_length(&self) -> f64 {
+        if self.paths == 0 { 0.0 } else { self.path_vertices as f64 / self.paths as f64 }
+    }
+
+    /// Returns the number of rays traced per second of rendering
+    pub fn rays_per_second(&self) -> f64 {
+        per_second(self.rays(), self.render)
+    }
+}
+
+fn per_second(count: u64, time: Duration) -> f64 {
+    if time.is_zero() { 0.0 } else { count as f64 / time.as_secs_f64() }
+}
+
+impl AddAssign<&RenderStats> for RenderStats {
+    fn add_assign(&mut self, other: &RenderStats) {
+        self.primary_rays += other.primary_rays;
+        self.aov_rays += other.aov_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.aabb_tests += other.aabb_tests;
+        self.primitive_tests += other.primitive_tests;
+        self.scatter_events += other.scatter_events;
+        self.paths += other.paths;
+        self.path_vertices += other.path_vertices;
+        self.bvh_build += other.bvh_build;
+        self.photon_tracing += other.photon_tracing;
+        self.render += other.render;
+        self.write_out += other.write_out;
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millions = |count: u64| count as f64 / 1e6;
+
+        writeln!(f, "rays:              {:.3}M ({:.3}M/s)", millions(self.rays()), self.rays_per_second() / 1e6)?;
+        writeln!(f, "  primary:         {:.3}M", millions(self.primary_rays))?;
+        writeln!(f, "  AOV:             {:.3}M", millions(self.aov_rays))?;
+        writeln!(f, "  secondary:       {:.3}M", millions(self.secondary_rays))?;
+        writeln!(f, "  shadow:          {:.3}M", millions(self.shadow_rays))?;
+        writeln!(f, "AABB tests:        {:.3}M ({:.1}/ray)", millions(self.aabb_tests), self.aabb_tests as f64 / self.rays().max(1) as f64)?;
+        writeln!(f, "primitive tests:   {:.3}M ({:.1}/ray)", millions(self.primitive_tests), self.primitive_tests as f64 / self.rays().max(1) as f64)?;
+        writeln!(f, "scatter events:    {:.3}M", millions(self.scatter_events))?;
+        writeln!(f, "average path:      {:.2} vertices", self.average_path_length())?;
+        writeln!(f, "BVH build:         {:.3}s", self.bvh_build.as_secs_f64())?;
+        writeln!(f, "photon tracing:    {:.3}s", self.photon_tracing.as_secs_f64())?;
+        writeln!(f, "render:            {:.3}s", self.render.as_secs_f64())?;
+        write!(f, "write out:         {:.3}s", self.write_out.as_secs_f64())
+    }
+}
@@ -1,2 +1,18 @@
 pub mod test_vec3;
-pub mod test_utils;
\ No newline at end of file
+pub mod test_utils;
+pub mod test_light_sampling;
+pub mod test_materials;
+pub mod test_renderer;
+pub mod test_output;
+pub mod test_tone_mapping;
+pub mod test_bidirectional;
+pub mod test_photon_map;
+pub mod test_aov;
+pub mod test_denoise;
+pub mod test_filter;
+pub mod test_sampler;
+pub mod test_stats;
+pub mod test_checkpoint;
+pub mod test_scene_file;
+pub mod test_affine;
+pub mod test_pbrt;
@@ -0,0 +1,107 @@
+use std::{sync::Arc, f64::consts::PI};
+use nalgebra::{Matrix4, Vector4};
+use rand::{rngs::SmallRng, SeedableRng};
+use crate::{
+    objects::{affine::{Transform, SingularTransform}, sphere, Object},
+    materials::lambertian::Lambertian,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3,
+    ray::Ray
+};
+
+fn unit_sphere() -> Object {
+    sphere::canonical(Arc::new(Lambertian::new(Colour::from_value(0.5))))
+}
+
+/// Applies the matrix of transform to point p
+fn transform_point(transform: &Transform, p: &Point3) -> Point3 {
+    let v = transform.matrix() * Vector4::new(p.x, p.y, p.z, 1.0);
+    Point3::new(v.x, v.y, v.z)
+}
+
+fn assert_near(a: &Vec3, b: &Vec3) {
+    assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+fn assert_matrix_near(a: Matrix4<f64>, b: Matrix4<f64>) {
+    assert!((a - b).abs().max() < 1e-9, "{} != {}", a, b);
+}
+
+#[test]
+fn test_apply_computes_inverse() {
+    let object = Transform::new().scale_uniform(2.0).translate(0.0, 0.0, 5.0).apply(unit_sphere()).unwrap();
+    let mut rng = SmallRng::seed_from_u64(0);
+
+    // sphere of radius 2 centred 5 along the z axis
+    let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, 1.0), 0.0);
+    let rec = (object.intersect)(&object, &mut rng, &r, 0.001, f64::INFINITY).unwrap();
+
+    assert!((rec.t - 3.0).abs() < 1e-9);
+    assert_near(&Vec3::new(0.0, 0.0, -1.0), &rec.n);
+}
+
+#[test]
+fn test_steps_applied_in_order() {
+    // stretched along z, then turned to lie along x, then moved
+    let object = Transform::new().scale(1.0, 1.0, 2.0).rotate_y(PI / 2.0).translate(1.0, 2.0, 3.0).apply(unit_sphere()).unwrap();
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let r = Ray::new(Point3::new(1.0, 2.0, -10.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+    let rec = (object.intersect)(&object, &mut rng, &r, 0.001, f64::INFINITY).unwrap();
+
+    assert!((rec.t - 12.0).abs() < 1e-9, "hit at {}", rec.t);
+    assert_near(&Vec3::new(0.0, 0.0, -1.0), &rec.n);
+}
+
+#[test]
+fn test_singular_transform_rejected() {
+    assert_eq!(Some(SingularTransform), Transform::new().scale(1.0, 0.0, 1.0).apply(unit_sphere()).err());
+    assert_eq!(Some(SingularTransform), Transform::new().rotate(&Vec3::zero(), 1.0).apply(unit_sphere()).err());
+    assert_eq!(Some(SingularTransform), Transform::from_matrix(Matrix4::zeros()).apply(unit_sphere()).err());
+
+    // looking straight along up leaves no sideways axis
+    let look_up = Transform::new().look_at(&Point3::zero(), &Point3::new(0.0, 1.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+    assert!(look_up.apply(unit_sphere()).is_err());
+}
+
+#[test]
+fn test_axis_angle_rotation() {
+    let theta = 0.7;
+    assert_matrix_near(Transform::new().rotate_x(theta).matrix(), Transform::new().rotate(&Vec3::new(2.0, 0.0, 0.0), theta).matrix());
+    assert_matrix_near(Transform::new().rotate_y(theta).matrix(), Transform::new().rotate(&Vec3::new(0.0, 1.0, 0.0), theta).matrix());
+    assert_matrix_near(Transform::new().rotate_z(theta).matrix(), Transform::new().rotate(&Vec3::new(0.0, 0.0, 1.0), theta).matrix());
+
+    // a third of a turn about the diagonal cycles the axes
+    let turn = Transform::new().rotate(&Vec3::from_value(1.0), 2.0 * PI / 3.0);
+    assert_near(&Vec3::new(0.0, 1.0, 0.0), &transform_point(&turn, &Vec3::new(1.0, 0.0, 0.0)));
+}
+
+#[test]
+fn test_euler_rotation_order() {
+    let (x, y, z) = (0.3, -1.1, 2.0);
+    let chained = Transform::new().rotate_x(x).rotate_y(y).rotate_z(z);
+
+    assert_eq!(chained, Transform::new().rotate_euler(x, y, z));
+    assert_ne!(chained, Transform::new().rotate_z(z).rotate_y(y).rotate_x(x));
+}
+
+#[test]
+fn test_look_at() {
+    let from = Point3::new(1.0, 2.0, 3.0);
+    let at = Point3::new(1.0, 2.0, 10.0);
+    let look_at = Transform::new().look_at(&from, &at, &Vec3::new(0.0, 1.0, 0.0));
+
+    assert_near(&from, &transform_point(&look_at, &Point3::zero()));
+    assert_near(&Point3::new(1.0, 2.0, 5.0), &transform_point(&look_at, &Point3::new(0.0, 0.0, 2.0)));
+    assert_near(&Point3::new(1.0, 3.0, 3.0), &transform_point(&look_at, &Point3::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn test_then_and_inverse() {
+    let a = Transform::new().scale(1.0, 2.0, 3.0).rotate_z(0.5);
+    let b = Transform::new().translate(4.0, 5.0, 6.0);
+
+    assert_eq!(a.translate(4.0, 5.0, 6.0), a.then(b));
+    assert_matrix_near(Matrix4::identity(), a.then(a.inverse().unwrap()).matrix());
+}
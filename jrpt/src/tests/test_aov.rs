@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use image::Rgb;
+use crate::{
+    renderer::Renderer,
+    aov::Aov,
+    film::Film,
+    scene::Scene,
+    camera::Camera,
+    objects::{Object, object_list, sphere, bvh},
+    materials::{lambertian::Lambertian, metal::Metal, dialetric::Dialetric},
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const SIZE: u32 = 41;
+
+/// Lambertian sphere straight ahead of the camera, with glass and metal spheres to its right and left
+fn scene() -> Scene {
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, sphere::new(Point3::new(-4.0, 0.0, 6.0), 1.0, Arc::new(Dialetric::new(1.5))));
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, 0.0, 6.0), 2.0, Arc::new(Lambertian::new(Colour::new(0.2, 0.4, 0.6)))));
+    object_list::add(&mut objects, sphere::new(Point3::new(4.0, 0.0, 6.0), 1.0, Arc::new(Metal::new(Colour::new(0.9, 0.8, 0.7), 0.0))));
+
+    let camera = Camera::new(Point3::zero(), Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    Scene::new(camera, objects, Colour::new(0.7, 0.8, 1.0))
+}
+
+fn render(aovs: &[Aov]) -> Film {
+    render_scene(&scene(), aovs)
+}
+
+fn render_scene(scene: &Scene, aovs: &[Aov]) -> Film {
+    let mut renderer = Renderer::new(4, 8, false);
+    renderer.set_aovs(aovs);
+
+    renderer.render_progressive(scene, SIZE, SIZE, 4, |_| true)
+}
+
+fn assert_close(Rgb([r, g, b]): Rgb<f32>, expected: [f32; 3]) {
+    for (v, e) in [r, g, b].into_iter().zip(expected) {
+        assert!((v - e).abs() < 0.05, "{:?} is not {:?}", [r, g, b], expected);
+    }
+}
+
+#[test]
+fn test_aovs_of_first_hit() {
+    let film = render(&Aov::ALL);
+    let centre = SIZE / 2;
+
+    // the centre pixel sees the front of the middle sphere, centred at (0,0,6) with radius 2
+    let Rgb([x, y, z]) = *film.aov_image(Aov::Position).unwrap().get_pixel(centre, centre);
+    let (x, y, z) = (x as f64, y as f64, z as f64);
+    assert!(z < 4.1);
+
+    let distance = (x * x + y * y + z * z).sqrt() as f32;
+    let normal = [x / 2.0, y / 2.0, (z - 6.0) / 2.0].map(|v| v as f32);
+
+    assert_close(*film.aov_image(Aov::Depth).unwrap().get_pixel(centre, centre), [distance; 3]);
+    assert_close(*film.aov_image(Aov::Normal).unwrap().get_pixel(centre, centre), normal);
+    assert_close(*film.aov_image(Aov::Albedo).unwrap().get_pixel(centre, centre), [0.2, 0.4, 0.6]);
+    assert_eq!(*film.aov_image(Aov::ObjectId).unwrap().get_pixel(centre, centre), Rgb([2.0, 2.0, 2.0]));
+
+    // glass is white, metal keeps its colour
+    assert_close(*film.aov_image(Aov::Albedo).unwrap().get_pixel(SIZE - 8, centre), [1.0, 1.0, 1.0]);
+    assert_close(*film.aov_image(Aov::Albedo).unwrap().get_pixel(7, centre), [0.9, 0.8, 0.7]);
+
+    // nothing hit in the corners
+    for aov in Aov::ALL {
+        assert_eq!(*film.aov_image(aov).unwrap().get_pixel(0, 0), Rgb([0.0, 0.0, 0.0]));
+    }
+}
+
+#[test]
+fn test_object_ids_are_not_averaged() {
+    let film = render(&[Aov::ObjectId]);
+    let ids = film.aov_image(Aov::ObjectId).unwrap();
+
+    for pixel in ids.pixels() {
+        assert!([0.0, 1.0, 2.0, 3.0].contains(&pixel.0[0]), "{:?} is not an id", pixel);
+    }
+}
+
+#[test]
+fn test_aovs_are_selected_per_render() {
+    let film = render(&[Aov::Normal, Aov::Uv]);
+
+    assert_eq!(film.aovs(), vec![Aov::Normal, Aov::Uv]);
+    assert!(film.aov_image(Aov::Depth).is_none());
+
+    // keeping AOVs does not change the image
+    assert_eq!(film.to_hdr_image(), render(&[]).to_hdr_image());
+}
+
+/// Red and blue spheres, with a group of two in the middle, in lists or BVHs built with seed
+fn id_scene(bvh_seed: Option<u64>) -> Scene {
+    let red: Arc<Lambertian> = Arc::new(Lambertian::new(Colour::new(0.8, 0.1, 0.1)));
+    let blue: Arc<Lambertian> = Arc::new(Lambertian::new(Colour::new(0.1, 0.1, 0.8)));
+    let build = |list: Object| match bvh_seed {
+        Some(seed) => bvh::with_seed(list, 0.0..0.0, seed),
+        None => list
+    };
+
+    let mut group = object_list::new();
+    object_list::add(&mut group, sphere::new(Point3::new(0.0, 0.0, 6.0), 2.0, blue.clone()));
+    object_list::add(&mut group, sphere::new(Point3::new(0.0, 3.0, 6.0), 1.0, red.clone()));
+
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, sphere::new(Point3::new(-4.0, 0.0, 6.0), 1.0, red));
+    object_list::add(&mut objects, build(group));
+    object_list::add(&mut objects, sphere::new(Point3::new(4.0, 0.0, 6.0), 1.0, blue));
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, -3.0, 6.0), 0.5, Arc::new(Dialetric::new(1.5))));
+
+    let camera = Camera::new(Point3::zero(), Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    Scene::new(camera, build(objects), Colour::new(0.7, 0.8, 1.0))
+}
+
+#[test]
+fn test_ids_do_not_depend_on_bvh() {
+    let aovs = [Aov::ObjectId, Aov::MaterialId];
+    let film = render_scene(&id_scene(None), &aovs);
+    let (object_ids, material_ids) = (film.aov_image(Aov::ObjectId).unwrap(), film.aov_image(Aov::MaterialId).unwrap());
+    let centre = SIZE / 2;
+
+    // objects keep the id they were added to the scene with, the group counting as one object,
+    // and materials are numbered in the order of the objects using them
+    for (i, j, object_id, material_id) in [(SIZE - 8, centre, 1.0, 1.0), (centre, centre, 2.0, 2.0), (7, centre, 3.0, 2.0), (0, 0, 0.0, 0.0)] {
+        assert_eq!(*object_ids.get_pixel(i, j), Rgb([object_id; 3]));
+        assert_eq!(*material_ids.get_pixel(i, j), Rgb([material_id; 3]));
+    }
+
+    for seed in 0..4 {
+        let bvh_film = render_scene(&id_scene(Some(seed)), &aovs);
+
+        for aov in aovs {
+            assert_eq!(film.aov_image(aov), bvh_film.aov_image(aov));
+        }
+    }
+}
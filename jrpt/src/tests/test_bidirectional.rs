@@ -0,0 +1,70 @@
+use std::sync::Arc;
+use crate::{
+    renderer::{Renderer, Integrator},
+    scene::Scene,
+    camera::Camera,
+    objects::{Object, object_list, sphere, aa_rectangles::{xz_rect, xy_rect}},
+    materials::{Material, lambertian::Lambertian, diffuse_light::DiffuseLight},
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+fn diffuse(col: Colour) -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(col))
+}
+
+/// Diffuse floor, back wall and sphere lit by the given light
+fn diffuse_scene(light: Object, light_copy: Object) -> Scene {
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, xz_rect::new(-5.0, 5.0, -5.0, 5.0, 0.0, diffuse(Colour::from_value(0.6))));
+    object_list::add(&mut objects, xy_rect::new(-5.0, 5.0, 0.0, 5.0, 3.0, diffuse(Colour::new(0.3, 0.6, 0.3))));
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, diffuse(Colour::new(0.7, 0.3, 0.3))));
+    object_list::add(&mut objects, light);
+
+    let camera = Camera::new(Point3::new(0.0, 2.0, -6.0), Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    let mut scene = Scene::new(camera, objects, Colour::zero());
+    scene.add_light(light_copy);
+    scene
+}
+
+/// Average colour of the image rendered with the given integrator
+fn mean_radiance(scene: &Scene, integrator: Integrator) -> Colour {
+    let size = 12;
+    let mut renderer = Renderer::new(96, 32, true);
+    renderer.set_integrator(integrator);
+    renderer.set_seed(3);
+
+    let film = renderer.render_progressive(scene, size, size, 96, |_| true);
+
+    let mut sum = Colour::zero();
+    for j in 0..size {
+        for i in 0..size {
+            sum += film.get_pixel(i, j);
+        }
+    }
+
+    sum / (size * size) as f64
+}
+
+fn assert_integrators_agree(scene: &Scene) {
+    let unidirectional = mean_radiance(scene, Integrator::PathTracer);
+    let bidirectional = mean_radiance(scene, Integrator::Bidirectional);
+
+    for c in 0..3 {
+        let relative = (unidirectional[c] - bidirectional[c]).abs() / unidirectional[c];
+        assert!(relative < 0.02, "path tracer {} and bidirectional {} differ", unidirectional, bidirectional);
+    }
+}
+
+#[test]
+fn test_bidirectional_matches_path_tracer_rect_light() {
+    let light = || xz_rect::new(-1.0, 1.0, -1.0, 1.0, 3.5, Arc::new(DiffuseLight::new(Colour::from_value(4.0))));
+    assert_integrators_agree(&diffuse_scene(light(), light()));
+}
+
+#[test]
+fn test_bidirectional_matches_path_tracer_sphere_light() {
+    let light = || sphere::new(Point3::new(2.0, 3.0, 0.5), 0.5, Arc::new(DiffuseLight::new(Colour::from_value(6.0))));
+    assert_integrators_agree(&diffuse_scene(light(), light()));
+}
@@ -0,0 +1,235 @@
+use std::{io, sync::Arc};
+use crate::{
+    renderer::{Renderer, Integrator, PhotonMapping, Partition},
+    filter::{Filter, FilterKind},
+    checkpoint::{self, Checkpoint},
+    aov::Aov,
+    film::Film,
+    scene::Scene,
+    camera::Camera,
+    objects::{object_list, sphere, aa_rectangles::xz_rect},
+    materials::{lambertian::Lambertian, dialetric::Dialetric, diffuse_light::DiffuseLight},
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const SIZE: u32 = 12;
+const SAMPLES_PER_PASS: u32 = 2;
+
+/// Glass and diffuse spheres on a floor under a rectangular light
+fn scene() -> Scene {
+    let light = || xz_rect::new(-1.0, 1.0, 5.0, 7.0, 3.0, Arc::new(DiffuseLight::new(Colour::from_value(4.0))));
+
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, sphere::new(Point3::new(-1.0, 0.0, 6.0), 1.0, Arc::new(Dialetric::new(1.5))));
+    object_list::add(&mut objects, sphere::new(Point3::new(1.2, 0.0, 6.0), 1.0, Arc::new(Lambertian::new(Colour::new(0.2, 0.4, 0.6)))));
+    object_list::add(&mut objects, xz_rect::new(-10.0, 10.0, 0.0, 20.0, -1.0, Arc::new(Lambertian::new(Colour::from_value(0.5)))));
+    object_list::add(&mut objects, light());
+
+    let camera = Camera::new(Point3::new(0.0, 1.0, 0.0), Point3::new(0.0, 0.0, 6.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    let mut scene = Scene::new(camera, objects, Colour::new(0.1, 0.1, 0.2));
+    scene.add_light(light());
+    scene
+}
+
+fn renderer(num_samples: u32) -> Renderer {
+    let mut renderer = Renderer::new(num_samples, 8, true);
+    renderer.set_seed(9);
+    renderer.set_aovs(&[Aov::Albedo]);
+    renderer
+}
+
+/// Renders until stop_at samples, then round trips the film through a checkpoint
+fn checkpoint_at(renderer: &Renderer, scene: &Scene, stop_at: u32) -> Checkpoint {
+    let film = renderer.render_progressive(scene, SIZE, SIZE, SAMPLES_PER_PASS, |film| film.samples() < stop_at);
+
+    let mut bytes = vec![];
+    checkpoint::write(&mut bytes, &film, 9, SAMPLES_PER_PASS, None).unwrap();
+    checkpoint::read(&mut bytes.as_slice()).unwrap()
+}
+
+fn assert_films_equal(a: &Film, b: &Film) {
+    assert_eq!(a.samples(), b.samples());
+    assert_eq!(a.to_hdr_image(), b.to_hdr_image());
+    assert_eq!(a.aov_image(Aov::Albedo), b.aov_image(Aov::Albedo));
+
+    for j in 0..SIZE {
+        for i in 0..SIZE {
+            assert_eq!(a.pixel_samples(i, j), b.pixel_samples(i, j));
+            assert_eq!(a.relative_error(i, j).to_bits(), b.relative_error(i, j).to_bits());
+        }
+    }
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    let renderer = renderer(4);
+    let scene = scene();
+
+    let film = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let checkpoint = checkpoint_at(&renderer, &scene, 4);
+
+    assert_eq!(checkpoint.seed, 9);
+    assert_eq!(checkpoint.samples_per_pass, SAMPLES_PER_PASS);
+    assert_films_equal(&film, &checkpoint.film);
+}
+
+#[test]
+fn test_resume_matches_uninterrupted_render() {
+    let scene = scene();
+    let mut renderer = renderer(8);
+
+    let uninterrupted = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let resumed = renderer.resume(&scene, checkpoint_at(&renderer, &scene, 4), |_| true).unwrap();
+    assert_films_equal(&uninterrupted, &resumed);
+
+    // photon mapping narrows its radius every pass, so the pass count has to carry over too
+    renderer.set_integrator(Integrator::PhotonMapping(PhotonMapping { photons: 2000, radius: 0.3, alpha: Some(0.7) }));
+
+    let uninterrupted = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let resumed = renderer.resume(&scene, checkpoint_at(&renderer, &scene, 4), |_| true).unwrap();
+    assert_films_equal(&uninterrupted, &resumed);
+}
+
+#[test]
+fn test_resume_adds_more_samples() {
+    let scene = scene();
+    let checkpoint = checkpoint_at(&renderer(4), &scene, 4);
+    assert_eq!(checkpoint.film.samples(), 4);
+
+    // the first four samples are the same ones a longer render would take
+    let film = renderer(10).resume(&scene, checkpoint, |_| true).unwrap();
+    let expected = renderer(10).render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+
+    assert_films_equal(&expected, &film);
+}
+
+#[test]
+fn test_resume_rejects_mismatched_settings() {
+    let scene = scene();
+    let checkpoint = || checkpoint_at(&renderer(4), &scene, 2);
+
+    let mut other_seed = renderer(4);
+    other_seed.set_seed(10);
+    assert!(other_seed.resume(&scene, checkpoint(), |_| true).is_err());
+
+    let mut other_aovs = renderer(4);
+    other_aovs.set_aovs(&[]);
+    assert!(other_aovs.resume(&scene, checkpoint(), |_| true).is_err());
+}
+
+#[test]
+fn test_read_rejects_invalid_checkpoints() {
+    let film = Film::new(2, 2);
+    let mut bytes = vec![];
+    checkpoint::write(&mut bytes, &film, 0, 1, None).unwrap();
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert!(checkpoint::read(&mut wrong_magic.as_slice()).is_err());
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(checkpoint::read(&mut &truncated[..]).is_err());
+
+    // sizes too large to allocate, whether or not width times height fits in a u32
+    for (width, height) in [(u32::MAX, u32::MAX), (1 << 15, 1 << 15)] {
+        let mut huge = bytes.clone();
+        huge[36..40].copy_from_slice(&width.to_le_bytes());
+        huge[40..44].copy_from_slice(&height.to_le_bytes());
+
+        let err = checkpoint::read(&mut huge.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+/// Renders each worker's share of the render with renderer, then merges their checkpoints
+fn render_partitioned(renderer: &mut Renderer, scene: &Scene, partitions: &[Partition]) -> io::Result<Checkpoint> {
+    let mut partials = vec![];
+
+    for &partition in partitions {
+        renderer.set_partition(Some(partition));
+        let film = renderer.render_progressive(scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+
+        let mut bytes = vec![];
+        checkpoint::write(&mut bytes, &film, 9, SAMPLES_PER_PASS, Some(partition)).unwrap();
+        partials.push(checkpoint::read(&mut bytes.as_slice()).unwrap());
+    }
+
+    renderer.set_partition(None);
+    checkpoint::merge(partials)
+}
+
+/// Films holding the same samples, summed in a different order
+fn assert_films_close(a: &Film, b: &Film) {
+    assert_eq!(a.samples(), b.samples());
+
+    for j in 0..SIZE {
+        for i in 0..SIZE {
+            assert_eq!(a.pixel_samples(i, j), b.pixel_samples(i, j));
+
+            let (p, q) = (a.get_pixel(i, j), b.get_pixel(i, j));
+            for c in 0..3 {
+                assert!((p[c] - q[c]).abs() <= 1e-9 * p[c].abs().max(1.0), "{} is not {}", p, q);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_merged_samples_match_single_render() {
+    let scene = scene();
+    let mut renderer = renderer(9);
+
+    let expected = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let partitions = (0..3).map(|worker| Partition::Samples { worker, workers: 3 }).collect::<Vec<_>>();
+    let merged = render_partitioned(&mut renderer, &scene, &partitions).unwrap();
+
+    assert_eq!(merged.partition, None);
+    assert_films_close(&expected, &merged.film);
+    assert_eq!(expected.aov_image(Aov::Albedo), merged.film.aov_image(Aov::Albedo));
+}
+
+#[test]
+fn test_merged_photon_mapping_matches_single_render() {
+    let scene = scene();
+    let mut renderer = renderer(9);
+    renderer.set_integrator(Integrator::PhotonMapping(PhotonMapping { photons: 500, radius: 0.3, alpha: Some(0.7) }));
+
+    // workers start partway through a pass, their photons must still be those of the pass
+    let expected = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let partitions = (0..3).map(|worker| Partition::Samples { worker, workers: 3 }).collect::<Vec<_>>();
+    let merged = render_partitioned(&mut renderer, &scene, &partitions).unwrap();
+
+    assert_films_close(&expected, &merged.film);
+}
+
+#[test]
+fn test_merged_tiles_match_single_render() {
+    let scene = scene();
+    let mut renderer = renderer(4);
+    renderer.set_tile_size(5);
+
+    // samples near tile edges are shared with the tiles of other workers
+    renderer.set_filter(Filter::new(FilterKind::Mitchell, 2.0).unwrap());
+
+    let expected = renderer.render_progressive(&scene, SIZE, SIZE, SAMPLES_PER_PASS, |_| true);
+    let partitions = (0..2).map(|worker| Partition::Tiles { worker, workers: 2 }).collect::<Vec<_>>();
+    let merged = render_partitioned(&mut renderer, &scene, &partitions).unwrap();
+
+    assert_films_close(&expected, &merged.film);
+}
+
+#[test]
+fn test_merge_rejects_incomplete_partitions() {
+    let scene = scene();
+    let mut renderer = renderer(2);
+    let samples = |worker| Partition::Samples { worker, workers: 2 };
+    let tiles = |worker| Partition::Tiles { worker, workers: 2 };
+
+    assert!(render_partitioned(&mut renderer, &scene, &[samples(0)]).is_err());
+    assert!(render_partitioned(&mut renderer, &scene, &[samples(0), samples(0)]).is_err());
+    assert!(render_partitioned(&mut renderer, &scene, &[samples(0), tiles(1)]).is_err());
+    assert!(render_partitioned(&mut renderer, &scene, &[samples(0), Partition::Samples { worker: 1, workers: 3 }]).is_err());
+    assert!(checkpoint::merge(vec![]).is_err());
+}
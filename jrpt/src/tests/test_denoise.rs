@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use image::{ImageBuffer, Rgb, Rgb32FImage};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use crate::{
+    denoise::{Denoiser, Guides},
+    renderer::Renderer,
+    aov::Aov,
+    film::Film,
+    scene::Scene,
+    camera::Camera,
+    objects::{object_list, sphere, aa_rectangles::xz_rect},
+    materials::lambertian::Lambertian,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const SIZE: u32 = 48;
+
+fn constant(col: [f32; 3]) -> Rgb32FImage {
+    ImageBuffer::from_pixel(SIZE, SIZE, Rgb(col))
+}
+
+/// Mean squared error with values clipped to [0,1] as on a display, so it is not dominated by bright pixels
+fn mean_squared_error(a: &Rgb32FImage, b: &Rgb32FImage) -> f64 {
+    let sum: f64 = a.pixels().zip(b.pixels())
+        .map(|(p, q)| (0..3).map(|c| ((p.0[c].min(1.0) - q.0[c].min(1.0)) as f64).powi(2)).sum::<f64>())
+        .sum();
+
+    sum / (a.width() * a.height()) as f64
+}
+
+#[test]
+fn test_denoise_keeps_constant_image() {
+    let image = constant([0.3, 0.5, 0.7]);
+    let (albedo, normal, depth) = (constant([0.5, 0.5, 0.5]), constant([0.0, 0.0, -1.0]), constant([2.0, 2.0, 2.0]));
+
+    let denoised = Denoiser::default().denoise(&image, &Guides { albedo: &albedo, normal: &normal, depth: &depth });
+
+    for (p, q) in denoised.pixels().zip(image.pixels()) {
+        for c in 0..3 {
+            assert!((p.0[c] - q.0[c]).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn test_denoise_keeps_edges() {
+    // two walls meeting down the middle of the image, one bright and one dark, with noise on both
+    let mut rng = SmallRng::seed_from_u64(0);
+    let wall = |i: u32| if i < SIZE / 2 { 1.0 } else { 0.2 };
+
+    let clean: Rgb32FImage = ImageBuffer::from_fn(SIZE, SIZE, |i, _| Rgb([wall(i); 3]));
+    let noisy: Rgb32FImage = ImageBuffer::from_fn(SIZE, SIZE, |i, _| Rgb([wall(i) * rng.gen_range(0.5..1.5); 3]));
+    let normal: Rgb32FImage = ImageBuffer::from_fn(SIZE, SIZE, |i, _| if i < SIZE / 2 { Rgb([1.0, 0.0, 0.0]) } else { Rgb([0.0, 0.0, -1.0]) });
+    let (albedo, depth) = (constant([0.5, 0.5, 0.5]), constant([2.0, 2.0, 2.0]));
+
+    let denoised = Denoiser::default().denoise(&noisy, &Guides { albedo: &albedo, normal: &normal, depth: &depth });
+
+    assert!(mean_squared_error(&denoised, &clean) < 0.1 * mean_squared_error(&noisy, &clean));
+
+    // nothing bleeds across the edge
+    for j in 0..SIZE {
+        assert!(denoised.get_pixel(SIZE / 2 - 1, j).0[0] > 0.8);
+        assert!(denoised.get_pixel(SIZE / 2, j).0[0] < 0.3);
+    }
+}
+
+/// Spheres on a floor under the sky
+fn render(num_samples: u32, seed: u64) -> Film {
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, xz_rect::new(-10.0, 10.0, -10.0, 10.0, 0.0, Arc::new(Lambertian::new(Colour::from_value(0.7)))));
+    object_list::add(&mut objects, sphere::new(Point3::new(-1.0, 1.0, 0.0), 1.0, Arc::new(Lambertian::new(Colour::new(0.2, 0.6, 0.3)))));
+    object_list::add(&mut objects, sphere::new(Point3::new(1.2, 0.7, -0.5), 0.7, Arc::new(Lambertian::new(Colour::new(0.6, 0.2, 0.2)))));
+
+    let camera = Camera::new(Point3::new(0.0, 2.0, -5.0), Point3::new(0.0, 0.7, 0.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    let scene = Scene::new(camera, objects, Colour::new(0.7, 0.8, 1.0));
+
+    let mut renderer = Renderer::new(num_samples, 16, true);
+    renderer.set_seed(seed);
+    renderer.set_aovs(&[Aov::Albedo, Aov::Normal, Aov::Depth]);
+
+    renderer.render_progressive(&scene, SIZE, SIZE, num_samples, |_| true)
+}
+
+#[test]
+fn test_denoise_render() {
+    let reference = render(512, 1).to_hdr_image();
+    let film = render(8, 2);
+
+    let denoised = Denoiser::default().denoise_film(&film).unwrap();
+    assert!(mean_squared_error(&denoised, &reference) < 0.5 * mean_squared_error(&film.to_hdr_image(), &reference));
+
+    // guides are needed
+    assert!(Denoiser::default().denoise_film(&Film::new(SIZE, SIZE)).is_none());
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+use crate::{
+    renderer::{Renderer, TileOrder},
+    filter::{Filter, FilterKind, MAX_RADIUS},
+    scene::Scene,
+    camera::Camera,
+    objects::{object_list, aa_rectangles::xy_rect},
+    materials::diffuse_light::DiffuseLight,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const KINDS: [FilterKind; 5] = [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell, FilterKind::Lanczos];
+
+/// Camera looking down z at a white light covering the left half of the view, against a black background
+fn edge_scene() -> Scene {
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, xy_rect::new(0.0, 10.0, -10.0, 10.0, 5.0, Arc::new(DiffuseLight::new(Colour::from_value(1.0)))));
+
+    let camera = Camera::new(Point3::zero(), Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    Scene::new(camera, objects, Colour::zero())
+}
+
+fn render(scene: &Scene, filter: Filter, (width, height): (u32, u32), num_samples: u32) -> image::Rgb32FImage {
+    let mut renderer = Renderer::new(num_samples, 4, true);
+    renderer.set_filter(filter);
+
+    renderer.render(scene, height, width).0
+}
+
+#[test]
+fn test_filter_weights() {
+    for kind in KINDS {
+        let filter = Filter::new(kind, 2.0).unwrap();
+
+        assert!(filter.evaluate(0.0, 0.0) > 0.0, "{:?} has no weight at its centre", kind);
+        assert!(filter.evaluate(0.0, 0.0) >= filter.evaluate(0.5, 0.25), "{:?} is not highest at its centre", kind);
+        assert_eq!(filter.evaluate(0.3, -0.7), filter.evaluate(-0.3, 0.7), "{:?} is not symmetric", kind);
+
+        // nothing beyond the radius, and continuous at it apart from the box
+        assert_eq!(0.0, filter.evaluate(2.01, 0.0));
+        assert_eq!(0.0, filter.evaluate(0.0, -2.5));
+        if kind != FilterKind::Box {
+            assert!(filter.evaluate(1.999, 0.0).abs() < 0.01, "{:?} does not fall to zero at its radius", kind);
+        }
+    }
+
+    // only the sharpening filters go negative
+    assert!(Filter::new(FilterKind::Mitchell, 2.0).unwrap().evaluate(1.5, 0.0) < 0.0);
+    assert!(Filter::new(FilterKind::Lanczos, 2.0).unwrap().evaluate(1.5, 0.0) < 0.0);
+    assert!(Filter::new(FilterKind::Gaussian, 2.0).unwrap().evaluate(1.5, 0.0) > 0.0);
+}
+
+#[test]
+fn test_filter_margin() {
+    assert_eq!(0, Filter::default().margin());
+    assert_eq!(1, Filter::new(FilterKind::Tent, 1.0).unwrap().margin());
+    assert_eq!(1, Filter::new(FilterKind::Gaussian, 1.5).unwrap().margin());
+    assert_eq!(2, Filter::new(FilterKind::Lanczos, 2.0).unwrap().margin());
+}
+
+#[test]
+fn test_filter_radius_checked() {
+    for radius in [0.0, -1.0, f64::NAN, f64::INFINITY, MAX_RADIUS + 0.1, 1e300] {
+        assert!(Filter::new(FilterKind::Gaussian, radius).is_err(), "radius {} was accepted", radius);
+    }
+    assert_eq!(MAX_RADIUS as u32, Filter::new(FilterKind::Lanczos, MAX_RADIUS).unwrap().margin());
+
+    for kind in KINDS {
+        let filter = Filter::with_default_radius(kind);
+        assert_eq!((kind, kind.default_radius()), (filter.kind(), filter.radius()));
+        assert!(Filter::new(kind, kind.default_radius()).is_ok());
+    }
+}
+
+#[test]
+fn test_filters_keep_constant_image() {
+    let background = Colour::new(0.2, 0.4, 0.6);
+    let mut scene = edge_scene();
+    scene.objects = object_list::new();
+    scene.background_colour = background;
+
+    for kind in KINDS {
+        let image = render(&scene, Filter::new(kind, 2.0).unwrap(), (12, 10), 4);
+
+        // weights are normalised per pixel, including those at the image's edges
+        for pixel in image.pixels() {
+            for (v, e) in pixel.0.iter().zip([0.2, 0.4, 0.6]) {
+                assert!((*v - e).abs() < 1e-5, "{:?} changed {:?} to {:?}", kind, background, pixel);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_wide_filters_blur_edges() {
+    let scene = edge_scene();
+    let (width, height) = (20, 4);
+
+    let box_image = render(&scene, Filter::default(), (width, height), 64);
+    let tent_image = render(&scene, Filter::new(FilterKind::Tent, 2.0).unwrap(), (width, height), 64);
+
+    // find the edge in the box filtered image, where pixels go from lit to unlit
+    let row = |image: &image::Rgb32FImage| (0..width).map(|i| image.get_pixel(i, 1)[0]).collect::<Vec<f32>>();
+    let (box_row, tent_row) = (row(&box_image), row(&tent_image));
+    let edge = box_row.iter().position(|v| *v < 0.5).unwrap();
+
+    // the box filter only sees light within a pixel, the tent also takes it from the lit pixels beside
+    assert_eq!(1.0, box_row[0]);
+    assert_eq!(0.0, box_row[edge + 1]);
+    assert!(tent_row[edge + 1] > 0.01, "{:?}", tent_row);
+    assert!(tent_row[edge - 1] < 1.0, "{:?}", tent_row);
+    assert_eq!(1.0, tent_row[0]);
+    assert_eq!(0.0, tent_row[width as usize - 1]);
+}
+
+#[test]
+fn test_wide_filter_deterministic() {
+    let scene = edge_scene();
+
+    let render = |threads: usize, multithreading: bool| {
+        let mut renderer = Renderer::new(8, 4, multithreading);
+        renderer.set_filter(Filter::new(FilterKind::Mitchell, 2.0).unwrap());
+        renderer.set_tile_size(3);
+        renderer.set_tile_order(TileOrder::Hilbert);
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| renderer.render_progressive(&scene, 10, 14, 4, |_| true).to_hdr_image())
+    };
+
+    let expected = render(1, false);
+    assert_eq!(expected, render(4, true));
+    assert_eq!(expected, render(3, true));
+}
@@ -0,0 +1,113 @@
+use std::{sync::Arc, f64::consts::PI};
+use rand::{rngs::SmallRng, SeedableRng};
+use crate::{
+    objects::{Object, aa_rectangles::xz_rect, sphere, triangle, object_list},
+    materials::{Material, lambertian::Lambertian},
+    colour::Colour,
+    point3::Point3,
+    utils::fmax,
+    ray::Ray
+};
+
+static SAMPLES: u32 = 200000;
+
+fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Colour::from_value(0.5)))
+}
+
+/// Monte carlo estimate of the solid angle obj subtends from origin, using E[1/pdf]
+fn estimate_solid_angle(obj: &Object, origin: &Point3) -> f64 {
+    let mut rng = SmallRng::seed_from_u64(1);
+    let mut sum = 0.0;
+
+    for _ in 0..SAMPLES {
+        let dir = (obj.sample)(obj, &mut rng, origin, 0.0).unwrap();
+        let pdf = (obj.pdf_value)(obj, &mut rng, origin, &dir, 0.0);
+
+        assert!(pdf > 0.0, "sampled direction must hit the object");
+        sum += 1.0 / pdf;
+    }
+
+    sum / SAMPLES as f64
+}
+
+fn relative_error(a: f64, b: f64) -> f64 {
+    (a - b).abs() / fmax(a.abs(), b.abs())
+}
+
+#[test]
+fn test_rect_solid_angle() {
+    // rectangle of sides a, b centered above the origin at distance d
+    let (a, b, d) = (2.0, 1.0, 1.5);
+    let rect = xz_rect::new(-a / 2.0, a / 2.0, -b / 2.0, b / 2.0, d, material());
+
+    let expected = 4.0 * (a * b / ((a * a + 4.0 * d * d) * (b * b + 4.0 * d * d)).sqrt()).asin();
+    let estimate = estimate_solid_angle(&rect, &Point3::zero());
+
+    assert!(relative_error(expected, estimate) < 0.01);
+}
+
+#[test]
+fn test_sphere_solid_angle() {
+    let (radius, d) = (1.0, 3.0);
+    let s = sphere::new(Point3::new(0.0, 0.0, d), radius, material());
+
+    let expected = 2.0 * PI * (1.0 - (1.0 - radius * radius / (d * d)).sqrt());
+    let estimate = estimate_solid_angle(&s, &Point3::zero());
+
+    assert!(relative_error(expected, estimate) < 0.01);
+}
+
+#[test]
+fn test_triangle_solid_angle() {
+    // half of the rectangle from test_rect_solid_angle, split along its diagonal
+    let (a, b, d) = (2.0, 1.0, 1.5);
+    let p0 = Point3::new(-a / 2.0, d, -b / 2.0);
+    let p1 = Point3::new(a / 2.0, d, -b / 2.0);
+    let p2 = Point3::new(-a / 2.0, d, b / 2.0);
+    let p3 = Point3::new(a / 2.0, d, b / 2.0);
+
+    let mut list = object_list::new();
+    object_list::add(&mut list, triangle::new(p0, p1, p2, None, Some((0.0, 0.0)), material()));
+    object_list::add(&mut list, triangle::new(p3, p2, p1, None, Some((0.0, 0.0)), material()));
+
+    let expected = 4.0 * (a * b / ((a * a + 4.0 * d * d) * (b * b + 4.0 * d * d)).sqrt()).asin();
+    let estimate = estimate_solid_angle(&list, &Point3::zero());
+
+    assert!(relative_error(expected, estimate) < 0.01);
+}
+
+
+/// Monte carlo estimate of the surface area of obj, using E[1/pdf] over sampled points
+fn estimate_area(obj: &Object) -> f64 {
+    let mut rng = SmallRng::seed_from_u64(1);
+    let mut sum = 0.0;
+
+    for _ in 0..SAMPLES {
+        let (rec, pdf) = (obj.sample_point)(obj, &mut rng, 0.0).unwrap();
+
+        // sampled points lie on the surface, with the normal pointing out of it
+        let r = Ray::new(rec.p - rec.n, rec.n, 0.0);
+        let hit = (obj.intersect)(obj, &mut rng, &r, 0.5, 1.5).unwrap();
+        assert!((hit.p - rec.p).length() < 1e-6);
+
+        sum += 1.0 / pdf;
+    }
+
+    sum / SAMPLES as f64
+}
+
+#[test]
+fn test_sample_point_area() {
+    let rect = xz_rect::new(-1.0, 1.0, 0.0, 0.5, 2.0, material());
+    assert!(relative_error(1.0, estimate_area(&rect)) < 1e-9);
+
+    let s = sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, material());
+    assert!(relative_error(PI, estimate_area(&s)) < 1e-9);
+
+    // list of both, picking each half the time
+    let mut list = object_list::new();
+    object_list::add(&mut list, xz_rect::new(-1.0, 1.0, 0.0, 0.5, 2.0, material()));
+    object_list::add(&mut list, triangle::new(Point3::zero(), Point3::new(0.0, 1.0, 0.0), Point3::new(1.0, 0.0, 0.0), None, Some((0.0, 0.0)), material()));
+    assert!(relative_error(1.5, estimate_area(&list)) < 0.01);
+}
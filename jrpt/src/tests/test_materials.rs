@@ -0,0 +1,57 @@
+use std::{sync::Arc, f64::consts::PI};
+use rand::{rngs::SmallRng, SeedableRng};
+use crate::{
+    materials::{Material, lambertian::Lambertian, metal::Metal},
+    objects::Intersection,
+    random::random_unit_vector,
+    renderer::MisHeuristic,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3,
+    ray::Ray,
+    utils::equal
+};
+
+static SAMPLES: u32 = 200000;
+
+/// Monte carlo estimate of the integral of the material's pdf over all directions,
+/// for a ray arriving straight down onto a horizontal surface
+fn integrate_pdf(material: Arc<dyn Material>) -> f64 {
+    let mut rng = SmallRng::seed_from_u64(1);
+    let ray_in = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+    let rec = Intersection::new(1.0, Point3::zero(), Vec3::new(0.0, 1.0, 0.0), &material, 0.0, 0.0);
+
+    let mut sum = 0.0;
+    for _ in 0..SAMPLES {
+        let dir = random_unit_vector(&mut rng);
+        sum += material.pdf(&ray_in, &rec, &dir) * 4.0 * PI;
+    }
+
+    sum / SAMPLES as f64
+}
+
+#[test]
+fn test_lambertian_pdf() {
+    let estimate = integrate_pdf(Arc::new(Lambertian::new(Colour::from_value(0.5))));
+
+    assert!((estimate - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn test_metal_pdf() {
+    // with fuzz below 1 every scattered ray stays above the surface
+    let estimate = integrate_pdf(Arc::new(Metal::new(Colour::from_value(0.5), 0.8)));
+
+    assert!((estimate - 1.0).abs() < 0.02);
+}
+
+#[test]
+fn test_mis_weights() {
+    let (pdf_a, pdf_b) = (0.3, 1.7);
+
+    for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+        assert!(equal(heuristic.weight(pdf_a, pdf_b) + heuristic.weight(pdf_b, pdf_a), 1.0));
+        assert_eq!(0.0, heuristic.weight(0.0, pdf_b));
+        assert_eq!(1.0, heuristic.weight(pdf_a, 0.0));
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Cursor;
+use image::{ImageBuffer, ImageFormat, Rgb, Rgb32FImage, codecs::hdr::HdrDecoder};
+use crate::{
+    renderer::Tile,
+    output::{OutputFormat, crop, write_exr, write_hdr, write_pfm, write_png},
+    tone_mapping::ToneMapping
+};
+
+/// Small image with values above 1 and distinct rows so flips are caught
+fn test_image() -> Rgb32FImage {
+    ImageBuffer::from_fn(3, 2, |i, j| Rgb([i as f32 * 4.0, j as f32 + 0.25, 15.0]))
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(Some(OutputFormat::Png), OutputFormat::from_path("image.png"));
+    assert_eq!(Some(OutputFormat::Hdr), OutputFormat::from_path("out/image.hdr"));
+    assert_eq!(Some(OutputFormat::Pfm), OutputFormat::from_path("image.PFM"));
+    assert_eq!(Some(OutputFormat::Exr), OutputFormat::from_path("image.exr"));
+    assert_eq!(None, OutputFormat::from_path("image.jpg"));
+    assert_eq!(None, OutputFormat::from_path("image"));
+}
+
+#[test]
+fn test_format_from_extension() {
+    assert_eq!(Some(OutputFormat::Exr), OutputFormat::from_extension("EXR"));
+    assert_eq!(Some(OutputFormat::Hdr), OutputFormat::from_extension("hdr"));
+    assert_eq!(None, OutputFormat::from_extension("tiff"));
+}
+
+#[test]
+fn test_crop() {
+    let image = test_image();
+    let cropped = crop(&image, Tile { x0: 1, y0: 1, x1: 3, y1: 2 });
+
+    assert_eq!((2, 1), cropped.dimensions());
+    assert_eq!(image.get_pixel(1, 1), cropped.get_pixel(0, 0));
+    assert_eq!(image.get_pixel(2, 1), cropped.get_pixel(1, 0));
+}
+
+#[test]
+fn test_write_pfm() {
+    let image = test_image();
+    let mut bytes = vec![];
+    write_pfm(&image, &mut bytes).unwrap();
+
+    let header = b"PF\n3 2\n-1.0\n";
+    assert_eq!(header, &bytes[..header.len()]);
+
+    let data: Vec<f32> = bytes[header.len()..].chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+    assert_eq!(3 * 2 * 3, data.len());
+
+    // first row in the file is the bottom row of the image
+    assert_eq!(&image.get_pixel(0, 1).0, &data[0..3]);
+    assert_eq!(&image.get_pixel(2, 0).0, &data[15..18]);
+}
+
+#[test]
+fn test_write_exr_round_trip() {
+    let image = test_image();
+    let mut bytes = Cursor::new(vec![]);
+    write_exr(&image, &mut bytes).unwrap();
+
+    let read = image::load_from_memory_with_format(bytes.get_ref(), ImageFormat::OpenExr).unwrap().into_rgb32f();
+    assert_eq!(image, read);
+}
+
+#[test]
+fn test_write_hdr_round_trip() {
+    let image = test_image();
+    let mut bytes = vec![];
+    write_hdr(&image, &mut bytes).unwrap();
+
+    // rgbe keeps 8 bits of mantissa relative to the brightest channel
+    let read = HdrDecoder::new(bytes.as_slice()).unwrap().read_image_hdr().unwrap();
+    for (a, b) in image.pixels().zip(read.iter()) {
+        for c in 0..3 {
+            assert!((a[c] - b[c]).abs() <= 15.0 / 128.0);
+        }
+    }
+}
+
+#[test]
+fn test_write_png_clamps() {
+    let image = test_image();
+    let mut bytes = vec![];
+    write_png(&image, &ToneMapping::default(), &mut bytes).unwrap();
+
+    let read = image::load_from_memory_with_format(&bytes, ImageFormat::Png).unwrap().into_rgb8();
+    assert_eq!(255, read.get_pixel(2, 0)[0]);
+    assert_eq!(0, read.get_pixel(0, 0)[0]);
+}
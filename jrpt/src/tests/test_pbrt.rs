@@ -0,0 +1,308 @@
+use std::{fs, path::Path};
+use rand::{rngs::SmallRng, SeedableRng};
+use crate::{
+    pbrt::{self, PbrtScene},
+    ply,
+    scene_file::SceneFileError,
+    renderer::Integrator,
+    sampler::SamplerKind,
+    filter::FilterKind,
+    objects::{Object, AuxObjectData, object_list},
+    point3::Point3,
+    vec3::Vec3,
+    ray::Ray
+};
+
+fn parse(source: &str) -> PbrtScene {
+    pbrt::parse(source, Path::new("")).unwrap()
+}
+
+/// Parses source, returning the position and message of the error it should have
+fn error(source: &str) -> (usize, usize, String) {
+    match pbrt::parse(source, Path::new("")) {
+        Err(SceneFileError::Syntax { line, column, message }) => (line, column, message),
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("parsed an invalid scene")
+    }
+}
+
+/// Distance along the ray from origin in direction dir to the first thing it hits
+fn hit_distance(objects: &Object, origin: Point3, dir: Vec3) -> Option<f64> {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let r = Ray::new(origin, dir, 0.0);
+    (objects.intersect)(objects, &mut rng, &r, 0.001, f64::INFINITY).map(|rec| rec.t)
+}
+
+#[test]
+fn test_example_scene_loads() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../render/scenes/cornell_box.pbrt");
+    let imported = pbrt::load(path).unwrap();
+
+    assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+    assert_eq!((imported.settings.width, imported.settings.height, imported.settings.samples_per_pixel), (400, 400, 200));
+    assert!(!object_list::is_empty(&imported.scene.lights));
+
+    let mut renderer = imported.settings.renderer(true);
+    renderer.set_num_samples(2);
+    let (image, _) = renderer.render(&imported.scene, 8, 8);
+    assert!(image.pixels().any(|p| p.0.iter().any(|&c| c > 0.0)));
+}
+
+#[test]
+fn test_settings() {
+    let imported = parse(r#"
+        Film "rgb" "integer xresolution" [64] "integer yresolution" 32
+        Sampler "stratified" "integer xsamples" 2 "integer ysamples" 3
+        Integrator "bdpt" "integer maxdepth" [7]
+        PixelFilter "mitchell" "float radius" [1.5]
+    "#);
+    let settings = imported.settings;
+
+    assert_eq!((settings.width, settings.height), (64, 32));
+    assert_eq!(settings.samples_per_pixel, 6);
+    assert_eq!(settings.sampler, SamplerKind::Stratified);
+    assert_eq!(settings.max_depth, 7);
+    assert!(matches!(settings.integrator, Integrator::Bidirectional));
+    assert_eq!((settings.filter.kind(), settings.filter.radius()), (FilterKind::Mitchell, 1.5));
+
+    // pbrt's own defaults otherwise
+    let settings = parse("").settings;
+    assert_eq!((settings.width, settings.height, settings.samples_per_pixel, settings.max_depth), (1280, 720, 16, 5));
+    assert_eq!((settings.filter.kind(), settings.filter.radius()), (FilterKind::Gaussian, 1.5));
+    let settings = parse("PixelFilter \"sinc\"").settings;
+    assert_eq!((settings.filter.kind(), settings.filter.radius()), (FilterKind::Lanczos, 4.0));
+
+    // the widest of pbrt's two radii
+    let settings = parse("PixelFilter \"triangle\" \"float radius\" [1 2.5]").settings;
+    assert_eq!(settings.filter.radius(), 2.5);
+}
+
+#[test]
+fn test_transforms_apply_innermost_first() {
+    let imported = parse(r#"
+        WorldBegin
+        AttributeBegin
+            Translate 0 0 10
+            Scale 2 2 2
+            Shape "sphere"
+        AttributeEnd
+        Shape "sphere" "float radius" 0.5
+    "#);
+    let objects = &imported.scene.objects;
+
+    // the sphere is scaled to a radius of 2 before it is moved, and the second one is not moved
+    assert!((hit_distance(objects, Point3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, 1.0)).unwrap() - 5.0).abs() < 1e-9);
+    assert!((hit_distance(objects, Point3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0)).unwrap() - 2.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_camera_matches_pbrt() {
+    let imported = parse(r#"
+        LookAt 0 0 -5  0 0 0  0 1 0
+        Camera "perspective" "float fov" 60
+        Film "rgb" "integer xresolution" 100 "integer yresolution" 100
+    "#);
+    let mut rng = SmallRng::seed_from_u64(0);
+
+    let centre = imported.scene.camera.get_ray(&mut rng, 0.5, 0.5);
+    assert!((centre.dir.normalized() - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+    // pbrt puts +x on the right and +y at the top
+    let right = imported.scene.camera.get_ray(&mut rng, 1.0, 0.5);
+    let top = imported.scene.camera.get_ray(&mut rng, 0.5, 1.0);
+    assert!(right.dir.x > 0.0);
+    assert!(top.dir.y > 0.0);
+
+    // fov spans the image vertically
+    let angle = top.dir.y.atan2(top.dir.z).to_degrees();
+    assert!((angle - 30.0).abs() < 1e-6, "{}", angle);
+}
+
+#[test]
+fn test_lights() {
+    let imported = parse(r#"
+        WorldBegin
+        LightSource "infinite" "rgb L" [0.1 0.2 0.3]
+        LightSource "point" "rgb I" [1 1 1] "point3 from" [0 5 0]
+        AttributeBegin
+            AreaLightSource "diffuse" "rgb L" [4 4 4]
+            Shape "trianglemesh" "point3 P" [0 0 0  1 0 0  0 0 1]
+            Shape "sphere" "float radius" 0.5
+        AttributeEnd
+        Shape "sphere" "float radius" 0.25
+    "#);
+
+    assert_eq!(imported.scene.background_colour, Vec3::new(0.1, 0.2, 0.3));
+    assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+
+    // point light, triangle and the glowing sphere, but not the sphere after AttributeEnd
+    match &imported.scene.lights.aux {
+        AuxObjectData::ObjectList(lights) => assert_eq!(lights.objects.len(), 3),
+        _ => panic!("lights should be an object list")
+    }
+    assert!(hit_distance(&imported.scene.objects, Point3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_some());
+}
+
+#[test]
+fn test_unsupported_parts_warn() {
+    let imported = parse(r#"WorldBegin
+ColorSpace "srgb"
+Shape "disk" "float radius" 1
+Shape "sphere" "float radius" 1 "float zmax" 0.5
+Material "coateddiffuse" "rgb reflectance" [0.2 0.2 0.2]
+AttributeBegin
+    Material "interface"
+    Shape "sphere"
+AttributeEnd
+"#);
+    let warnings: Vec<(usize, usize)> = imported.warnings.iter().map(|w| (w.line, w.column)).collect();
+
+    assert_eq!(warnings, vec![(2, 1), (3, 1), (4, 33), (5, 1), (8, 5)], "{:?}", imported.warnings);
+    assert!(imported.warnings[2].message.contains("zmax"));
+}
+
+#[test]
+fn test_ignored_material_parameters_warn() {
+    let imported = parse(r#"WorldBegin
+Material "dielectric" "rgb reflectance" [0.2 0.2 0.2]
+Material "interface" "rgb reflectance" [0.2 0.2 0.2]
+Material "conductor" "rgb eta" [0.2 0.9 1.1]
+Material "conductor" "rgb eta" [0.2 0.9 1.1] "rgb k" [3.9 2.4 2.2]
+"#);
+    let warnings: Vec<(usize, usize)> = imported.warnings.iter().map(|w| (w.line, w.column)).collect();
+
+    // an eta is no use without a k, the two together give the colour
+    assert_eq!(warnings, vec![(2, 23), (3, 22), (4, 22)], "{:?}", imported.warnings);
+    assert!(imported.warnings.iter().all(|w| w.message.contains("left out")));
+}
+
+#[test]
+fn test_include_cycles_rejected() {
+    let dir = std::env::temp_dir().join(format!("jrpt_test_pbrt_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.pbrt"), "Include \"b.pbrt\"\n").unwrap();
+    fs::write(dir.join("b.pbrt"), "Shape \"sphere\"\nInclude \"./a.pbrt\"\n").unwrap();
+    fs::write(dir.join("c.pbrt"), "Shape \"sphere\"\n").unwrap();
+
+    // the same file may be included more than once, as long as it does not end up including itself
+    assert!(pbrt::parse("Include \"c.pbrt\"\nInclude \"c.pbrt\"\n", &dir).is_ok());
+
+    match pbrt::parse("WorldBegin\nInclude \"a.pbrt\"\n", &dir) {
+        Err(SceneFileError::Syntax { line, column, message }) => {
+            assert_eq!((line, column), (2, 1));
+            assert!(message.contains("a.pbrt includes itself"), "{}", message);
+        },
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("parsed an include cycle")
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_error_positions() {
+    let (line, column, _) = error("WorldBegin\nShape \"sphere\" \"float radius\" [1\n");
+    assert_eq!((line, column), (2, 31));
+
+    let (line, column, message) = error("WorldBegin\n  Translate 1 x 2\n");
+    assert_eq!((line, column), (2, 15));
+    assert!(message.contains('x'), "{}", message);
+
+    assert_eq!(error("AttributeEnd").0, 1);
+    assert_eq!(error("Shape \"sphere\" \"float radius\" 1 \"oops").1, 33);
+    assert_eq!(error("NamedMaterial \"gold\"").2, "no material named gold");
+    assert_eq!(error("Shape \"trianglemesh\" \"integer indices\" [0 1 3] \"point3 P\" [0 0 0 1 0 0 0 1 0]").1, 1);
+    assert_eq!(error("Shape \"trianglemesh\" \"point3 P\" [0 0 0 1 0 0 0 1]").2, "trianglemesh P must come in threes");
+    for indices in ["0 -1 2", "0 1.5 2"] {
+        let source = format!("Shape \"trianglemesh\" \"integer indices\" [{}] \"point3 P\" [0 0 0 1 0 0 0 1 0]", indices);
+        assert!(error(&source).2.contains("non-negative"), "{}", indices);
+    }
+
+    // unusable filter radii are reported at the radius, even beside a usable one
+    for radii in ["0 2", "2 -1", "2 nan", "2 1e6"] {
+        let (line, column, message) = error(&format!("PixelFilter \"box\" \"float radius\" [{}]", radii));
+        assert_eq!(line, 1);
+        assert!(message.contains("radius"), "{}", message);
+        assert_eq!(column, if radii.starts_with('0') { 35 } else { 37 }, "{}", radii);
+    }
+
+    let missing = pbrt::load("no/such/scene.pbrt");
+    assert!(matches!(missing, Err(SceneFileError::Io(_))));
+}
+
+#[test]
+fn test_ply_ascii() {
+    let source = b"ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+4 0 1 2 3
+";
+    let mesh = ply::parse(source).unwrap();
+
+    assert_eq!(mesh.positions[2], Point3::new(1.0, 1.0, 0.0));
+    assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+}
+
+#[test]
+fn test_ply_binary() {
+    let mut bytes = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty double x\nproperty double y\nproperty double z\nproperty uchar red\nelement face 1\nproperty list uchar uint vertex_indices\nend_header\n".to_vec();
+    for p in [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 2.5, -1.0]] {
+        for v in p {
+            bytes.extend_from_slice(&f64::to_be_bytes(v));
+        }
+        bytes.push(255);
+    }
+    bytes.push(3);
+    for i in [0_u32, 2, 1] {
+        bytes.extend_from_slice(&i.to_be_bytes());
+    }
+
+    let mesh = ply::parse(&bytes).unwrap();
+    assert_eq!(mesh.positions[2], Point3::new(0.0, 2.5, -1.0));
+    assert_eq!(mesh.triangles, vec![[0, 2, 1]]);
+
+    // faces referring to vertices that are not there, and files cut short, are rejected
+    bytes.truncate(bytes.len() - 4);
+    bytes.extend_from_slice(&7_u32.to_be_bytes());
+    assert!(ply::parse(&bytes).is_err());
+    assert!(ply::parse(&bytes[..bytes.len() - 2]).is_err());
+
+    // as are lists claiming more values than there are
+    let mut long = bytes[..bytes.len() - 13].to_vec();
+    long.push(255);
+    long.extend_from_slice(&[0; 8]);
+    assert!(ply::parse(&long).is_err());
+}
+
+#[test]
+fn test_ply_rejects_bad_indices() {
+    let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar float vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n";
+
+    assert!(ply::parse(format!("{}3 0 1 2\n", header).as_bytes()).is_ok());
+    assert!(ply::parse(format!("{}3 0 -1 2\n", header).as_bytes()).is_err());
+    assert!(ply::parse(format!("{}3 0 1.5 2\n", header).as_bytes()).is_err());
+    assert!(ply::parse(format!("{}-3 0 1 2\n", header).as_bytes()).is_err());
+}
+
+#[test]
+fn test_ply_rejects_elements_without_properties() {
+    // would otherwise read a trillion vertices out of no data
+    for format in ["ascii", "binary_little_endian"] {
+        let source = format!("ply\nformat {} 1.0\nelement vertex 1000000000000\nend_header\n", format);
+        assert!(ply::parse(source.as_bytes()).is_err(), "{}", format);
+    }
+
+    // but empty elements are harmless
+    assert!(ply::parse(b"ply\nformat ascii 1.0\nelement vertex 0\nend_header\n").is_ok());
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use crate::{
+    renderer::{Renderer, Integrator, PhotonMapping},
+    photon_map::PhotonMap,
+    scene::Scene,
+    camera::Camera,
+    objects::{object_list, sphere, aa_rectangles::xz_rect},
+    materials::{Material, lambertian::Lambertian, dialetric::Dialetric, diffuse_light::DiffuseLight},
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+/// Glass sphere over a diffuse floor, lit by a rectangle above it
+fn caustic_scene(sphere_material: Arc<dyn Material>) -> Scene {
+    let light = || xz_rect::new(-1.5, 1.5, -1.5, 1.5, 4.0, Arc::new(DiffuseLight::new(Colour::from_value(4.0))));
+
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, xz_rect::new(-5.0, 5.0, -5.0, 5.0, 0.0, Arc::new(Lambertian::new(Colour::from_value(0.6)))));
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, 1.2, 0.0), 1.0, sphere_material));
+    object_list::add(&mut objects, light());
+
+    let camera = Camera::new(Point3::new(0.0, 3.0, -5.0), Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    let mut scene = Scene::new(camera, objects, Colour::zero());
+    scene.add_light(light());
+    scene
+}
+
+/// Average colour of the image rendered with the given integrator, in passes of samples_per_pass samples
+fn mean_radiance(scene: &Scene, integrator: Integrator, num_samples: u32, samples_per_pass: u32) -> Colour {
+    let size = 12;
+    let mut renderer = Renderer::new(num_samples, 32, true);
+    renderer.set_integrator(integrator);
+    renderer.set_seed(5);
+
+    let film = renderer.render_progressive(scene, size, size, samples_per_pass, |_| true);
+
+    let mut sum = Colour::zero();
+    for j in 0..size {
+        for i in 0..size {
+            sum += film.get_pixel(i, j);
+        }
+    }
+
+    sum / (size * size) as f64
+}
+
+#[test]
+fn test_photon_mapping_matches_path_tracer() {
+    let scene = caustic_scene(Arc::new(Dialetric::new(1.5)));
+    let photon_mapping = PhotonMapping { photons: 20000, radius: 0.1, alpha: Some(0.7) };
+
+    let path_traced = mean_radiance(&scene, Integrator::PathTracer, 4096, 4096);
+    let photon_mapped = mean_radiance(&scene, Integrator::PhotonMapping(photon_mapping), 512, 8);
+
+    // caustics are left out of the paths, so without photons they are missing
+    let without_photons = mean_radiance(&scene, Integrator::PhotonMapping(PhotonMapping { photons: 0, ..photon_mapping }), 512, 8);
+    assert!(without_photons.x < 0.9 * path_traced.x);
+
+    for c in 0..3 {
+        let relative = (path_traced[c] - photon_mapped[c]).abs() / path_traced[c];
+        assert!(relative < 0.02, "path tracer {} and photon mapping {} differ", path_traced, photon_mapped);
+    }
+}
+
+#[test]
+fn test_photon_map_stores_caustics_only() {
+    let glass = caustic_scene(Arc::new(Dialetric::new(1.5)));
+    assert!(!PhotonMap::build(&glass, 10000, (32, 3), 0.1, (0, false)).is_empty());
+
+    // light reaching the floor directly or off another diffuse surface is left to the path tracer
+    let diffuse = caustic_scene(Arc::new(Lambertian::new(Colour::from_value(0.5))));
+    assert!(PhotonMap::build(&diffuse, 10000, (32, 3), 0.1, (0, false)).is_empty());
+}
+
+#[test]
+fn test_progressive_radius() {
+    let fixed = PhotonMapping { photons: 1000, radius: 0.5, alpha: None };
+    assert_eq!(fixed.radius(0), 0.5);
+    assert_eq!(fixed.radius(100), 0.5);
+
+    let progressive = PhotonMapping { alpha: Some(0.7), ..fixed };
+    assert_eq!(progressive.radius(0), 0.5);
+
+    for pass in 0..100 {
+        assert!(progressive.radius(pass + 1) < progressive.radius(pass));
+    }
+
+    // the radius shrinks slowly enough for the photons gathered to keep growing
+    let photons_gathered = |pass: u32| (pass + 1) as f64 * progressive.radius(pass).powi(2);
+    assert!(photons_gathered(100) > photons_gathered(10));
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use image::Rgb32FImage;
+use crate::{
+    sampler::{Sampler, SamplerKind},
+    renderer::Renderer,
+    scene::Scene,
+    camera::Camera,
+    objects::{object_list, sphere},
+    materials::lambertian::Lambertian,
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const KINDS: [SamplerKind; 4] = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+/// Returns the values handed out by the samplers of the given pixel's first n samples,
+/// taking dimensions with f after skipping the first skip pairs of dimensions
+fn samples<T>(kind: SamplerKind, pixel: u64, n: u32, skip: u32, f: impl Fn(&mut dyn Sampler) -> T) -> Vec<T> {
+    (0..n).map(|index| {
+        let sampler = &mut *kind.create(3, pixel, index, n);
+        for _ in 0..skip {
+            sampler.get_2d();
+        }
+
+        f(sampler)
+    }).collect()
+}
+
+/// Returns the number of values in each of the cells of an nx x ny grid
+fn cell_counts(values: &[(f64, f64)], nx: u32, ny: u32) -> Vec<u32> {
+    let mut counts = vec![0; (nx * ny) as usize];
+    for (u, v) in values {
+        counts[((v * ny as f64) as u32 * nx + (u * nx as f64) as u32) as usize] += 1;
+    }
+
+    counts
+}
+
+#[test]
+fn test_samples_in_unit_interval() {
+    for kind in KINDS {
+        // far past the dimensions halton has bases for
+        for values in samples(kind, 7, 50, 0, |sampler| (0..80).map(|_| sampler.get_1d()).collect::<Vec<f64>>()) {
+            assert!(values.iter().all(|v| (0.0..1.0).contains(v)), "{:?} gave {:?}", kind, values);
+        }
+    }
+}
+
+#[test]
+fn test_samples_deterministic() {
+    for kind in KINDS {
+        let a = samples(kind, 5, 8, 2, |sampler| sampler.get_2d());
+
+        assert_eq!(a, samples(kind, 5, 8, 2, |sampler| sampler.get_2d()));
+        assert_ne!(a, samples(kind, 6, 8, 2, |sampler| sampler.get_2d()), "{:?} is the same in every pixel", kind);
+    }
+}
+
+#[test]
+fn test_samples_stratified_1d() {
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        for pixel in 0..4 {
+            let values = samples(kind, pixel, 16, 0, |sampler| (sampler.get_1d(), 0.0));
+
+            assert_eq!(vec![1; 16], cell_counts(&values, 16, 1), "{:?} is not stratified", kind);
+        }
+    }
+}
+
+#[test]
+fn test_stratified_many_samples() {
+    // as many samples as a time budget plans for
+    for index in [0, 1 << 20, u32::MAX - 1] {
+        let sampler = &mut *SamplerKind::Stratified.create(3, 0, index, u32::MAX);
+        let (u, v) = sampler.get_2d();
+
+        assert!([sampler.get_1d(), u, v].iter().all(|x| (0.0..1.0).contains(x)));
+    }
+}
+
+#[test]
+fn test_samples_stratified_2d() {
+    for pixel in 0..4 {
+        // later dimensions are spread out as well as the first
+        for skip in [0, 5] {
+            for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+                let values = samples(kind, pixel, 16, skip, |sampler| sampler.get_2d());
+                assert_eq!(vec![1; 16], cell_counts(&values, 4, 4), "{:?} is not stratified", kind);
+            }
+
+            // sobol points are spread out over every split of the square into 16 equal rectangles
+            let values = samples(SamplerKind::Sobol, pixel, 16, skip, |sampler| sampler.get_2d());
+            assert_eq!(vec![1; 16], cell_counts(&values, 16, 1));
+            assert_eq!(vec![1; 16], cell_counts(&values, 2, 8));
+        }
+
+        // halton's first two dimensions are in bases 2 and 3
+        let values = samples(SamplerKind::Halton, pixel, 6, 0, |sampler| sampler.get_2d());
+        assert_eq!(vec![1; 6], cell_counts(&values, 2, 3));
+    }
+}
+
+/// Diffuse sphere lit by a uniform sky
+fn sky_scene() -> Scene {
+    let mut objects = object_list::new();
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, 0.0, 4.0), 1.0, Arc::new(Lambertian::new(Colour::from_value(0.8)))));
+    object_list::add(&mut objects, sphere::new(Point3::new(0.0, -101.0, 4.0), 100.0, Arc::new(Lambertian::new(Colour::from_value(0.5)))));
+
+    let camera = Camera::new(Point3::zero(), Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    Scene::new(camera, objects, Colour::from_value(1.0))
+}
+
+fn render(scene: &Scene, kind: SamplerKind, num_samples: u32, seed: u64) -> Rgb32FImage {
+    let mut renderer = Renderer::new(num_samples, 4, true);
+    renderer.set_roulette_depth(4);
+    renderer.set_sampler(kind);
+    renderer.set_seed(seed);
+
+    renderer.render(scene, 16, 16).0
+}
+
+fn mse(a: &Rgb32FImage, b: &Rgb32FImage) -> f64 {
+    a.pixels().zip(b.pixels()).map(|(p, q)| ((p.0[1] - q.0[1]) as f64).powi(2)).sum::<f64>() / (a.width() * a.height()) as f64
+}
+
+#[test]
+fn test_samplers_converge_faster() {
+    let scene = sky_scene();
+    let reference = render(&scene, SamplerKind::Independent, 2048, 1);
+    let independent = mse(&render(&scene, SamplerKind::Independent, 16, 2), &reference);
+
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        let error = mse(&render(&scene, kind, 16, 2), &reference);
+
+        assert!(error < 0.8 * independent, "{:?} has error {} against {} for independent samples", kind, error, independent);
+    }
+}
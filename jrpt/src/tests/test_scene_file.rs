@@ -0,0 +1,199 @@
+use std::{fs, path::Path};
+use crate::{
+    scene_file::{self, SceneFileError},
+    renderer::{Integrator, PhotonMapping},
+    sampler::SamplerKind,
+    filter::FilterKind,
+    objects::object_list
+};
+
+/// Parses source, returning the position and message of the error it should have
+fn error(source: &str) -> (usize, usize, String) {
+    match scene_file::parse(source, Path::new("")) {
+        Err(SceneFileError::Syntax { line, column, message }) => (line, column, message),
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("parsed an invalid scene")
+    }
+}
+
+const CAMERA: &str = "[camera]\nlookfrom = [0, 0, 0]\nlookat = [0, 0, 1]\nvfov = 40\n";
+
+#[test]
+fn test_example_scene_loads() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../render/scenes/cornell_box.toml");
+    let file = scene_file::load(path).unwrap();
+
+    assert_eq!((file.settings.width, file.settings.height, file.settings.samples_per_pixel), (400, 400, 200));
+    assert!(!object_list::is_empty(&file.scene.lights));
+
+    // a few samples find the light through the box's opening
+    let mut renderer = file.settings.renderer(true);
+    renderer.set_num_samples(2);
+    let (image, _) = renderer.render(&file.scene, 8, 8);
+    assert!(image.pixels().any(|p| p.0.iter().any(|&c| c > 0.0)));
+}
+
+#[test]
+fn test_render_settings() {
+    let source = format!("{}{}", CAMERA, r#"
+[render]
+width = 30
+height = 20
+seed = 4
+sampler = "sobol"
+filter = { type = "mitchell", radius = 2.0 }
+integrator = { type = "photon_mapping", photons = 1000, radius = 0.5 }
+"#);
+    let settings = scene_file::parse(&source, Path::new("")).unwrap().settings;
+
+    assert_eq!((settings.width, settings.height, settings.seed), (30, 20, 4));
+    assert_eq!(settings.sampler, SamplerKind::Sobol);
+    assert_eq!((settings.filter.kind(), settings.filter.radius()), (FilterKind::Mitchell, 2.0));
+    assert!(matches!(settings.integrator, Integrator::PhotonMapping(PhotonMapping { photons: 1000, radius, alpha: None }) if radius == 0.5));
+}
+
+#[test]
+fn test_filter_radius() {
+    // each kind has its own default radius
+    for (name, kind) in [("box", FilterKind::Box), ("gaussian", FilterKind::Gaussian), ("lanczos", FilterKind::Lanczos)] {
+        let source = format!("{}[render]\nfilter = \"{}\"\n", CAMERA, name);
+        let filter = scene_file::parse(&source, Path::new("")).unwrap().settings.filter;
+
+        assert_eq!((filter.kind(), filter.radius()), (kind, kind.default_radius()));
+    }
+
+    // unusable radii are reported where the radius is
+    for radius in ["0", "-1", "nan", "inf", "1e6"] {
+        let (line, column, message) = error(&format!("{}[render]\nfilter = {{ type = \"tent\", radius = {} }}\n", CAMERA, radius));
+        assert_eq!((line, column), (6, 36), "{}", radius);
+        assert!(message.contains("radius"), "{}", message);
+    }
+}
+
+#[test]
+fn test_objects_and_references() {
+    // keys above the first table belong to the file rather than the camera
+    let source = format!("background = [0.5, 0.7, 1.0]\n{}{}", CAMERA, r#"
+[textures.checks]
+type = "checker"
+odd = [0, 0, 0]
+even = "white"
+
+[textures.white]
+type = "solid"
+colour = [1, 1, 1]
+
+[materials.ground]
+type = "lambertian"
+albedo = "checks"
+
+[materials.glass]
+type = "dielectric"
+ior = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0, -1000, 0]
+radius = 1000
+material = "ground"
+
+[[objects]]
+type = "group"
+bvh = true
+objects = [
+    { type = "sphere", center = [0, 1, 5], radius = 1, material = "glass" },
+    { type = "box", min = [-1, -1, -1], max = [1, 1, 1], material = "ground", transform = [{ scale = 0.5 }, { translate = [2, 0.5, 5] }] },
+]
+
+[[objects]]
+type = "constant_medium"
+boundary = { type = "sphere", center = [0, 1, 8], radius = 1 }
+density = 0.2
+albedo = [1, 1, 1]
+"#);
+    let file = scene_file::parse(&source, Path::new("")).unwrap();
+
+    assert_eq!(file.scene.background_colour.y, 0.7);
+    assert!(object_list::is_empty(&file.scene.lights));
+}
+
+#[test]
+fn test_syntax_error_position() {
+    let (line, column, _) = error("[camera]\nlookfrom = [0, 0,\n");
+    assert_eq!(line, 3);
+    assert_eq!(column, 1);
+
+    let (line, column, _) = error("[camera]\nvfov = = 40\n");
+    assert_eq!((line, column), (2, 8));
+}
+
+#[test]
+fn test_semantic_error_positions() {
+    // unknown keys are reported where the key is
+    let (line, column, message) = error(&format!("{}colour = [1, 1, 1]\n", CAMERA));
+    assert_eq!((line, column), (5, 1));
+    assert!(message.contains("colour"), "{}", message);
+
+    // a reference to a missing material is reported where the name is
+    let source = format!("{}[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 5]\nradius = 1\nmaterial = \"gold\"\n", CAMERA);
+    let (line, column, message) = error(&source);
+    assert_eq!((line, column), (9, 12));
+    assert!(message.contains("gold"), "{}", message);
+
+    // values of the wrong type are reported where the value is
+    let (line, column, _) = error("[camera]\nlookfrom = [0, 0]\nlookat = [0, 0, 1]\nvfov = 40\n");
+    assert_eq!((line, column), (2, 12));
+
+    // as are missing tables and keys, where the table is
+    assert_eq!(error("").0, 1);
+    assert_eq!(error("[camera]\nlookfrom = [0, 0, 0]\nvfov = 40\n").0, 1);
+}
+
+#[test]
+fn test_invalid_scenes_rejected() {
+    let invalid = [
+        "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 5]\nradius = 1\n",             // missing material
+        "[[objects]]\ntype = \"cone\"\n",
+        "[materials.a]\ntype = \"lambertian\"\nalbedo = \"nothing\"\n",                    // unused, but still checked
+        "[textures.a]\ntype = \"checker\"\nodd = \"a\"\neven = [0, 0, 0]\n",               // refers to itself
+        "[textures.a]\ntype = \"image\"\nfile = \"missing.png\"\n",
+        "[materials.m]\ntype = \"metal\"\nalbedo = [1, 1, 1]\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 5]\nradius = 1\nmaterial = \"m\"\ntransform = [{ scale = 0 }]\n",
+        "[render]\nwidth = 0\n",
+        "[render]\nintegrator = \"whitted\"\n",
+    ];
+
+    for source in invalid {
+        let source = format!("{}{}", CAMERA, source);
+        assert!(scene_file::parse(&source, Path::new("")).is_err(), "{}", source);
+    }
+
+    let missing = scene_file::load("no/such/scene.toml");
+    assert!(matches!(missing, Err(SceneFileError::Io(_))));
+}
+
+#[test]
+fn test_unreadable_files_rejected() {
+    let dir = std::env::temp_dir().join(format!("jrpt_test_scene_file_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    fs::write(dir.join("corrupt.png"), b"not a png").unwrap();
+    image::GrayImage::new(2, 2).save(dir.join("grey.png")).unwrap();
+    fs::write(dir.join("corrupt.obj"), "v 0 0 0\nf 1 2 3 4 5\nnonsense\n").unwrap();
+    fs::write(dir.join("bare.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+    // files that exist but can not be loaded are reported where their name is
+    let textures = ["corrupt.png", "grey.png"].map(|file| format!("[textures.t]\ntype = \"image\"\nfile = \"{}\"\n", file));
+    let meshes = ["corrupt.obj", "bare.obj"].map(|file| {
+        format!("[materials.m]\ntype = \"metal\"\nalbedo = [1, 1, 1]\n[[objects]]\ntype = \"mesh\"\nmaterial = \"m\"\nfile = \"{}\"\n", file)
+    });
+
+    for (source, line) in textures.iter().map(|source| (source, 7)).chain(meshes.iter().map(|source| (source, 11))) {
+        match scene_file::parse(&format!("{}{}", CAMERA, source), &dir) {
+            Err(SceneFileError::Syntax { line: l, column, .. }) => assert_eq!((l, column), (line, 8), "{}", source),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("parsed {}", source)
+        }
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use crate::{
+    renderer::Renderer,
+    scene::Scene,
+    camera::Camera,
+    stats::{self, RenderStats},
+    aov::Aov,
+    objects::{object_list, sphere, bvh, aa_rectangles::xz_rect},
+    materials::{lambertian::Lambertian, diffuse_light::DiffuseLight},
+    colour::Colour,
+    point3::Point3,
+    vec3::Vec3
+};
+
+const SIZE: u32 = 10;
+const SAMPLES: u32 = 4;
+
+/// Diffuse spheres in a BVH on a floor filling the view, lit by the sky and optionally a rectangular light
+fn scene(with_light: bool) -> Scene {
+    let mut objects = object_list::new();
+    for k in 0..5 {
+        let centre = Point3::new(k as f64 - 2.0, 0.0, 6.0);
+        object_list::add(&mut objects, sphere::new(centre, 0.6, Arc::new(Lambertian::new(Colour::new(0.8, 0.5, 0.3)))));
+    }
+    object_list::add(&mut objects, xz_rect::new(-50.0, 50.0, -50.0, 50.0, -0.6, Arc::new(Lambertian::new(Colour::from_value(0.5)))));
+
+    let light = || xz_rect::new(-1.0, 1.0, 5.0, 7.0, 3.0, Arc::new(DiffuseLight::new(Colour::from_value(4.0))));
+    if with_light {
+        object_list::add(&mut objects, light());
+    }
+
+    let camera = Camera::new(Point3::new(0.0, 3.0, 0.0), Point3::new(0.0, -0.6, 2.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0, 0.0, 1.0, 0.0..0.0);
+    let mut scene = Scene::new(camera, bvh::with_seed(objects, 0.0..0.0, 7), Colour::new(0.7, 0.8, 1.0));
+    if with_light {
+        scene.add_light(light());
+    }
+    scene
+}
+
+fn render(scene: &Scene, multithreading: bool) -> RenderStats {
+    let mut renderer = Renderer::new(SAMPLES, 8, multithreading);
+    renderer.set_seed(5);
+
+    renderer.render(scene, SIZE, SIZE).1
+}
+
+/// Stats with the timings left out, which change from run to run
+fn counts(stats: RenderStats) -> RenderStats {
+    RenderStats { bvh_build: Default::default(), photon_tracing: Default::default(), render: Default::default(), write_out: Default::default(), ..stats }
+}
+
+#[test]
+fn test_stats_count_camera_rays() {
+    let stats = render(&scene(true), true);
+
+    // one ray and one path per sample
+    assert_eq!(stats.primary_rays, (SIZE * SIZE * SAMPLES) as u64);
+    assert_eq!(stats.paths, stats.primary_rays);
+
+    assert!(stats.secondary_rays > 0);
+    assert!(stats.shadow_rays > 0);
+    assert!(stats.aabb_tests > 0);
+    assert!(stats.primitive_tests > 0);
+    assert!(stats.scatter_events > 0);
+    assert!(!stats.render.is_zero());
+}
+
+#[test]
+fn test_stats_no_shadow_rays_without_lights() {
+    let stats = render(&scene(false), true);
+
+    assert_eq!(stats.shadow_rays, 0);
+    assert!(stats.secondary_rays > 0);
+}
+
+#[test]
+fn test_stats_average_path_length() {
+    let stats = render(&scene(true), true);
+    let length = stats.average_path_length();
+
+    // every path from the camera hits the floor or a sphere, and depth caps the rest
+    assert!(length > 1.0 && length <= 8.0, "average path length {}", length);
+
+    // only rays that hit something scatter, and paths ended by russian roulette do not trace their scattered ray
+    assert!(stats.path_vertices >= stats.scatter_events);
+    assert!(stats.scatter_events >= stats.secondary_rays);
+}
+
+#[test]
+fn test_stats_independent_of_threading() {
+    let scene = scene(true);
+
+    assert_eq!(counts(render(&scene, false)), counts(render(&scene, true)));
+}
+
+#[test]
+fn test_stats_count_aov_rays_apart() {
+    let mut renderer = Renderer::new(SAMPLES, 8, true);
+    renderer.set_seed(5);
+    renderer.set_aovs(&[Aov::Normal]);
+
+    let stats = renderer.render(&scene(true), SIZE, SIZE).1;
+
+    // one more ray per sample finds the AOVs, leaving the camera's rays as they were
+    assert_eq!(stats.primary_rays, (SIZE * SIZE * SAMPLES) as u64);
+    assert_eq!(stats.aov_rays, stats.primary_rays);
+    assert_eq!(stats.rays(), stats.primary_rays + stats.aov_rays + stats.secondary_rays + stats.shadow_rays);
+}
+
+#[test]
+fn test_stats_time_bvh_builds() {
+    let before = stats::bvh_build_time();
+    scene(true);
+
+    assert!(stats::bvh_build_time() > before);
+}
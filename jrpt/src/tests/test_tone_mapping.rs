@@ -0,0 +1,93 @@
+use crate::{
+    tone_mapping::{ToneMapping, ToneMapOperator, srgb_oetf},
+    colour::Colour
+};
+
+const OPERATORS: [ToneMapOperator; 5] = [
+    ToneMapOperator::Clamp,
+    ToneMapOperator::Reinhard,
+    ToneMapOperator::ExtendedReinhard(4.0),
+    ToneMapOperator::Aces,
+    ToneMapOperator::Hable
+];
+
+fn map_grey(operator: ToneMapOperator, exposure: f64, v: f64) -> f64 {
+    ToneMapping::new(operator, exposure).map(Colour::from_value(v)).x
+}
+
+#[test]
+fn test_srgb_oetf() {
+    assert_eq!(0.0, srgb_oetf(0.0));
+    assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-12);
+    assert!((srgb_oetf(0.5) - 0.735357).abs() < 1e-6);
+    assert!((srgb_oetf(0.18) - 0.461356).abs() < 1e-6);
+
+    // linear and power segments meet at the threshold
+    assert!((srgb_oetf(0.0031308) - srgb_oetf(0.0031308 + 1e-12)).abs() < 1e-6);
+}
+
+#[test]
+fn test_to_rgb_uses_srgb() {
+    assert_eq!(188, Colour::from_value(0.5).to_rgb()[0]);
+    assert_eq!(255, Colour::from_value(15.0).to_rgb()[0]);
+    assert_eq!(0, Colour::from_value(-1.0).to_rgb()[0]);
+}
+
+#[test]
+fn test_operators_monotonic() {
+    for operator in OPERATORS {
+        assert!(map_grey(operator, 0.0, 0.0).abs() < 1e-3);
+
+        let mut last = -1.0;
+        for k in 0..200 {
+            let v = map_grey(operator, 0.0, k as f64 * 0.02);
+            assert!(v >= last);
+            last = v;
+        }
+
+        // values past the white point are clipped when encoding
+        assert_eq!(255, ToneMapping::new(operator, 0.0).to_rgb(Colour::from_value(1000.0))[0]);
+    }
+
+    assert!(map_grey(ToneMapOperator::Reinhard, 0.0, 1e6) < 1.0);
+}
+
+#[test]
+fn test_operator_values() {
+    assert_eq!(0.5, map_grey(ToneMapOperator::Clamp, 0.0, 0.5));
+    assert_eq!(0.5, map_grey(ToneMapOperator::Reinhard, 0.0, 1.0));
+    assert!((map_grey(ToneMapOperator::ExtendedReinhard(4.0), 0.0, 4.0) - 1.0).abs() < 1e-12);
+    assert!((map_grey(ToneMapOperator::Hable, 0.0, 5.6) - 1.0).abs() < 1e-12);
+    assert!((map_grey(ToneMapOperator::Aces, 0.0, 1.0) - 0.8038).abs() < 1e-4);
+}
+
+#[test]
+fn test_extended_reinhard_white_point_is_positive() {
+    for white in [0.0, -2.0, f64::NAN] {
+        let mapping = ToneMapping::new(ToneMapOperator::ExtendedReinhard(white), 0.0);
+
+        for col in [Colour::zero(), Colour::new(0.5, 0.0, 0.0), Colour::from_value(100.0)] {
+            let mapped = mapping.map(col);
+            assert!(mapped.x.is_finite() && mapped.y.is_finite() && mapped.z.is_finite());
+        }
+
+        assert_eq!(255, mapping.to_rgb(Colour::from_value(0.01))[0]);
+    }
+}
+
+#[test]
+fn test_reinhard_keeps_hue() {
+    let col = Colour::new(4.0, 2.0, 1.0);
+    let mapped = ToneMapping::new(ToneMapOperator::Reinhard, 0.0).map(col);
+
+    assert!((mapped.x / mapped.y - 2.0).abs() < 1e-12);
+    assert!((mapped.y / mapped.z - 2.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_exposure() {
+    for operator in OPERATORS {
+        assert!((map_grey(operator, 1.0, 0.1) - map_grey(operator, 0.0, 0.2)).abs() < 1e-12);
+        assert!((map_grey(operator, -2.0, 0.8) - map_grey(operator, 0.0, 0.2)).abs() < 1e-12);
+    }
+}
@@ -1,3 +1,4 @@
+use std::io;
 use image::{RgbImage, DynamicImage};
 use crate::{
     colour::Colour,
@@ -11,26 +12,17 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
-    pub fn new(filename: &str) -> Self {
+    /// Loads the 8 bit RGB image at filename, failing if it can not be read or has other channels
+    pub fn new(filename: &str) -> io::Result<Self> {
         let img = match image::open(filename) {
-            Err(err) => {
-                eprintln!("Error opening {filename}: {err}");
-                panic!();
-            },
-            Ok(dynamic_image) => {
-                match dynamic_image {
-                    DynamicImage::ImageRgb8(image) => image, 
-                    _ => {
-                        eprintln!("Can not create image texture from non-rgb image!");
-                        panic!();
-                    }
-                }
-            }
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("could not open {}: {}", filename, err))),
+            Ok(DynamicImage::ImageRgb8(image)) => image,
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not an 8 bit RGB image", filename)))
         };
 
-        Self {
+        Ok(Self {
             data: img
-        }
+        })
     }
 }
 
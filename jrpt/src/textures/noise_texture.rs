@@ -11,8 +11,13 @@ pub struct NoiseTexture {
 
 impl NoiseTexture {
     pub fn new(frequency: f64) -> Self {
+        Self::with_seed(frequency, 0)
+    }
+
+    /// Create noise texture whose noise is chosen by seed
+    pub fn with_seed(frequency: f64, seed: u64) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(seed),
             frequency
         }
     }
@@ -1,6 +1,6 @@
-use rand::{rngs::SmallRng, SeedableRng};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 use crate::{
-    random::{random_i32, random_in_range}, 
+    random::random_in_range, 
     point3::Point3, 
     vec3::Vec3
 };
@@ -16,9 +16,10 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Self {
+    /// Create noise with seed choosing its gradients and permutations
+    pub fn new(seed: u64) -> Self {
         let mut random_vectors: [Vec3; POINT_COUNT] = [Vec3::zero(); POINT_COUNT];
-        let mut rng = SmallRng::from_entropy();
+        let mut rng = SmallRng::seed_from_u64(seed);
 
         for i in 0..POINT_COUNT {
             random_vectors[i] = random_in_range(&mut rng, -1.0, 1.0);
@@ -26,9 +27,9 @@ impl Perlin {
         
         Self {
             random_vectors,
-            perm_x: Self::generate_perlin_permutation(),
-            perm_y: Self::generate_perlin_permutation(),
-            perm_z: Self::generate_perlin_permutation()
+            perm_x: Self::generate_perlin_permutation(&mut rng),
+            perm_y: Self::generate_perlin_permutation(&mut rng),
+            perm_z: Self::generate_perlin_permutation(&mut rng)
         }
     }
 
@@ -75,21 +76,21 @@ impl Perlin {
         accum.abs()
     }
 
-    fn generate_perlin_permutation() -> [i32; POINT_COUNT] {
+    fn generate_perlin_permutation(rng: &mut SmallRng) -> [i32; POINT_COUNT] {
         let mut p: [i32; POINT_COUNT] = [0; POINT_COUNT];
 
         for i in 0..POINT_COUNT {
             p[i] = i as i32;
         }
 
-        Self::permute(&mut p);
+        Self::permute(rng, &mut p);
 
         p
     }
 
-    fn permute(p: &mut[i32; POINT_COUNT]) {
+    fn permute(rng: &mut SmallRng, p: &mut[i32; POINT_COUNT]) {
         for i in (0..POINT_COUNT-1).rev() {
-            let target = random_i32(0..((i+1usize) as i32)) as usize;
+            let target = rng.gen_range(0..i+1);
             p.swap(i, target);
         }
     }
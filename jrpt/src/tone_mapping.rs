@@ -0,0 +1,105 @@
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use crate::colour::Colour;
+
+/// Operators compressing linear radiance into the [0,1] range of a display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapOperator {
+    Clamp,                      // values above 1 are clipped
+    Reinhard,                   // L / (1 + L) on luminance
+    ExtendedReinhard(f64),      // reinhard with the given luminance mapped to white, at least MIN_WHITE
+    Aces,                       // Narkowicz's fit of the ACES filmic curve
+    Hable                       // John Hable's Uncharted 2 filmic curve
+}
+
+/// Smallest white point of the extended reinhard operator, lower ones are raised to it
+/// as a white point of 0 would divide by zero
+pub const MIN_WHITE: f64 = 1e-3;
+
+/// Display transform applied when converting linear images to 8 bits
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: f64           // in stops, each stop doubles the brightness
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self::new(ToneMapOperator::Clamp, 0.0)
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f64) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Applies exposure and the operator to a linear colour, giving a linear colour in [0,1]
+    pub fn map(&self, col: Colour) -> Colour {
+        let col = col * 2.0_f64.powf(self.exposure);
+
+        match self.operator {
+            ToneMapOperator::Clamp => col,
+            ToneMapOperator::Reinhard => scale_luminance(col, |l| l / (1.0 + l)),
+            ToneMapOperator::ExtendedReinhard(white) => {
+                let white = white.max(MIN_WHITE);
+                scale_luminance(col, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            },
+            ToneMapOperator::Aces => map_channels(col, aces),
+            ToneMapOperator::Hable => {
+                // hable's curve expects a brighter input, and is normalized so its white point maps to 1
+                let white_scale = 1.0 / hable(HABLE_WHITE);
+                map_channels(col, |x| hable(2.0 * x) * white_scale)
+            }
+        }
+    }
+
+    /// Tone maps and encodes a linear colour as an 8 bit sRGB pixel
+    pub fn to_rgb(&self, col: Colour) -> Rgb<u8> {
+        self.map(col).to_rgb()
+    }
+
+    /// Tone maps a linear image into an 8 bit sRGB image
+    pub fn apply(&self, image: &Rgb32FImage) -> RgbImage {
+        ImageBuffer::from_fn(image.width(), image.height(), |i, j| {
+            let Rgb([r, g, b]) = *image.get_pixel(i, j);
+            self.to_rgb(Colour::new(r as f64, g as f64, b as f64))
+        })
+    }
+}
+
+/// sRGB opto-electronic transfer function, encoding a linear value in [0,1] for display
+pub fn srgb_oetf(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Scales col so its luminance becomes f(luminance), keeping its hue
+fn scale_luminance<F: Fn(f64) -> f64>(col: Colour, f: F) -> Colour {
+    let l = col.luminance();
+
+    if l <= 0.0 {
+        return Colour::zero();
+    }
+
+    col * (f(l) / l)
+}
+
+fn map_channels<F: Fn(f64) -> f64>(col: Colour, f: F) -> Colour {
+    Colour::new(f(col.x), f(col.y), f(col.z))
+}
+
+fn aces(x: f64) -> f64 {
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    (x * (a * x + b)) / (x * (c * x + d) + e)
+}
+
+// linear value hable's curve maps to white
+const HABLE_WHITE: f64 = 11.2;
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
@@ -0,0 +1,464 @@
+use std::{path::Path, process, time::Duration};
+use jrpt::{aov::Aov, output::OutputFormat, renderer::{Partition, Tile}, tone_mapping::{ToneMapOperator, ToneMapping}};
+use crate::scenes;
+
+pub const USAGE: &str = "\
+Usage: render [options]
+
+Options:
+  -s, --scene <scene>       built-in scene name, or a .toml or .pbrt scene file [default: cornell_box]
+  -W, --width <pixels>      image width, with the height following the scene's aspect ratio
+  -H, --height <pixels>     image height
+  -n, --spp <samples>       samples per pixel
+  -d, --max-depth <depth>   most bounces a path may take
+  -t, --threads <count>     threads to render with, 0 for one per core [default: 0]
+      --seed <seed>         seed random numbers are derived from
+  -o, --output <path>       image to write [default: image.png]
+  -f, --format <format>     png, hdr, pfm or exr [default: from the output's extension]
+      --crop <x0,y0,x1,y1>  render only the pixels from (x0, y0) up to (x1, y1), rows counting from the top
+      --tonemap <operator>  clamp, reinhard, reinhard:<white>, aces or hable, for png output [default: clamp]
+      --exposure <stops>    brighten or darken png output by a number of stops [default: 0]
+      --adaptive <error>    stop sampling pixels once their relative error is below error, with --spp
+                            the average samples per pixel, writing where samples went to <output>_heatmap.png
+      --max-samples <samples>
+                            most samples a pixel takes with --adaptive [default: 4 times --spp]
+      --time <seconds>      stop taking samples after a time, with --spp as the most taken
+      --min-samples <samples>
+                            samples every pixel takes with --adaptive or --time [default: 16, at most --spp]
+      --aov <names>         also write each of depth, normal, albedo, position, uv, object_id and
+                            material_id listed to <output>_<name>.exr
+      --denoise             denoise the image, guided by its albedo, normals and depth
+      --checkpoint <path>   save the render's progress to path as it goes and when it finishes
+      --checkpoint-interval <seconds>
+                            time between saves of the checkpoint [default: 600]
+      --resume              carry on with the render saved in the checkpoint
+      --more-samples <samples>
+                            samples per pixel to add to a resumed render that had finished
+      --worker <i/n>        render only worker i's share of a render split between n workers,
+                            counting from 0, and save it to the checkpoint
+      --split <split>       samples or tiles, what --worker splits between workers [default: samples]
+      --merge <a,b,...>     merge the checkpoints saved by every worker and finish the render from them
+  -l, --list                list the built-in scenes
+  -h, --help                print this message
+
+Built-in scenes:
+  ";
+
+/// What the command line asks for
+pub enum Command {
+    Render(Box<Options>),
+    List,
+    Help
+}
+
+/// Settings given on the command line, with None leaving the scene's own setting
+pub struct Options {
+    pub scene: String,          // a built-in scene's name, or a scene file
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub threads: usize,
+    pub seed: Option<u64>,
+    pub output: String,
+    pub format: OutputFormat,
+    pub crop: Option<Tile>,
+    pub tone_mapping: ToneMapping,
+    pub adaptive_threshold: Option<f64>,    // relative error pixels converge at, with adaptive sampling
+    pub max_samples: Option<u32>,
+    pub time_budget: Option<Duration>,
+    pub min_samples: u32,               // taken by every pixel before adaptive sampling or the time budget stop it
+    pub aovs: Vec<Aov>,
+    pub denoise: bool,
+    pub checkpoint: Option<String>,     // nothing is saved without one
+    pub checkpoint_interval: Duration,
+    pub resume: bool,                   // continue from the checkpoint instead of starting over
+    pub more_samples: u32,
+    pub partition: Option<Partition>,   // share of the render taken by this worker
+    pub merge: Vec<String>              // checkpoints of every worker, rendered from instead of starting over
+}
+
+/// Parses the arguments following the program name
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut options = Options {
+        scene: "cornell_box".to_string(),
+        width: None,
+        height: None,
+        samples_per_pixel: None,
+        max_depth: None,
+        threads: 0,
+        seed: None,
+        output: "image.png".to_string(),
+        format: OutputFormat::Png,
+        crop: None,
+        tone_mapping: ToneMapping::new(ToneMapOperator::Clamp, 0.0),
+        adaptive_threshold: None,
+        max_samples: None,
+        time_budget: None,
+        min_samples: 16,
+        aovs: Vec::new(),
+        denoise: false,
+        checkpoint: None,
+        checkpoint_interval: Duration::from_secs(600),
+        resume: false,
+        more_samples: 0,
+        partition: None,
+        merge: Vec::new()
+    };
+    let (mut format, mut checkpoint_interval) = (None, None);
+    let (mut worker, mut tiles) = (None, None);
+    let mut min_samples = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // options take their value from the next argument, or after an =
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None)
+        };
+        let mut value = || inline.clone().or_else(|| args.next().cloned()).ok_or_else(|| format!("{} needs a value", flag));
+
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-l" | "--list" => return Ok(Command::List),
+            "-s" | "--scene" => options.scene = value()?,
+            "-W" | "--width" => options.width = Some(positive(flag, &value()?)?),
+            "-H" | "--height" => options.height = Some(positive(flag, &value()?)?),
+            "-n" | "--spp" => options.samples_per_pixel = Some(positive(flag, &value()?)?),
+            "-d" | "--max-depth" => options.max_depth = Some(positive(flag, &value()?)?),
+            "-t" | "--threads" => options.threads = number(flag, &value()?)?,
+            "--seed" => options.seed = Some(number(flag, &value()?)?),
+            "-o" | "--output" => options.output = value()?,
+            "-f" | "--format" => {
+                let name = value()?;
+                format = Some(OutputFormat::from_extension(&name).ok_or_else(|| format!("unknown format {}", name))?);
+            },
+            "--crop" => options.crop = Some(crop_window(&value()?)?),
+            "--tonemap" => options.tone_mapping.operator = tone_map_operator(&value()?)?,
+            "--exposure" => options.tone_mapping.exposure = real(flag, &value()?)?,
+            "--adaptive" => options.adaptive_threshold = Some(positive_real(flag, &value()?)?),
+            "--max-samples" => options.max_samples = Some(positive(flag, &value()?)?),
+            "--time" => options.time_budget = Some(Duration::from_secs_f64(positive_real(flag, &value()?)?)),
+            "--min-samples" => min_samples = Some(positive(flag, &value()?)?),
+            "--aov" => options.aovs = aov_list(&value()?)?,
+            "--denoise" => options.denoise = true,
+            "--checkpoint" => options.checkpoint = Some(value()?),
+            "--checkpoint-interval" => checkpoint_interval = Some(Duration::from_secs(positive(flag, &value()?)? as u64)),
+            "--resume" => options.resume = true,
+            "--more-samples" => options.more_samples = positive(flag, &value()?)?,
+            "--worker" => worker = Some(worker_share(&value()?)?),
+            "--split" => tiles = Some(match value()?.as_str() {
+                "samples" => false,
+                "tiles" => true,
+                other => return Err(format!("unknown split {}", other))
+            }),
+            "--merge" => options.merge = checkpoint_list(&value()?)?,
+            _ => return Err(format!("unknown option {}", arg))
+        }
+    }
+
+    if !scenes::names().any(|name| name == options.scene) && !options.scene.ends_with(".toml") && !options.scene.ends_with(".pbrt") {
+        return Err(format!("unknown scene {}", options.scene));
+    }
+
+    if options.max_samples.is_some() && options.adaptive_threshold.is_none() {
+        return Err("--max-samples only applies with --adaptive".to_string());
+    }
+    if min_samples.is_some() && options.adaptive_threshold.is_none() && options.time_budget.is_none() {
+        return Err("--min-samples only applies with --adaptive or --time".to_string());
+    }
+    options.min_samples = min_samples.unwrap_or(options.min_samples);
+
+    if options.checkpoint.is_none() {
+        if options.resume {
+            return Err("--resume needs a --checkpoint to resume from".to_string());
+        }
+        if checkpoint_interval.is_some() {
+            return Err("--checkpoint-interval needs a --checkpoint to save".to_string());
+        }
+    }
+    options.checkpoint_interval = checkpoint_interval.unwrap_or(options.checkpoint_interval);
+
+    options.partition = match (worker, tiles) {
+        (Some((worker, workers)), Some(true)) => Some(Partition::Tiles { worker, workers }),
+        (Some((worker, workers)), _) => Some(Partition::Samples { worker, workers }),
+        (None, Some(_)) => return Err("--split needs a --worker to split the render with".to_string()),
+        (None, None) => None
+    };
+    if options.partition.is_some() && options.checkpoint.is_none() {
+        return Err("--worker needs a --checkpoint to save its share to".to_string());
+    }
+
+    if !options.merge.is_empty() && (options.resume || options.partition.is_some()) {
+        return Err("--merge starts from the workers' checkpoints, so can not be given with --resume or --worker".to_string());
+    }
+
+    if options.more_samples > 0 && !options.resume && options.merge.is_empty() {
+        return Err("--more-samples only adds to a render given with --resume or --merge".to_string());
+    }
+    // adaptive sampling stops each pixel at max_samples, so there is no adding to it
+    if options.more_samples > 0 && options.adaptive_threshold.is_some() {
+        return Err("--more-samples can not add to an adaptively sampled render, raise --max-samples instead".to_string());
+    }
+
+    // checked before rendering rather than when the first preview or checkpoint is written
+    for path in [Some(&options.output), options.checkpoint.as_ref()].into_iter().flatten() {
+        let dir = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty());
+        if let Some(dir) = dir.filter(|dir| !dir.is_dir()) {
+            return Err(format!("no such directory {} to write {} to", dir.display(), path));
+        }
+    }
+
+    options.format = match format.or_else(|| OutputFormat::from_path(&options.output)) {
+        Some(format) => format,
+        None => return Err(format!("can not tell the format of {}, give one with --format", options.output))
+    };
+
+    Ok(Command::Render(Box::new(options)))
+}
+
+/// Returns the usage message, ending with the names of the built-in scenes
+pub fn usage() -> String {
+    format!("{}{}", USAGE, scenes::names().collect::<Vec<_>>().join(", "))
+}
+
+/// Prints message and the usage, then exits with the status for bad arguments
+pub fn fail(message: &str) -> ! {
+    eprintln!("render: {}\n", message);
+    eprintln!("{}", usage());
+    process::exit(2);
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a whole number, not {}", flag, value))
+}
+
+fn real(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => Err(format!("{} expects a number, not {}", flag, value))
+    }
+}
+
+fn positive_real(flag: &str, value: &str) -> Result<f64, String> {
+    match real(flag, value)? {
+        v if v > 0.0 => Ok(v),
+        _ => Err(format!("{} must be above 0", flag))
+    }
+}
+
+fn positive(flag: &str, value: &str) -> Result<u32, String> {
+    match number(flag, value)? {
+        0 => Err(format!("{} must be at least 1", flag)),
+        n => Ok(n)
+    }
+}
+
+fn crop_window(value: &str) -> Result<Tile, String> {
+    let bounds = value.split(',').map(|v| number::<u32>("--crop", v.trim())).collect::<Result<Vec<_>, _>>()?;
+
+    match bounds[..] {
+        [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(Tile { x0, y0, x1, y1 }),
+        [_, _, _, _] => Err(format!("crop window {} is empty", value)),
+        _ => Err(format!("--crop expects x0,y0,x1,y1, not {}", value))
+    }
+}
+
+fn tone_map_operator(value: &str) -> Result<ToneMapOperator, String> {
+    match value {
+        "clamp" => Ok(ToneMapOperator::Clamp),
+        "reinhard" => Ok(ToneMapOperator::Reinhard),
+        "aces" => Ok(ToneMapOperator::Aces),
+        "hable" => Ok(ToneMapOperator::Hable),
+        _ => match value.strip_prefix("reinhard:") {
+            Some(white) => Ok(ToneMapOperator::ExtendedReinhard(positive_real("--tonemap", white)?)),
+            None => Err(format!("unknown tone mapping operator {}", value))
+        }
+    }
+}
+
+fn aov_list(value: &str) -> Result<Vec<Aov>, String> {
+    value.split(',').map(|name| {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name.trim()).ok_or_else(|| format!("unknown AOV {}", name))
+    }).collect()
+}
+
+fn worker_share(value: &str) -> Result<(u32, u32), String> {
+    let (worker, workers) = value.split_once('/').ok_or_else(|| format!("--worker expects i/n, not {}", value))?;
+    let (worker, workers) = (number("--worker", worker)?, positive("--worker", workers)?);
+
+    if worker >= workers {
+        return Err(format!("worker {} is not one of workers 0 to {}", worker, workers - 1));
+    }
+    Ok((worker, workers))
+}
+
+fn checkpoint_list(value: &str) -> Result<Vec<String>, String> {
+    value.split(',').map(|path| match path.trim() {
+        "" => Err(format!("--merge expects a list of checkpoints, not {}", value)),
+        path => Ok(path.to_string())
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        parse(&args.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+    }
+
+    fn options(args: &str) -> Options {
+        match parse_args(args) {
+            Ok(Command::Render(options)) => *options,
+            Ok(_) => panic!("{} does not render", args),
+            Err(message) => panic!("{} was rejected: {}", args, message)
+        }
+    }
+
+    fn error(args: &str) -> String {
+        match parse_args(args) {
+            Err(message) => message,
+            Ok(_) => panic!("{} was accepted", args)
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = options("");
+
+        assert_eq!(options.scene, "cornell_box");
+        assert_eq!(options.output, "image.png");
+        assert_eq!(options.format, OutputFormat::Png);
+        assert_eq!((options.width, options.height, options.samples_per_pixel, options.seed), (None, None, None, None));
+
+        // nothing is left behind unless asked for
+        assert_eq!(options.checkpoint, None);
+        assert!(!options.resume);
+    }
+
+    #[test]
+    fn test_options_and_values() {
+        let options = options("-s earth --width=640 -H 480 -n 16 -d 5 -t 2 --seed 7 -o out.exr --crop 1,2,30,40");
+
+        assert_eq!(options.scene, "earth");
+        assert_eq!((options.width, options.height), (Some(640), Some(480)));
+        assert_eq!((options.samples_per_pixel, options.max_depth, options.threads, options.seed), (Some(16), Some(5), 2, Some(7)));
+        assert_eq!(options.format, OutputFormat::Exr);
+        assert_eq!(options.crop, Some(Tile { x0: 1, y0: 2, x1: 30, y1: 40 }));
+
+        // an explicit format wins over the extension
+        assert_eq!(self::options("-o out.img -f hdr").format, OutputFormat::Hdr);
+    }
+
+    #[test]
+    fn test_checkpoint_options() {
+        let options = options("--checkpoint render.ckpt --checkpoint-interval 30 --resume --more-samples 64");
+
+        assert_eq!(options.checkpoint.as_deref(), Some("render.ckpt"));
+        assert_eq!(options.checkpoint_interval, Duration::from_secs(30));
+        assert_eq!((options.resume, options.more_samples), (true, 64));
+        assert_eq!(self::options("--checkpoint render.ckpt").checkpoint_interval, Duration::from_secs(600));
+
+        // each needs the one before it
+        assert!(error("--resume").contains("--checkpoint"));
+        assert!(error("--checkpoint-interval 30").contains("--checkpoint"));
+        assert!(error("--checkpoint render.ckpt --more-samples 64").contains("--resume"));
+        assert!(error("--checkpoint no/such/dir/render.ckpt").contains("no/such/dir"));
+        error("--checkpoint render.ckpt --checkpoint-interval 0");
+    }
+
+    #[test]
+    fn test_worker_and_merge_options() {
+        let options = options("--worker 2/3 --checkpoint worker2.ckpt");
+        assert_eq!(options.partition, Some(Partition::Samples { worker: 2, workers: 3 }));
+        assert_eq!(self::options("--worker 0/2 --split tiles --checkpoint a.ckpt").partition, Some(Partition::Tiles { worker: 0, workers: 2 }));
+
+        let options = self::options("--merge a.ckpt,b.ckpt,c.ckpt --more-samples 8");
+        assert_eq!(options.merge, ["a.ckpt", "b.ckpt", "c.ckpt"]);
+        assert_eq!((options.partition, options.more_samples), (None, 8));
+
+        for args in ["--worker 1/0", "--worker 1", "--worker 1/x", "--worker=-1/2"] {
+            assert!(error(&format!("{} --checkpoint a.ckpt", args)).contains("--worker"), "{}", args);
+        }
+        assert!(error("--worker 3/3 --checkpoint a.ckpt").contains("0 to 2"));
+        assert!(error("--worker 0/2").contains("--checkpoint"));
+        assert!(error("--split tiles").contains("--worker"));
+        assert!(error("--worker 0/2 --split pixels --checkpoint a.ckpt").contains("pixels"));
+        assert!(error("--merge a.ckpt,,b.ckpt").contains("list"));
+        assert!(error("--merge a.ckpt --checkpoint a.ckpt --resume").contains("--merge"));
+        assert!(error("--merge a.ckpt --worker 0/2 --checkpoint c.ckpt").contains("--merge"));
+    }
+
+    #[test]
+    fn test_image_options() {
+        let options = options("--tonemap reinhard:4 --exposure -1.5 --aov albedo,depth --denoise");
+
+        assert_eq!(options.tone_mapping.operator, ToneMapOperator::ExtendedReinhard(4.0));
+        assert_eq!(options.tone_mapping.exposure, -1.5);
+        assert_eq!(options.aovs, [Aov::Albedo, Aov::Depth]);
+        assert!(options.denoise);
+        assert_eq!(self::options("--tonemap aces").tone_mapping.operator, ToneMapOperator::Aces);
+
+        let defaults = self::options("");
+        assert_eq!((defaults.tone_mapping.operator, defaults.tone_mapping.exposure), (ToneMapOperator::Clamp, 0.0));
+        assert!(defaults.aovs.is_empty() && !defaults.denoise);
+
+        assert!(error("--tonemap filmic").contains("filmic"));
+        assert!(error("--tonemap reinhard:0").contains("--tonemap"));
+        assert!(error("--exposure nan").contains("--exposure"));
+        assert!(error("--aov albedo,colour").contains("colour"));
+    }
+
+    #[test]
+    fn test_sampling_options() {
+        let options = options("--adaptive 0.01 --min-samples 8 --max-samples 500 --time 90.5");
+
+        assert_eq!((options.adaptive_threshold, options.max_samples), (Some(0.01), Some(500)));
+        assert_eq!((options.time_budget, options.min_samples), (Some(Duration::from_secs_f64(90.5)), 8));
+        assert_eq!(self::options("--time 60").min_samples, 16);
+
+        for args in ["--adaptive 0", "--adaptive -0.1", "--adaptive inf", "--time 0", "--time x", "--min-samples 0 --time 1"] {
+            error(args);
+        }
+        assert!(error("--max-samples 100").contains("--adaptive"));
+        assert!(error("--min-samples 4").contains("--adaptive"));
+        assert!(error("--adaptive 0.01 --checkpoint a.ckpt --resume --more-samples 8").contains("--max-samples"));
+    }
+
+    #[test]
+    fn test_help_and_list() {
+        assert!(matches!(parse_args("--help"), Ok(Command::Help)));
+        assert!(matches!(parse_args("-s earth -l"), Ok(Command::List)));
+
+        // the first of them wins, and arguments after it are not looked at
+        assert!(matches!(parse_args("-h --bogus"), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn test_invalid_arguments_rejected() {
+        assert!(error("--bogus").contains("--bogus"));
+        assert!(error("-s").contains("needs a value"));
+        assert!(error("-n").contains("needs a value"));
+        assert!(error("-s no_such_scene").contains("no_such_scene"));
+        assert!(error("-o image.xyz").contains("format"));
+        assert!(error("-f xyz").contains("xyz"));
+        assert!(error("-o no/such/dir/image.png").contains("no/such/dir"));
+    }
+
+    #[test]
+    fn test_bad_numbers_rejected() {
+        for args in ["-W 0", "-H -3", "-n many", "-d 1.5", "-t x", "--seed -1", "--width=", "-n 99999999999"] {
+            error(args);
+        }
+    }
+
+    #[test]
+    fn test_crop_bounds() {
+        assert!(error("--crop 1,2,3").contains("x0,y0,x1,y1"));
+        assert!(error("--crop 1,2,3,4,5").contains("x0,y0,x1,y1"));
+        assert!(error("--crop 5,0,5,10").contains("empty"));
+        assert!(error("--crop 0,10,5,2").contains("empty"));
+        assert!(error("--crop 0,-1,5,5").contains("whole number"));
+    }
+}
@@ -1,158 +1,272 @@
 mod scenes;
+mod cli;
 
-use jrpt::colour::Colour;
-use jrpt::objects::{Object, object_list};
-use jrpt::{camera::Camera};
-use jrpt::vec3::Vec3;
-use jrpt::scene::{Scene};
-use jrpt::point3::Point3;
-use jrpt::renderer::Renderer;
+use std::{fmt, process};
+use std::time::Instant;
+use jrpt::renderer::{Renderer, AdaptiveSampling, TimeBudget};
+use jrpt::output;
+use jrpt::aov::Aov;
+use jrpt::denoise::Denoiser;
+use jrpt::film::Film;
+use jrpt::checkpoint;
+use jrpt::scene_file;
+use jrpt::pbrt;
+use jrpt::stats;
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 fn main() {
-    let now = std::time::Instant::now();
-    let multithreading = true;
-
-    // Image
-    let mut aspect_ratio = 16.0/9.0;
-    let mut image_width = 512_u32;
-    let mut samples_per_pixel = 300;
-    let max_depth = 15;
-
-    // World
-    let world: Object;
-    let lookfrom: Point3;
-    let lookat: Point3;
-    let vfov: f64;
-    let mut aperture: f64 = 0.0;
-    let mut background_colour: Colour = Colour::new(0.7, 0.8, 1.0);
-    let mut time = 0.0..0.0;
-
-    let scene_num = 5;
-
-    match scene_num {
-        1 => {
-            world = scenes::two_spheres::build_scene();
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::zero();
-            vfov = 20.0;
-        },
-        2 => {
-            world = scenes::two_perlin_spheres::build_scene();
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::zero();
-            vfov = 20.0;
-        },
-        3 => {
-            world = scenes::earth::build_scene();
-            lookfrom = Point3::new(0.0, 0.0, -15.0);
-            lookat = Point3::zero();
-            vfov = 20.0;
-        },
-        4 => {
-            world = scenes::simple_light::build_scene();
-            lookfrom = Point3::new(26.0, 3.0, 6.0);
-            lookat = Point3::new(0.0, 2.0, 0.0);
-            vfov = 20.0;
-            background_colour = Colour::zero();
-        },
-        5 => {
-            world = scenes::cornell_box::build_scene();
-            aspect_ratio = 1.0;
-            image_width = 400;
-            samples_per_pixel = 200;
-            background_colour = Colour::zero();
-
-            lookfrom = Point3::new(278.0, 278.0, -800.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-        },
-        6 => {
-            world = scenes::cornell_smoke::build_scene();
-            aspect_ratio = 1.0;
-            image_width = 400;
-            samples_per_pixel = 400;
-            background_colour = Colour::zero();
-
-            lookfrom = Point3::new(278.0, 278.0, -800.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-        },
-        7 => {
-            world = scenes::final_scene::build_scene();
-            aspect_ratio = 1.0;
-            image_width = 400;
-            samples_per_pixel = 100;
-            background_colour = Colour::zero();
-            time = 0.0..1.0;
-            lookfrom = Point3::new(478.0, 278.0, -600.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(cli::Command::Render(options)) => options,
+        Ok(cli::Command::List) => {
+            for name in scenes::names() {
+                println!("{}", name);
+            }
+            return;
         },
-        8 => {
-            world = scenes::random_scene::build_scene();
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::zero();
-            vfov = 20.0;
-            aperture = 0.1;
-            time = 0.0..0.0;
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::usage());
+            return;
         },
-        9 => {
-            world = scenes::triangle_scene::build_scene();
-            lookfrom = Point3::new(0.0, 0.0, -10.0);
-            lookat = Point3::zero();
-            vfov = 30.0;
-            aperture = 0.0;
-            time = 0.0..0.0;
-        },
-        10 => {
-            world = scenes::wavefront_scene::build_scene();
-            aspect_ratio = 1.0;
-            image_width = 1024;
-            samples_per_pixel = 10000;
-            background_colour = Colour::zero();
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            lookfrom = Point3::new(278.0, 278.0, -800.0);
-            vfov = 40.0;
-            aperture = 0.0;
-            time = 0.0..0.0;
-        },
-        11 => {
-            world = scenes::scene1::build_scene();
-            image_width = 2048;
-            samples_per_pixel = 20000;
-            background_colour = Colour::zero();
-            lookfrom = Point3::new(50.0,5.0,-20.0);
-            lookat = Point3::new(0.0, 0.0, 50.0);
-            vfov = 90.0;
-            aperture = 0.0;
-            time = 0.0..0.0;
+        Err(message) => cli::fail(&message)
+    };
+
+    // 0 leaves rayon to pick one thread per core
+    if options.threads > 1 {
+        rayon::ThreadPoolBuilder::new().num_threads(options.threads).build_global().unwrap_or_else(|err| fail("could not start threads", err));
+    }
+
+    render(&options);
+}
+
+/// Renders the scene options asks for and writes out the image, its AOVs and checkpoint
+fn render(options: &cli::Options) {
+    let now = Instant::now();
+    let multithreading = options.threads != 1;
+
+    // Image
+    let output_path = options.output.as_str();
+    let tone_mapping = options.tone_mapping;    // only applies to png output
+    let aovs = options.aovs.as_slice();     // each written to <output>_<name>.exr
+    let denoiser = options.denoise.then(Denoiser::default);     // applied to previews and the final image
+    let checkpoint_path = options.checkpoint.as_deref();    // written every checkpoint_interval and when the render finishes
+    let mut partition = options.partition;     // this worker's share, saved to its checkpoint for --merge
+
+    // World, rendered with the settings it recommends. A scene file's integrator, sampler and
+    // filter pick e.g. bidirectional path tracing for light that is hard to reach from the camera
+    let path = options.scene.as_str();
+    let (mut scene, settings) = if path.ends_with(".toml") {
+        let file = scene_file::load(path).unwrap_or_else(|err| fail(path, err));
+        (file.scene, file.settings)
+    } else if path.ends_with(".pbrt") {
+        let file = pbrt::load(path).unwrap_or_else(|err| fail(path, err));
+        for warning in &file.warnings {
+            eprintln!("render: {}: warning: {}", path, warning);
+        }
+        (file.scene, file.settings)
+    } else {
+        let builtin = scenes::build(&options.scene).expect("scene names are checked by cli::parse");
+        (builtin.scene, builtin.settings)
+    };
+
+    // the command line overrides the scene's settings. Giving one side of the image keeps the
+    // scene's aspect ratio, giving both widens or narrows the camera's view to fit
+    let aspect_ratio = settings.width as f64 / settings.height as f64;
+    let (image_width, image_height) = match (options.width, options.height) {
+        (Some(width), Some(height)) => {
+            scene.camera.set_aspect_ratio(width as f64 / height as f64);
+            (width, height)
         },
-        _ => {
-            world = object_list::new();
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::zero();
-            vfov = 20.0;
-            background_colour = Colour::zero();
+        (Some(width), None) => (width, ((width as f64 / aspect_ratio) as u32).max(1)),
+        (None, Some(height)) => (((height as f64 * aspect_ratio) as u32).max(1), height),
+        (None, None) => (settings.width, settings.height)
+    };
+    let mut samples_per_pixel = options.samples_per_pixel.unwrap_or(settings.samples_per_pixel);
+    let max_depth = options.max_depth.unwrap_or(settings.max_depth);     // safety limit, paths are normally ended by russian roulette
+    let mut seed = options.seed.unwrap_or(settings.seed);
+    let mut samples_per_pass = settings.samples_per_pass;
+
+    if let Some(crop) = options.crop {
+        if crop.x1 > image_width || crop.y1 > image_height {
+            cli::fail(&format!("crop window reaches past the {}x{} image", image_width, image_height));
         }
     }
 
-    let vup = Vec3::new(0.0,1.0,0.0);
-    let dist_to_focus = 10.0;
-    
-    let cam = Camera::new(lookfrom, lookat, vup, vfov, aspect_ratio, aperture, dist_to_focus, time);
-    let scene = Scene::new(cam, world, background_colour);
+    // a resumed render carries on with the checkpoint's seed, passes and share of the render, finishing
+    // the samples it had planned, or adding more_samples to a finished render. Merged renders are
+    // resumed like any other
+    let more_samples = options.more_samples;
+    let checkpoint = if !options.merge.is_empty() {
+        let partials = options.merge.iter().map(|path| checkpoint::load(path).unwrap_or_else(|err| fail(path, err))).collect();
+        Some(checkpoint::merge(partials).unwrap_or_else(|err| fail("could not merge checkpoints", err)))
+    } else {
+        checkpoint_path.filter(|_| options.resume).map(|path| checkpoint::load(path).unwrap_or_else(|err| fail(path, err)))
+    };
+
+    if let Some(checkpoint) = &checkpoint {
+        seed = checkpoint.seed;
+        samples_per_pass = checkpoint.samples_per_pass;
+        partition = checkpoint.partition;
+        samples_per_pixel = checkpoint.film.samples().max(samples_per_pixel) + more_samples;
+    }
+
+    // with adaptive sampling samples_per_pixel is the average taken, and with a time budget the most
+    let min_samples = options.min_samples.min(samples_per_pixel);
+    let adaptive_sampling = options.adaptive_threshold.map(|threshold| AdaptiveSampling {
+        min_samples,
+        max_samples: options.max_samples.unwrap_or(samples_per_pixel.saturating_mul(4)),
+        threshold
+    });
+    let time_budget = options.time_budget.map(|duration| TimeBudget { duration, min_samples });
 
     // Render
-    let renderer = Renderer::new(samples_per_pixel, max_depth, multithreading);
+    let mut renderer = Renderer::new(samples_per_pixel, max_depth, multithreading);
+    renderer.set_roulette_depth(settings.roulette_depth);
+    renderer.set_seed(seed);
+    renderer.set_integrator(settings.integrator);
+    renderer.set_adaptive_sampling(adaptive_sampling);
+    renderer.set_time_budget(time_budget);
+    renderer.set_filter(settings.filter);
+    renderer.set_sampler(settings.sampler);
+    renderer.set_partition(partition);
+    renderer.set_crop_window(options.crop);
+
+    // the denoiser is guided by albedo, normals and depth
+    let mut film_aovs = aovs.to_vec();
+    if denoiser.is_some() {
+        for aov in [Aov::Albedo, Aov::Normal, Aov::Depth] {
+            if !film_aovs.contains(&aov) {
+                film_aovs.push(aov);
+            }
+        }
+    }
+    renderer.set_aovs(&film_aovs);
+
+    let image = |film: &Film| {
+        let image = match &denoiser {
+            Some(denoiser) => denoiser.denoise_film(film).expect("the film keeps the AOVs the denoiser needs"),
+            None => film.to_hdr_image()
+        };
+
+        match options.crop {
+            Some(crop) => output::crop(&image, crop),
+            None => image
+        }
+    };
+
+    let mut last_checkpoint = Instant::now();
+    let progress = |film: &Film| {
+        // write out a preview after every pass
+        println!("{}/{} samples after {}", film.samples(), samples_per_pixel, now.elapsed().as_secs_f64());
+        output::save_with_format(&image(film), output_path, options.format, &tone_mapping)
+            .unwrap_or_else(|err| fail(&format!("could not write {}", output_path), err));
+
+        if let Some(path) = checkpoint_path.filter(|_| last_checkpoint.elapsed() >= options.checkpoint_interval) {
+            checkpoint::save(path, film, seed, samples_per_pass, partition)
+                .unwrap_or_else(|err| fail(&format!("could not write {}", path), err));
+            last_checkpoint = Instant::now();
+        }
 
-    let img = renderer.render(&scene, (image_width  as f64 / aspect_ratio) as u32, image_width);
+        true
+    };
 
-    println!("Finished in {}", now.elapsed().as_secs_f64());
+    let film = match checkpoint {
+        Some(checkpoint) => renderer.resume(&scene, checkpoint, progress).unwrap_or_else(|err| fail("could not resume", err)),
+        None => renderer.render_progressive(&scene, image_height, image_width, samples_per_pass, progress)
+    };
+
+    if let Some(path) = checkpoint_path {
+        checkpoint::save(path, &film, seed, samples_per_pass, partition)
+            .unwrap_or_else(|err| fail(&format!("could not write {}", path), err));
+    }
+
+    println!("Finished {} samples per pixel in {}", film.samples(), now.elapsed().as_secs_f64());
     
     println!("Writing to file...");
-    
-    img.save("image.png").unwrap();
+    let write_start = Instant::now();
+
+    output::save_with_format(&image(&film), output_path, options.format, &tone_mapping)
+        .unwrap_or_else(|err| fail(&format!("could not write {}", output_path), err));
+
+    let stem = std::path::Path::new(output_path).with_extension("");
+    for &aov in aovs {
+        let path = format!("{}_{}.exr", stem.display(), aov.name());
+        let aov_image = film.aov_image(aov).expect("the film keeps every AOV asked for");
+        let aov_image = match options.crop {
+            Some(crop) => output::crop(&aov_image, crop),
+            None => aov_image
+        };
+        output::save(&aov_image, &path, &tone_mapping).unwrap_or_else(|err| fail(&format!("could not write {}", path), err));
+    }
+
+    if adaptive_sampling.is_some() {
+        let path = format!("{}_heatmap.png", stem.display());
+        film.to_heatmap_image().save(&path).unwrap_or_else(|err| fail(&format!("could not write {}", path), err));
+    }
+
+    let mut stats = *film.stats();
+    stats.bvh_build = stats::bvh_build_time();     // scenes build their BVHs as they are created
+    stats.write_out = write_start.elapsed();
+    println!("{}", stats);
+}
+
+/// Prints what went wrong and why, then exits with an error
+fn fail(what: &str, err: impl fmt::Display) -> ! {
+    eprintln!("render: {}: {}", what, err);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    /// Renders as the binary would with the given arguments
+    fn run(args: &[&str]) {
+        match cli::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>()) {
+            Ok(cli::Command::Render(options)) => render(&options),
+            Ok(_) => panic!("{:?} does not render", args),
+            Err(message) => panic!("{:?} was rejected: {}", args, message)
+        }
+    }
+
+    #[test]
+    fn test_workers_merge_to_single_render() {
+        let dir = std::env::temp_dir().join(format!("render_merge_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        let scene = ["-s", "two_spheres", "-W", "12", "-H", "8", "-n", "6", "--seed", "3"];
+
+        run(&[&scene[..], &["-o", &path("single.exr"), "--checkpoint", &path("single.ckpt")]].concat());
+        let single = checkpoint::load(path("single.ckpt")).unwrap().film;
+
+        for split in ["samples", "tiles"] {
+            let workers = (0..3).map(|worker| path(&format!("{}{}.ckpt", split, worker))).collect::<Vec<_>>();
+            for (worker, checkpoint) in workers.iter().enumerate() {
+                let share = format!("{}/3", worker);
+                run(&[&scene[..], &["-o", &path("worker.exr"), "--worker", &share, "--split", split, "--checkpoint", checkpoint]].concat());
+            }
+
+            run(&[&scene[..], &["-o", &path("merged.exr"), "--merge", &workers.join(","), "--checkpoint", &path("merged.ckpt")]].concat());
+            let merged = checkpoint::load(path("merged.ckpt")).unwrap();
+
+            // the same samples, summed in a different order
+            assert_eq!(merged.partition, None);
+            assert_eq!(merged.film.samples(), single.samples());
+            for j in 0..single.height() {
+                for i in 0..single.width() {
+                    assert_eq!(merged.film.pixel_samples(i, j), single.pixel_samples(i, j));
+
+                    let (p, q) = (merged.film.get_pixel(i, j), single.get_pixel(i, j));
+                    for c in 0..3 {
+                        assert!((p[c] - q[c]).abs() <= 1e-9 * p[c].abs().max(1.0), "{} split gave {} for {}", split, p, q);
+                    }
+                }
+            }
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
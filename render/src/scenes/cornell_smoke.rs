@@ -10,13 +10,28 @@ use jrpt::{
         }, 
         rect_prism::{RectangularPrism, self},
         Object,
-        affine::{Affine, self}, constant_medium::{ConstantMedium, self}
+        affine::{Affine, Transform}, constant_medium::{ConstantMedium, self}
     }, 
     materials::{
-        lambertian::Lambertian, diffuse_light::DiffuseLight
+        lambertian::Lambertian, diffuse_light::DiffuseLight, Material
     }, 
-    colour::Colour, point3::Point3
+    colour::Colour, point3::Point3,
+    scene_file::RenderSettings
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(278.0, 278.0, -800.0),
+        lookat: Point3::new(278.0, 278.0, 0.0),
+        vfov: 40.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { samples_per_pixel: 400, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), build_lights(), view, Colour::zero(), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
@@ -24,30 +39,32 @@ pub fn build_scene() -> Object {
     let red = Arc::new(Lambertian::new(Colour::new(0.65, 0.05, 0.05)));
     let white = Arc::new(Lambertian::new(Colour::new(0.73, 0.73, 0.73)));
     let green = Arc::new(Lambertian::new(Colour::new(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::new(Colour::new(15.0, 15.0, 15.0)));
+    let light_mat = Arc::new(DiffuseLight::new(Colour::new(15.0, 15.0, 15.0)));
 
     // walls
     object_list::add(&mut world, yz_rect::new(0.0, 555.0, 0.0, 555.0, 555.0, green));
     object_list::add(&mut world, yz_rect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
-    object_list::add(&mut world, xz_rect::new(213.0, 343.0, 227.0, 332.0, 554.0, light));
+    object_list::add(&mut world, light(light_mat));
     object_list::add(&mut world, xz_rect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
     object_list::add(&mut world, xz_rect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
     object_list::add(&mut world, xy_rect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
 
     // boxes
     let b = rect_prism::new(Point3::zero(), Point3::new(165.0, 330.0, 165.0), white.clone());
-    let mut transform = affine::new(b);
-    affine::rotate_y(&mut transform, (15.0 as f64).to_radians());
-    affine::translate(&mut transform, 265.0, 0.0, 295.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().rotate_y((15.0 as f64).to_radians()).translate(265.0, 0.0, 295.0).apply(b).unwrap();
     object_list::add(&mut world, constant_medium::new(transform, 0.01, Colour::zero()));
 
     let b = rect_prism::new(Point3::zero(), Point3::from_value(165.0), white.clone());
-    let mut transform = affine::new(b);
-    affine::rotate_y(&mut transform, (-18.0 as f64).to_radians());
-    affine::translate(&mut transform, 130.0, 0.0, 65.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().rotate_y((-18.0 as f64).to_radians()).translate(130.0, 0.0, 65.0).apply(b).unwrap();
     object_list::add(&mut world, constant_medium::new(transform, 0.01, Colour::from_value(1.0)));
 
     world
 }
+
+pub fn build_lights() -> Vec<Object> {
+    vec![light(Arc::new(DiffuseLight::new(Colour::new(15.0, 15.0, 15.0))))]
+}
+
+fn light(material: Arc<dyn Material>) -> Object {
+    xz_rect::new(213.0, 343.0, 227.0, 332.0, 554.0, material)
+}
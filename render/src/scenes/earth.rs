@@ -3,26 +3,37 @@ use std::sync::Arc;
 use jrpt::{
     materials::lambertian::Lambertian,
     objects::{
-        affine::{self},
+        affine::Transform,
         object_list::{self},
         sphere::{self},
         Object,
     },
     textures::image_texture::ImageTexture,
+    scene_file::RenderSettings, colour::Colour, point3::Point3,
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(0.0, 0.0, -15.0),
+        lookat: Point3::zero(),
+        vfov: 20.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 512, height: 288, samples_per_pixel: 300, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::new(0.7, 0.8, 1.0), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
 
-    let earth_texture = Arc::new(ImageTexture::new("textures/earthmap.jpg"));
+    let earth_texture = Arc::new(ImageTexture::new("textures/earthmap.jpg").expect("textures/earthmap.jpg should be an RGB image"));
     let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
 
     let sphere = sphere::canonical(earth_surface);
-    let mut transform = affine::new(sphere);
-    affine::rotate_z(&mut transform, 0.4);
-    affine::scale_uniform(&mut transform, 2.0);
-    affine::translate(&mut transform, 1.0, 1.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().rotate_z(0.4).scale_uniform(2.0).translate(1.0, 1.0, 0.0).apply(sphere).unwrap();
 
     object_list::add(&mut world, transform);
 
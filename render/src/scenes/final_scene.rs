@@ -4,16 +4,31 @@ use jrpt::{
     materials::{
         lambertian::Lambertian,
         diffuse_light::DiffuseLight,
-        dialetric::Dialetric, metal::Metal
+        dialetric::Dialetric, metal::Metal, Material
     },
     colour::Colour,
     point3::Point3,
     textures::{image_texture::ImageTexture, noise_texture::NoiseTexture},
-    random::random_in_range, objects::{Object, object_list, rect_prism, bvh, aa_rectangles::xz_rect, moving_sphere, sphere, constant_medium}
+    random::random_in_range, objects::{Object, object_list, rect_prism, bvh, aa_rectangles::xz_rect, moving_sphere, sphere, constant_medium},
+    scene_file::RenderSettings
 };
+use super::{BuiltinScene, View};
 
 const BOXES_PER_SIDE: i32 = 20;
 
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(478.0, 278.0, -600.0),
+        lookat: Point3::new(278.0, 278.0, 0.0),
+        vfov: 40.0,
+        aperture: 0.0,
+        time: 0.0..1.0
+    };
+    let settings = RenderSettings { samples_per_pixel: 100, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), build_lights(), view, Colour::zero(), settings)
+}
+
 pub fn build_scene() -> Object {
     let mut boxes1 = object_list::new();
     let ground_mat = Arc::new(Lambertian::new(Colour::new(0.48, 0.83, 0.53)));
@@ -36,8 +51,8 @@ pub fn build_scene() -> Object {
     let mut world = object_list::new();
     object_list::add(&mut world, bvh::new(boxes1, 0.0..1.0));
 
-    let light = Arc::new(DiffuseLight::new(Colour::from_value(7.0)));
-    object_list::add(&mut world, xz_rect::new(123.0,423.0,147.0,412.0,554.0, light.clone()));
+    let light_mat = Arc::new(DiffuseLight::new(Colour::from_value(7.0)));
+    object_list::add(&mut world, light(light_mat));
 
     let center1 = Point3::new(400.0,400.0,200.0);
     let center2 = &center1 + Point3::new(30.0,0.0,0.0);
@@ -55,7 +70,7 @@ pub fn build_scene() -> Object {
     let boundary = sphere::new(Point3::zero(), 5000.0, Arc::new(Dialetric::new(1.5)));
     object_list::add(&mut world, constant_medium::new(boundary, 0.0001, Colour::from_value(1.0)));
 
-    let emat = Arc::new(Lambertian::from_texture(Arc::new(ImageTexture::new("textures/earthmap.jpg"))));
+    let emat = Arc::new(Lambertian::from_texture(Arc::new(ImageTexture::new("textures/earthmap.jpg").expect("textures/earthmap.jpg should be an RGB image"))));
     object_list::add(&mut world, sphere::new(Point3::new(400.0, 200.0, 400.0), 100.0, emat));
     let pertext = Arc::new(NoiseTexture::new(0.1));
     object_list::add(&mut world, sphere::new(Point3::new(220.0, 280.0, 300.0), 80.0, Arc::new(Lambertian::from_texture(pertext))));
@@ -74,3 +89,11 @@ pub fn build_scene() -> Object {
 
     world2
 }
+
+pub fn build_lights() -> Vec<Object> {
+    vec![light(Arc::new(DiffuseLight::new(Colour::from_value(7.0))))]
+}
+
+fn light(material: Arc<dyn Material>) -> Object {
+    xz_rect::new(123.0, 423.0, 147.0, 412.0, 554.0, material)
+}
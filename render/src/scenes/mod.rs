@@ -9,3 +9,69 @@ pub mod final_scene;
 pub mod wavefront_scene;
 pub mod triangle_scene;
 pub mod scene1;
+
+use std::ops::Range;
+use jrpt::{
+    camera::Camera, colour::Colour, objects::Object, point3::Point3, scene::Scene,
+    scene_file::RenderSettings, vec3::Vec3
+};
+
+/// Builds a scene along with its camera and settings
+pub type Builder = fn() -> BuiltinScene;
+
+/// Built-in scenes by name, in the order they are listed
+pub const SCENES: &[(&str, Builder)] = &[
+    ("two_spheres", two_spheres::build),
+    ("two_perlin_spheres", two_perlin_spheres::build),
+    ("earth", earth::build),
+    ("simple_light", simple_light::build),
+    ("cornell_box", cornell_box::build),
+    ("cornell_smoke", cornell_smoke::build),
+    ("final_scene", final_scene::build),
+    ("random_scene", random_scene::build),
+    ("triangle_scene", triangle_scene::build),
+    ("wavefront_scene", wavefront_scene::build),
+    ("scene1", scene1::build)
+];
+
+/// Builds the built-in scene called name, or returns None if there is no such scene
+pub fn build(name: &str) -> Option<BuiltinScene> {
+    SCENES.iter().find(|(scene, _)| *scene == name).map(|(_, build)| build())
+}
+
+/// Names of the built-in scenes
+pub fn names() -> impl Iterator<Item = &'static str> {
+    SCENES.iter().map(|(name, _)| *name)
+}
+
+/// Built-in scene, along with the settings it is meant to be rendered with
+pub struct BuiltinScene {
+    pub scene: Scene,
+    pub settings: RenderSettings
+}
+
+/// Where a built-in scene's camera is placed, it always looks out level and focuses 10 units away
+pub struct View {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub time: Range<f64>    // shutter interval
+}
+
+impl BuiltinScene {
+    /// Puts objects and lights in front of a camera placed by view, with the aspect ratio of settings' image
+    pub fn new(objects: Object, lights: Vec<Object>, view: View, background_colour: Colour, settings: RenderSettings) -> Self {
+        let aspect_ratio = settings.width as f64 / settings.height as f64;
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        let dist_to_focus = 10.0;
+
+        let camera = Camera::new(view.lookfrom, view.lookat, vup, view.vfov, aspect_ratio, view.aperture, dist_to_focus, view.time);
+        let mut scene = Scene::new(camera, objects, background_colour);
+        for light in lights {
+            scene.add_light(light);
+        }
+
+        Self { scene, settings }
+    }
+}
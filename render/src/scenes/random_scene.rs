@@ -4,7 +4,7 @@ use jrpt::{
     colour::Colour,
     materials::{dialetric::Dialetric, lambertian::Lambertian, metal::Metal},
     objects::{
-        affine::{self},
+        affine::Transform,
         bvh::{self},
         moving_sphere::{self},
         object_list::{self},
@@ -15,9 +15,24 @@ use jrpt::{
     random::{random, random_in_range},
     textures::checker_texture::CheckerTexture,
     vec3::Vec3,
+    scene_file::RenderSettings,
 };
+use super::{BuiltinScene, View};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(13.0, 2.0, 3.0),
+        lookat: Point3::zero(),
+        vfov: 20.0,
+        aperture: 0.1,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 512, height: 288, samples_per_pixel: 300, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::new(0.7, 0.8, 1.0), settings)
+}
+
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
 
@@ -28,11 +43,7 @@ pub fn build_scene() -> Object {
     let ground_material = Arc::new(Lambertian::from_texture(checker));
     // let sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material);
     let sphere = sphere::canonical(ground_material);
-    let mut transform = affine::new(sphere);
-
-    affine::scale_uniform(&mut transform, 1000.0);
-    affine::translate(&mut transform, 0.0, -1000.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().scale_uniform(1000.0).translate(0.0, -1000.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let mut rng = SmallRng::seed_from_u64(1232);
@@ -63,10 +74,7 @@ pub fn build_scene() -> Object {
                     let sphere_material = Arc::new(Metal::new(albedo, fuzz));
 
                     let sphere = sphere::canonical(sphere_material);
-                    let mut transform = affine::new(sphere);
-                    affine::scale_uniform(&mut transform, 0.2);
-                    affine::translate(&mut transform, center.x, center.y, center.z);
-                    affine::set_inverse(&mut transform);
+                    let transform = Transform::new().scale_uniform(0.2).translate(center.x, center.y, center.z).apply(sphere).unwrap();
 
                     // let sphere = Sphere::new(center, 0.2, sphere_material);
                     object_list::add(&mut world, transform);
@@ -76,10 +84,7 @@ pub fn build_scene() -> Object {
                     // let sphere = Sphere::new(center, 0.2, sphere_material);
 
                     let sphere = sphere::canonical(sphere_material);
-                    let mut transform = affine::new(sphere);
-                    affine::scale_uniform(&mut transform, 0.2);
-                    affine::translate(&mut transform, center.x, center.y, center.z);
-                    affine::set_inverse(&mut transform);
+                    let transform = Transform::new().scale_uniform(0.2).translate(center.x, center.y, center.z).apply(sphere).unwrap();
 
                     object_list::add(&mut world, transform);
                 }
@@ -90,25 +95,19 @@ pub fn build_scene() -> Object {
     let material = Arc::new(Dialetric::new(1.5));
     // let sphere = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material);
     let sphere = sphere::canonical(material);
-    let mut transform = affine::new(sphere);
-    affine::translate(&mut transform, 0.0, 1.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().translate(0.0, 1.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let material = Arc::new(Lambertian::new(Colour::new(0.4, 0.2, 0.1)));
     // let sphere = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material);
     let sphere = sphere::canonical(material);
-    let mut transform = affine::new(sphere);
-    affine::translate(&mut transform, -4.0, 1.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().translate(-4.0, 1.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let material = Arc::new(Metal::new(Colour::new(0.7, 0.6, 0.5), 0.0));
     // let sphere = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material);
     let sphere = sphere::canonical(material);
-    let mut transform = affine::new(sphere);
-    affine::translate(&mut transform, 4.0, 1.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().translate(4.0, 1.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let mut world2 = object_list::new();
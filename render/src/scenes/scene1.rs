@@ -10,49 +10,54 @@ use jrpt::{
         }, 
         rect_prism::{RectangularPrism, self},
         Object,
-        affine::{Affine, self}, bvh::{BvhNode, self}, wavefront_obj::new_mesh, sphere::{Sphere, self}, constant_medium::{ConstantMedium, self}
+        affine::{Affine, Transform}, bvh::{BvhNode, self}, wavefront_obj::new_mesh, sphere::{Sphere, self}, constant_medium::{ConstantMedium, self}
     }, 
     materials::{
         lambertian::Lambertian, diffuse_light::DiffuseLight, metal::Metal, dialetric::Dialetric, Material
     }, 
-    colour::Colour, point3::Point3, textures::{Texture, image_texture::ImageTexture, solid_colour::SolidColour}, random
+    colour::Colour, point3::Point3, textures::{Texture, image_texture::ImageTexture, solid_colour::SolidColour}, random,
+    scene_file::RenderSettings
 };
+use super::{BuiltinScene, View};
 use rand::{rngs::SmallRng, SeedableRng, Rng};
 
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(50.0, 5.0, -20.0),
+        lookat: Point3::new(0.0, 0.0, 50.0),
+        vfov: 90.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 2048, height: 1152, samples_per_pixel: 20000, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::zero(), settings)
+}
+
 pub fn build_scene() -> Object {
     let mut rng = SmallRng::seed_from_u64(1);
     let mut world = object_list::new();
 
     // textures
-    let lamp_texture = Arc::new(ImageTexture::new("textures/wood.jpg"));
+    let lamp_texture = Arc::new(ImageTexture::new("textures/wood.jpg").expect("textures/wood.jpg should be an RGB image"));
 
     // materials
     let lamp_mat = Arc::new(Lambertian::from_texture(lamp_texture));
     let light_mat = Arc::new(DiffuseLight::new(Colour::new(1.0, 1.0, 0.8)));
 
     // lamp
-    let obj = new_mesh("meshes/lamp3.obj".to_string(), lamp_mat);
+    let obj = new_mesh("meshes/lamp3.obj".to_string(), lamp_mat).expect("meshes/lamp3.obj should be a mesh");
     let b = bvh::new(obj, 0.0..0.0);
-    let mut transform = affine::new(b);
-    affine::rotate_y(&mut transform, PI*0.2);
-    affine::translate(&mut transform, 5.0, -35.0, -25.0);
-    affine::scale_uniform(&mut transform, 0.5);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().rotate_y(PI*0.2).translate(5.0, -35.0, -25.0).scale_uniform(0.5).apply(b).unwrap();
     object_list::add(&mut world, transform);
 
     // light in lamp
     let sphere = sphere::canonical(light_mat.clone());
-    let mut transform = affine::new(sphere);
-    affine::scale(&mut transform, 1.8, 2.0, 1.75);
-    affine::translate(&mut transform, 5.2, -3.0, -10.1);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().scale(1.8, 2.0, 1.75).translate(5.2, -3.0, -10.1).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let sphere = sphere::canonical(Arc::new(Dialetric::new(1.3)));
-    let mut transform = affine::new(sphere);
-    affine::scale(&mut transform, 1.84, 2.04, 1.754);
-    affine::translate(&mut transform, 5.2, -3.0, -10.1);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().scale(1.84, 2.04, 1.754).translate(5.2, -3.0, -10.1).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     // ceiling light
@@ -88,10 +93,10 @@ pub fn build_scene() -> Object {
             let ground_texture = Arc::new(SolidColour::new(Colour::new(0.4, 0.3, 0.2)));
             let ground_mat = Arc::new(Metal::new(Colour::new(0.5, 0.4, 0.2), 0.0));
             let cube = rect_prism::canonical(ground_mat);
-            let mut transform = affine::new(cube);
-            affine::scale_uniform(&mut transform, size*0.95);
-            affine::translate(&mut transform, i*size, -2.0*size + rng.gen_range((-0.5*size)..(0.5*size)), j * size);
-            affine::set_inverse(&mut transform);
+            let transform = Transform::new()
+                .scale_uniform(size*0.95)
+                .translate(i*size, -2.0*size + rng.gen_range((-0.5*size)..(0.5*size)), j * size)
+                .apply(cube).unwrap();
             object_list::add(&mut world, transform);
         }
     }
@@ -122,10 +127,10 @@ pub fn build_scene() -> Object {
             };
             
             let sphere = sphere::canonical(material);
-            let mut transform = affine::new(sphere);
-            affine::scale_uniform(&mut transform, size* 0.3);
-            affine::translate(&mut transform, i*size, size * 2.5 - 2.0*size + rng.gen_range(0.0..(size)), j * size);
-            affine::set_inverse(&mut transform);
+            let transform = Transform::new()
+                .scale_uniform(size* 0.3)
+                .translate(i*size, size * 2.5 - 2.0*size + rng.gen_range(0.0..(size)), j * size)
+                .apply(sphere).unwrap();
             object_list::add(&mut world, transform);
         }
     }
@@ -2,11 +2,26 @@ use std::sync::Arc;
 
 use jrpt::{
     colour::Colour,
-    materials::{diffuse_light::DiffuseLight, lambertian::Lambertian},
+    materials::{diffuse_light::DiffuseLight, lambertian::Lambertian, Material},
     objects::{object_list, Object, sphere, aa_rectangles::xy_rect},
     point3::Point3,
     textures::noise_texture::NoiseTexture,
+    scene_file::RenderSettings,
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(26.0, 3.0, 6.0),
+        lookat: Point3::new(0.0, 2.0, 0.0),
+        vfov: 20.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 512, height: 288, samples_per_pixel: 300, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), build_lights(), view, Colour::zero(), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
@@ -26,10 +41,15 @@ pub fn build_scene() -> Object {
 
     let difflight = Arc::new(DiffuseLight::new(Colour::from_value(4.0)));
 
-    object_list::add(
-        &mut world,
-        xy_rect::new(3.0, 5.0, 1.0, 3.0, -2.0, difflight)
-    );
+    object_list::add(&mut world, light(difflight));
 
     world
 }
+
+pub fn build_lights() -> Vec<Object> {
+    vec![light(Arc::new(DiffuseLight::new(Colour::from_value(4.0))))]
+}
+
+fn light(material: Arc<dyn Material>) -> Object {
+    xy_rect::new(3.0, 5.0, 1.0, 3.0, -2.0, material)
+}
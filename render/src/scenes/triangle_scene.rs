@@ -10,7 +10,22 @@ use jrpt::{
     },
     point3::Point3,
     textures::checker_texture::CheckerTexture,
+    scene_file::RenderSettings,
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(0.0, 0.0, -10.0),
+        lookat: Point3::zero(),
+        vfov: 30.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 512, height: 288, samples_per_pixel: 300, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::new(0.7, 0.8, 1.0), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
@@ -3,9 +3,24 @@ use std::sync::Arc;
 use jrpt::{
     colour::Colour,
     materials::lambertian::Lambertian,
-    objects::{affine, object_list, sphere, Object},
+    objects::{affine::Transform, object_list, sphere, Object},
     textures::checker_texture::CheckerTexture,
+    scene_file::RenderSettings, point3::Point3,
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(13.0, 2.0, 3.0),
+        lookat: Point3::zero(),
+        vfov: 20.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 512, height: 288, samples_per_pixel: 300, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::new(0.7, 0.8, 1.0), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
@@ -17,18 +32,11 @@ pub fn build_scene() -> Object {
     let mat = Arc::new(Lambertian::from_texture(checker));
 
     let sphere = sphere::canonical(mat.clone());
-    let mut transform = affine::new(sphere);
-
-    affine::scale_uniform(&mut transform, 10.0);
-    affine::translate(&mut transform, 0.0, -10.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().scale_uniform(10.0).translate(0.0, -10.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     let sphere = sphere::canonical(mat.clone());
-    let mut transform = affine::new(sphere);
-    affine::scale_uniform(&mut transform, 10.0);
-    affine::translate(&mut transform, 0.0, 10.0, 0.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().scale_uniform(10.0).translate(0.0, 10.0, 0.0).apply(sphere).unwrap();
     object_list::add(&mut world, transform);
 
     world
@@ -9,7 +9,7 @@ use jrpt::{
             xz_rect::{self},
             yz_rect::{self},
         },
-        affine::{self},
+        affine::Transform,
         bvh::{self},
         object_list::{self},
         rect_prism::{self},
@@ -17,7 +17,22 @@ use jrpt::{
         Object,
     },
     point3::Point3,
+    scene_file::RenderSettings,
 };
+use super::{BuiltinScene, View};
+
+pub fn build() -> BuiltinScene {
+    let view = View {
+        lookfrom: Point3::new(278.0, 278.0, -800.0),
+        lookat: Point3::new(278.0, 278.0, 0.0),
+        vfov: 40.0,
+        aperture: 0.0,
+        time: 0.0..0.0
+    };
+    let settings = RenderSettings { width: 1024, height: 1024, samples_per_pixel: 10000, ..RenderSettings::default() };
+
+    BuiltinScene::new(build_scene(), vec![], view, Colour::zero(), settings)
+}
 
 pub fn build_scene() -> Object {
     let mut world = object_list::new();
@@ -56,25 +71,22 @@ pub fn build_scene() -> Object {
         Point3::new(165.0, 330.0, 165.0),
         white.clone(),
     );
-    let mut transform = affine::new(b);
-    affine::rotate_y(&mut transform, (15.0 as f64).to_radians());
-    affine::translate(&mut transform, 265.0, 0.0, 295.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new().rotate_y((15.0 as f64).to_radians()).translate(265.0, 0.0, 295.0).apply(b).unwrap();
     object_list::add(&mut world, transform);
 
     // objects
     let monke_material = Arc::new(Metal::new(Colour::new(0.8, 0.4, 0.2), 0.5));
-    let obj = new_mesh("meshes/monke.obj".to_string(), monke_material);
+    let obj = new_mesh("meshes/monke.obj".to_string(), monke_material).expect("meshes/monke.obj should be a mesh");
     let b = bvh::new(obj, 0.0..0.0);
 
-    let mut transform = affine::new(b);
-    affine::scale_uniform(&mut transform, 100.0);
-    affine::rotate_y(&mut transform, 3.4 * PI / 4.0);
-    affine::rotate_x(&mut transform, PI / 5.0 * 0.99);
-    affine::rotate_z(&mut transform, PI * 0.1);
-    affine::rotate_y(&mut transform, -PI * 0.05);
-    affine::translate(&mut transform, 160.0, 42.0, 200.0);
-    affine::set_inverse(&mut transform);
+    let transform = Transform::new()
+        .scale_uniform(100.0)
+        .rotate_y(3.4 * PI / 4.0)
+        .rotate_x(PI / 5.0 * 0.99)
+        .rotate_z(PI * 0.1)
+        .rotate_y(-PI * 0.05)
+        .translate(160.0, 42.0, 200.0)
+        .apply(b).unwrap();
 
     object_list::add(&mut world, transform);
 